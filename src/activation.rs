@@ -0,0 +1,497 @@
+//! Deciding when KTMM should keep the system awake
+//!
+//! An [`ActivationSource`] says whether keeping awake is wanted at a given
+//! time and when that next changes. The configured weekly windows, cron
+//! expressions and calendar file are each a source, and [`Activation`]
+//! combines them: keeping awake is wanted while any of them says so, or
+//! always if none is configured. The mouse mover and the inhibitor loop
+//! pause and resume on the combined answer.
+
+use chrono::{DateTime, Local, TimeDelta, TimeZone};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::calendar::Calendar;
+use crate::cron::CronExpr;
+use crate::schedule::Schedule;
+use crate::{KtmmError, MouseMoverConfig};
+
+/// The longest to wait before asking the sources again, in case the clock
+/// is changed, the system sleeps through a change or a calendar file is edited
+pub const RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How far ahead to look for the combined answer to change
+const SEARCH_STEPS: usize = 64;
+
+/// How far around a query calendar occurrences must be known: far enough
+/// back to find events that are still running and ahead to find the next one
+const CALENDAR_REACH: TimeDelta = TimeDelta::days(366);
+
+/// How far around a query calendar occurrences are expanded, more than
+/// [`CALENDAR_REACH`] so later queries can reuse them
+const CALENDAR_EXPANSION: TimeDelta = TimeDelta::days(400);
+
+/// Something that decides when to keep the system awake
+pub trait ActivationSource<Tz: TimeZone> {
+    /// Whether keeping awake is wanted at `now`
+    fn is_active_at(&mut self, now: &DateTime<Tz>) -> bool;
+
+    /// The next time after `now` at which [`is_active_at`](Self::is_active_at)
+    /// may change; `None` if it is not known to change
+    fn next_change(&mut self, now: &DateTime<Tz>) -> Option<DateTime<Tz>>;
+
+    /// A short description for status output, e.g. `Mon-Fri 09:00-18:00`
+    fn describe(&self) -> String;
+
+    /// A problem the source ran into since it was last asked, such as a
+    /// calendar file that can no longer be read; the source keeps answering
+    /// as it did before
+    fn take_error(&mut self) -> Option<KtmmError> {
+        None
+    }
+}
+
+impl<Tz: TimeZone> ActivationSource<Tz> for Schedule {
+    fn is_active_at(&mut self, now: &DateTime<Tz>) -> bool {
+        Schedule::is_active_at(self, now)
+    }
+
+    fn next_change(&mut self, now: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        Schedule::next_change(self, now)
+    }
+
+    fn describe(&self) -> String {
+        self.windows
+            .iter()
+            .map(|window| format!("window {}", window))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl<Tz: TimeZone> ActivationSource<Tz> for CronExpr {
+    fn is_active_at(&mut self, now: &DateTime<Tz>) -> bool {
+        CronExpr::is_active_at(self, now)
+    }
+
+    fn next_change(&mut self, now: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        CronExpr::next_change(self, now)
+    }
+
+    fn describe(&self) -> String {
+        format!("cron {}", self)
+    }
+}
+
+/// The events of a calendar file, re-read whenever the file changes
+#[derive(Debug)]
+pub struct CalendarSource<Tz: TimeZone = Local> {
+    path: PathBuf,
+    tag: Option<String>,
+    calendar: Calendar,
+    modified: Option<SystemTime>,
+    /// The occurrences within `spans_range`, kept until the file changes
+    spans: Vec<(DateTime<Tz>, DateTime<Tz>)>,
+    spans_range: Option<(DateTime<Tz>, DateTime<Tz>)>,
+    /// The last refresh error, so it is only reported once
+    reported: Option<String>,
+    error: Option<KtmmError>,
+}
+
+impl<Tz: TimeZone> CalendarSource<Tz> {
+    /// Read the calendar at `path`, using only events tagged `tag` if given
+    pub fn open(path: &Path, tag: Option<&str>) -> Result<Self, KtmmError> {
+        let mut source = Self {
+            path: path.to_path_buf(),
+            tag: tag.map(str::to_string),
+            calendar: Calendar::default(),
+            modified: None,
+            spans: Vec::new(),
+            spans_range: None,
+            reported: None,
+            error: None,
+        };
+        source.reload()?;
+        Ok(source)
+    }
+
+    /// The events as last read
+    pub fn calendar(&self) -> &Calendar {
+        &self.calendar
+    }
+
+    fn reload(&mut self) -> Result<(), KtmmError> {
+        let error =
+            |e: String| KtmmError::ConfigError(format!("calendar {}: {}", self.path.display(), e));
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|e| error(e.to_string()))?;
+        let text = std::fs::read_to_string(&self.path).map_err(|e| error(e.to_string()))?;
+        self.calendar = Calendar::parse(&text).map_err(error)?;
+        self.modified = Some(modified);
+        self.spans_range = None;
+        Ok(())
+    }
+
+    /// Re-read the file if it was modified since it was last read
+    ///
+    /// Keeps the previous events if the file cannot be read or parsed, so a
+    /// half-saved file does not end a calendar event early.
+    pub fn refresh(&mut self) -> Result<bool, KtmmError> {
+        let modified = std::fs::metadata(&self.path).and_then(|metadata| metadata.modified());
+        match modified {
+            Ok(modified) if Some(modified) == self.modified => Ok(false),
+            Ok(modified) => {
+                // Only retry a broken file once it changes again
+                self.modified = Some(modified);
+                self.reload().map(|()| true)
+            }
+            Err(e) => Err(KtmmError::ConfigError(format!(
+                "calendar {}: {}",
+                self.path.display(),
+                e
+            ))),
+        }
+    }
+
+    /// Refresh, keeping each new error for [`take_error`](ActivationSource::take_error)
+    fn refresh_quietly(&mut self) {
+        match self.refresh() {
+            Ok(_) => self.reported = None,
+            Err(e) => {
+                let message = e.to_string();
+                if self.reported.as_ref() != Some(&message) {
+                    self.error = Some(KtmmError::ConfigError(format!(
+                        "{}; keeping the events read before",
+                        message
+                    )));
+                    self.reported = Some(message);
+                }
+            }
+        }
+    }
+
+    /// The occurrences around `now`, expanded again only when the file has
+    /// changed or `now` is too close to the edge of the range last expanded
+    fn spans(&mut self, now: &DateTime<Tz>) -> &[(DateTime<Tz>, DateTime<Tz>)] {
+        let current = self.spans_range.as_ref().is_some_and(|(from, to)| {
+            *from <= now.clone() - CALENDAR_REACH && now.clone() + CALENDAR_REACH <= *to
+        });
+        if !current {
+            let from = now.clone() - CALENDAR_EXPANSION;
+            let to = now.clone() + CALENDAR_EXPANSION;
+            self.spans = self.calendar.spans(self.tag.as_deref(), &from, &to);
+            self.spans_range = Some((from, to));
+        }
+        &self.spans
+    }
+}
+
+impl<Tz: TimeZone> ActivationSource<Tz> for CalendarSource<Tz> {
+    fn is_active_at(&mut self, now: &DateTime<Tz>) -> bool {
+        self.refresh_quietly();
+        self.spans(now)
+            .iter()
+            .any(|(start, end)| start <= now && now < end)
+    }
+
+    fn next_change(&mut self, now: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        self.refresh_quietly();
+        let spans = self.spans(now);
+        let active = spans.iter().any(|(start, end)| start <= now && now < end);
+        next_transition(spans, now, !active)
+    }
+
+    fn describe(&self) -> String {
+        let events = match self.calendar.len() {
+            1 => "1 event".to_string(),
+            n => format!("{} events", n),
+        };
+        match &self.tag {
+            Some(tag) => format!(
+                "calendar {} ({}, using those tagged `{}`)",
+                self.path.display(),
+                events,
+                tag
+            ),
+            None => format!("calendar {} ({})", self.path.display(), events),
+        }
+    }
+
+    fn take_error(&mut self) -> Option<KtmmError> {
+        self.error.take()
+    }
+}
+
+/// The first boundary of `spans` after `now` at which being inside any span
+/// becomes `to_active`
+pub(crate) fn next_transition<Tz: TimeZone>(
+    spans: &[(DateTime<Tz>, DateTime<Tz>)],
+    now: &DateTime<Tz>,
+    to_active: bool,
+) -> Option<DateTime<Tz>> {
+    let mut boundaries: Vec<DateTime<Tz>> = spans
+        .iter()
+        .flat_map(|(start, end)| [start.clone(), end.clone()])
+        .filter(|boundary| boundary > now)
+        .collect();
+    boundaries.sort();
+    boundaries.dedup();
+
+    let active_at =
+        |time: &DateTime<Tz>| spans.iter().any(|(start, end)| start <= time && time < end);
+    let mut active = active_at(now);
+    for boundary in boundaries {
+        let next = active_at(&boundary);
+        if next != active && next == to_active {
+            return Some(boundary);
+        }
+        active = next;
+    }
+    None
+}
+
+/// All configured activation sources combined
+pub struct Activation<Tz: TimeZone = Local> {
+    sources: Vec<Box<dyn ActivationSource<Tz> + Send>>,
+}
+
+impl<Tz: TimeZone> Default for Activation<Tz> {
+    fn default() -> Self {
+        Self {
+            sources: Vec::new(),
+        }
+    }
+}
+
+impl Activation<Local> {
+    /// The sources configured by the schedule, cron and calendar settings
+    pub fn from_config(config: &MouseMoverConfig) -> Result<Self, KtmmError> {
+        let mut activation = Self::default();
+        if !config.schedule.is_empty() {
+            activation.add(config.schedule.clone());
+        }
+        for expr in &config.cron {
+            activation.add(expr.clone());
+        }
+        if let Some(path) = &config.calendar {
+            activation.add(CalendarSource::open(path, config.calendar_tag.as_deref())?);
+        }
+        Ok(activation)
+    }
+}
+
+impl<Tz: TimeZone> Activation<Tz> {
+    /// Add a source; keeping awake is wanted while any source wants it
+    pub fn add(&mut self, source: impl ActivationSource<Tz> + Send + 'static) {
+        self.sources.push(Box::new(source));
+    }
+
+    /// Whether there are no sources, so keeping awake is always wanted
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Whether keeping awake is wanted at `now`
+    pub fn is_active_at(&mut self, now: &DateTime<Tz>) -> bool {
+        self.is_empty()
+            || self
+                .sources
+                .iter_mut()
+                .any(|source| source.is_active_at(now))
+    }
+
+    /// The next time after `now` at which the combined answer becomes `to_active`
+    fn next_transition(&mut self, now: &DateTime<Tz>, to_active: bool) -> Option<DateTime<Tz>> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut time = now.clone();
+        let mut active = self.is_active_at(now);
+        // Sources can change without changing the combined answer, so step
+        // through their changes until it does
+        for _ in 0..SEARCH_STEPS {
+            time = self
+                .sources
+                .iter_mut()
+                .filter_map(|source| source.next_change(&time))
+                .min()?;
+            let next = self.is_active_at(&time);
+            if next != active && next == to_active {
+                return Some(time);
+            }
+            active = next;
+        }
+        None
+    }
+
+    /// The next time after `now` at which keeping awake starts being wanted
+    pub fn next_activation(&mut self, now: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        self.next_transition(now, true)
+    }
+
+    /// The next time after `now` at which keeping awake stops being wanted
+    pub fn next_deactivation(&mut self, now: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        self.next_transition(now, false)
+    }
+
+    /// The next time after `now` at which the combined answer changes
+    pub fn next_change(&mut self, now: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let active = self.is_active_at(now);
+        self.next_transition(now, !active)
+    }
+
+    /// How long to wait after `now` before asking again
+    ///
+    /// This is the time to the next change, but at most
+    /// [`RECHECK_INTERVAL`]; `None` without any sources.
+    pub fn recheck_in(&mut self, now: &DateTime<Tz>) -> Option<Duration> {
+        if self.is_empty() {
+            return None;
+        }
+        let until_change = self
+            .next_change(now)
+            .and_then(|change| (change - now.clone()).to_std().ok())
+            .unwrap_or(RECHECK_INTERVAL);
+        Some(until_change.min(RECHECK_INTERVAL))
+    }
+
+    /// The problems the sources ran into since last asked
+    pub fn take_errors(&mut self) -> Vec<KtmmError> {
+        self.sources
+            .iter_mut()
+            .filter_map(|source| source.take_error())
+            .collect()
+    }
+
+    /// Describe each source on its own line
+    pub fn describe(&self) -> Vec<String> {
+        self.sources
+            .iter()
+            .flat_map(|source| {
+                source
+                    .describe()
+                    .lines()
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+    use std::fs;
+
+    fn tz() -> FixedOffset {
+        FixedOffset::east_opt(0).unwrap()
+    }
+
+    #[test]
+    fn test_sources_combine() {
+        let mut activation = Activation::default();
+        activation.add("Mon-Fri 09:00-12:00".parse::<Schedule>().unwrap());
+        activation.add("* 12-13 * * Mon-Fri".parse::<CronExpr>().unwrap());
+
+        // 2024-05-10 is a Friday; the window and the cron hours meet at noon
+        let morning = tz().with_ymd_and_hms(2024, 5, 10, 10, 0, 0).unwrap();
+        assert!(activation.is_active_at(&morning));
+        assert_eq!(
+            activation.next_deactivation(&morning),
+            tz().with_ymd_and_hms(2024, 5, 10, 14, 0, 0).single()
+        );
+        assert_eq!(
+            activation.next_activation(&morning),
+            tz().with_ymd_and_hms(2024, 5, 13, 9, 0, 0).single()
+        );
+        assert!(activation.recheck_in(&morning).unwrap() <= RECHECK_INTERVAL);
+
+        let mut always = Activation::default();
+        assert!(always.is_active_at(&morning));
+        assert_eq!(always.next_change(&morning), None);
+        assert_eq!(always.recheck_in(&morning), None);
+    }
+
+    #[test]
+    fn test_calendar_is_reread_when_it_changes() {
+        let dir = std::env::temp_dir().join(format!("ktmm-calendar-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("work.ics");
+        let event = |summary: &str| {
+            format!(
+                "BEGIN:VCALENDAR\nBEGIN:VEVENT\nSUMMARY:{}\nDTSTART:20240510T140000Z\n\
+                 DTEND:20240510T150000Z\nEND:VEVENT\nEND:VCALENDAR\n",
+                summary
+            )
+        };
+        fs::write(&path, event("Team demo")).unwrap();
+
+        let mut source = CalendarSource::open(&path, Some("demo")).unwrap();
+        let during = tz().with_ymd_and_hms(2024, 5, 10, 14, 30, 0).unwrap();
+        assert!(source.is_active_at(&during));
+
+        // Make sure the modification time differs even on coarse filesystems
+        fs::write(&path, event("Team retro")).unwrap();
+        let later = SystemTime::now() + Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(!source.is_active_at(&during));
+
+        // A broken file keeps the events read before
+        fs::write(&path, "garbage").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later + Duration::from_secs(5))
+            .unwrap();
+        assert!(source.refresh().is_err());
+        assert_eq!(source.calendar().len(), 1);
+
+        // Errors are left to the caller to report, once each
+        fs::remove_file(&path).unwrap();
+        assert!(!source.is_active_at(&during));
+        assert!(source.take_error().is_some());
+        assert!(!source.is_active_at(&during));
+        assert!(source.take_error().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_calendar_answers_queries_out_of_order() {
+        let dir = std::env::temp_dir().join(format!("ktmm-calendar-order-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("work.ics");
+        fs::write(
+            &path,
+            "BEGIN:VCALENDAR\nBEGIN:VEVENT\nSUMMARY:Demo\nDTSTART:20240510T140000Z\n\
+             DTEND:20240510T150000Z\nEND:VEVENT\nBEGIN:VEVENT\nSUMMARY:Demo\n\
+             DTSTART:20250510T140000Z\nDTEND:20250510T150000Z\nEND:VEVENT\nEND:VCALENDAR\n",
+        )
+        .unwrap();
+
+        let mut source = CalendarSource::open(&path, None).unwrap();
+        let first = tz().with_ymd_and_hms(2024, 5, 10, 14, 30, 0).unwrap();
+        let before = tz().with_ymd_and_hms(2024, 5, 10, 13, 0, 0).unwrap();
+        let second = tz().with_ymd_and_hms(2025, 5, 10, 14, 30, 0).unwrap();
+        assert!(source.is_active_at(&first));
+        // Earlier than the last query, but within the range already expanded
+        assert!(!source.is_active_at(&before));
+        assert_eq!(
+            source.next_change(&before),
+            tz().with_ymd_and_hms(2024, 5, 10, 14, 0, 0).single()
+        );
+        // A year later, beyond what a query in 2024 needed
+        assert!(source.is_active_at(&second));
+        assert!(source.is_active_at(&first));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
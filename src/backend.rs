@@ -0,0 +1,384 @@
+//! Input backends used by [`MouseMover`](crate::MouseMover)
+//!
+//! A backend is the only part of KTMM that talks to the operating system's
+//! input APIs. The default [`EnigoBackend`] uses `enigo` to generate input and
+//! `device_query` to read the pointer position; [`RecordingBackend`] keeps
+//! everything in memory so the mover logic can be exercised in tests and dry
+//! runs without a display.
+
+use device_query::{DeviceQuery, DeviceState};
+use enigo::{Enigo, KeyboardControllable, MouseControllable};
+use serde::Deserialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::geometry::Rect;
+use crate::{platform, KtmmError};
+
+pub use enigo::Key;
+
+/// Which way a scroll wheel turns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScrollAxis {
+    /// Up and down
+    #[default]
+    Vertical,
+    /// Left and right
+    Horizontal,
+}
+
+impl std::str::FromStr for ScrollAxis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "vertical" => Ok(ScrollAxis::Vertical),
+            "horizontal" => Ok(ScrollAxis::Horizontal),
+            _ => Err("expected `vertical` or `horizontal`".to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for ScrollAxis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScrollAxis::Vertical => write!(f, "vertical"),
+            ScrollAxis::Horizontal => write!(f, "horizontal"),
+        }
+    }
+}
+
+/// Low-level input operations needed to keep the system awake
+pub trait InputBackend {
+    /// Get the current pointer position in screen coordinates
+    fn mouse_location(&mut self) -> Result<(i32, i32), KtmmError>;
+
+    /// Move the pointer to an absolute screen position
+    fn mouse_move_to(&mut self, x: i32, y: i32) -> Result<(), KtmmError>;
+
+    /// Move the pointer relative to its current position
+    fn mouse_move_relative(&mut self, dx: i32, dy: i32) -> Result<(), KtmmError>;
+
+    /// Whether the pointer position can be read and set absolutely
+    ///
+    /// Backends that return `false` are only driven with relative moves.
+    fn supports_absolute_positioning(&self) -> bool {
+        true
+    }
+
+    /// The monitors' areas in screen coordinates, or an empty list if unknown
+    fn monitors(&mut self) -> Vec<Rect> {
+        Vec::new()
+    }
+
+    /// Check that the backend is allowed to generate input
+    fn check_permissions(&self) -> Result<(), KtmmError> {
+        platform::check_accessibility_permissions()
+    }
+
+    /// Time since the last user input according to the platform, if it keeps count
+    ///
+    /// This includes input generated by KTMM itself.
+    fn idle_time(&mut self) -> Option<Duration> {
+        None
+    }
+
+    /// Whether any key is currently held down
+    ///
+    /// Used to detect activity when [`idle_time`](Self::idle_time) is not
+    /// available; backends that cannot tell report `false`.
+    fn keys_held(&mut self) -> bool {
+        false
+    }
+
+    /// Reset the system idle timer without any visible pointer movement
+    ///
+    /// Returns the name of the mechanism used. Backends and platforms that
+    /// cannot do this return [`KtmmError::Unsupported`], and the mover falls
+    /// back to a pointer nudge.
+    fn reset_idle_timer(&mut self) -> Result<&'static str, KtmmError> {
+        Err(KtmmError::Unsupported(
+            "resetting the idle timer directly is not supported by this input backend".to_string(),
+        ))
+    }
+
+    /// Turn the scroll wheel by `notches`, down or right when positive
+    ///
+    /// Scrolling is optional; backends that cannot scroll keep this default
+    /// implementation.
+    fn scroll(&mut self, axis: ScrollAxis, notches: i32) -> Result<(), KtmmError> {
+        Err(KtmmError::Unsupported(format!(
+            "{} scrolling by {} is not supported by this input backend",
+            axis, notches
+        )))
+    }
+
+    /// Press and release a key
+    ///
+    /// Keyboard support is optional; backends that cannot generate key events
+    /// keep this default implementation.
+    fn key_click(&mut self, key: Key) -> Result<(), KtmmError> {
+        Err(KtmmError::Unsupported(format!(
+            "key events ({:?}) are not supported by this input backend",
+            key
+        )))
+    }
+}
+
+/// The default backend, built on `enigo` and `device_query`
+pub struct EnigoBackend {
+    enigo: Enigo,
+    device_state: DeviceState,
+    display: platform::Display,
+}
+
+impl EnigoBackend {
+    /// Create a new backend connected to the current display
+    pub fn new() -> Self {
+        Self {
+            enigo: Enigo::new(),
+            device_state: DeviceState::new(),
+            display: platform::Display::new(),
+        }
+    }
+}
+
+impl Default for EnigoBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputBackend for EnigoBackend {
+    fn mouse_location(&mut self) -> Result<(i32, i32), KtmmError> {
+        Ok(self.device_state.get_mouse().coords)
+    }
+
+    fn mouse_move_to(&mut self, x: i32, y: i32) -> Result<(), KtmmError> {
+        self.enigo.mouse_move_to(x, y);
+        Ok(())
+    }
+
+    fn mouse_move_relative(&mut self, dx: i32, dy: i32) -> Result<(), KtmmError> {
+        self.enigo.mouse_move_relative(dx, dy);
+        Ok(())
+    }
+
+    fn monitors(&mut self) -> Vec<Rect> {
+        if let Some(monitors) = self.display.monitors() {
+            return monitors;
+        }
+        // Fall back to the main display on its own
+        match self.enigo.main_display_size() {
+            (width, height) if width > 0 && height > 0 => vec![Rect::new(0, 0, width, height)],
+            _ => Vec::new(),
+        }
+    }
+
+    fn idle_time(&mut self) -> Option<Duration> {
+        self.display.idle_time()
+    }
+
+    fn keys_held(&mut self) -> bool {
+        !self.device_state.get_keys().is_empty()
+    }
+
+    fn reset_idle_timer(&mut self) -> Result<&'static str, KtmmError> {
+        self.display.reset_idle_timer().ok_or_else(|| {
+            KtmmError::Unsupported(
+                "this platform or display server cannot reset the idle timer directly".to_string(),
+            )
+        })
+    }
+
+    fn scroll(&mut self, axis: ScrollAxis, notches: i32) -> Result<(), KtmmError> {
+        match axis {
+            ScrollAxis::Vertical => self.enigo.mouse_scroll_y(notches),
+            ScrollAxis::Horizontal => self.enigo.mouse_scroll_x(notches),
+        }
+        Ok(())
+    }
+
+    fn key_click(&mut self, key: Key) -> Result<(), KtmmError> {
+        self.enigo.key_click(key);
+        Ok(())
+    }
+}
+
+/// An input operation captured by [`RecordingBackend`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    /// Absolute pointer move
+    MoveTo(i32, i32),
+    /// Relative pointer move
+    MoveRelative(i32, i32),
+    /// Scroll wheel turn by a number of notches
+    Scroll(ScrollAxis, i32),
+    /// Idle timer reset without pointer movement
+    ResetIdleTimer,
+    /// Key press and release
+    KeyClick(Key),
+}
+
+#[derive(Debug, Default)]
+struct RecordingState {
+    position: (i32, i32),
+    events: Vec<InputEvent>,
+    positions: Vec<(i32, i32)>,
+    idle_time: Option<Duration>,
+    keys_held: bool,
+    user_move: Option<(i32, i32)>,
+    monitors: Vec<Rect>,
+    no_idle_reset: bool,
+}
+
+impl RecordingState {
+    fn apply_user_move(&mut self) {
+        if let Some(position) = self.user_move.take() {
+            self.position = position;
+        }
+    }
+}
+
+/// An in-memory backend that records every operation instead of performing it
+///
+/// Clones share the same state, so a test can keep one clone for inspection
+/// while the mover owns the other.
+#[derive(Debug, Clone, Default)]
+pub struct RecordingBackend {
+    state: Arc<Mutex<RecordingState>>,
+}
+
+impl RecordingBackend {
+    /// Create a recording backend with the pointer at the given position
+    pub fn new(position: (i32, i32)) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(RecordingState {
+                position,
+                ..RecordingState::default()
+            })),
+        }
+    }
+
+    /// Current simulated pointer position
+    pub fn position(&self) -> (i32, i32) {
+        self.lock().position
+    }
+
+    /// Move the simulated pointer without recording an event, as a user would
+    pub fn set_position(&self, x: i32, y: i32) {
+        self.lock().position = (x, y);
+    }
+
+    /// Simulate the user grabbing the pointer right after the next recorded
+    /// movement, leaving it at the given position
+    pub fn interrupt_next_move(&self, x: i32, y: i32) {
+        self.lock().user_move = Some((x, y));
+    }
+
+    /// Set the monitor layout reported to the mover
+    pub fn set_monitors(&self, monitors: Vec<Rect>) {
+        self.lock().monitors = monitors;
+    }
+
+    /// Set the idle time reported as the platform's idle counter
+    pub fn set_idle_time(&self, idle: Option<Duration>) {
+        self.lock().idle_time = idle;
+    }
+
+    /// Make [`reset_idle_timer`](InputBackend::reset_idle_timer) report
+    /// whether it is supported
+    pub fn set_idle_reset_supported(&self, supported: bool) {
+        self.lock().no_idle_reset = !supported;
+    }
+
+    /// Simulate the user holding down (or releasing) keys
+    pub fn set_keys_held(&self, held: bool) {
+        self.lock().keys_held = held;
+    }
+
+    /// All operations recorded so far
+    pub fn events(&self) -> Vec<InputEvent> {
+        self.lock().events.clone()
+    }
+
+    /// Pointer positions after each recorded pointer movement
+    pub fn positions(&self) -> Vec<(i32, i32)> {
+        self.lock().positions.clone()
+    }
+
+    /// Forget all recorded operations
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.events.clear();
+        state.positions.clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RecordingState> {
+        // A panic while holding the lock cannot leave the state inconsistent
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl InputBackend for RecordingBackend {
+    fn check_permissions(&self) -> Result<(), KtmmError> {
+        Ok(())
+    }
+
+    fn mouse_location(&mut self) -> Result<(i32, i32), KtmmError> {
+        Ok(self.position())
+    }
+
+    fn mouse_move_to(&mut self, x: i32, y: i32) -> Result<(), KtmmError> {
+        let mut state = self.lock();
+        state.position = (x, y);
+        state.events.push(InputEvent::MoveTo(x, y));
+        state.positions.push((x, y));
+        state.apply_user_move();
+        Ok(())
+    }
+
+    fn mouse_move_relative(&mut self, dx: i32, dy: i32) -> Result<(), KtmmError> {
+        let mut state = self.lock();
+        state.position = (state.position.0 + dx, state.position.1 + dy);
+        state.events.push(InputEvent::MoveRelative(dx, dy));
+        let position = state.position;
+        state.positions.push(position);
+        state.apply_user_move();
+        Ok(())
+    }
+
+    fn monitors(&mut self) -> Vec<Rect> {
+        self.lock().monitors.clone()
+    }
+
+    fn idle_time(&mut self) -> Option<Duration> {
+        self.lock().idle_time
+    }
+
+    fn keys_held(&mut self) -> bool {
+        self.lock().keys_held
+    }
+
+    fn reset_idle_timer(&mut self) -> Result<&'static str, KtmmError> {
+        let mut state = self.lock();
+        if state.no_idle_reset {
+            return Err(KtmmError::Unsupported(
+                "idle timer resets are disabled".to_string(),
+            ));
+        }
+        state.events.push(InputEvent::ResetIdleTimer);
+        Ok("recording")
+    }
+
+    fn scroll(&mut self, axis: ScrollAxis, notches: i32) -> Result<(), KtmmError> {
+        self.lock().events.push(InputEvent::Scroll(axis, notches));
+        Ok(())
+    }
+
+    fn key_click(&mut self, key: Key) -> Result<(), KtmmError> {
+        self.lock().events.push(InputEvent::KeyClick(key));
+        Ok(())
+    }
+}
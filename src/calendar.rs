@@ -0,0 +1,751 @@
+//! Keep-awake windows from the events of an iCalendar (`.ics`) file
+//!
+//! Only what is needed to know when events take place is read: `DTSTART`,
+//! `DTEND` or `DURATION`, `RRULE`, `EXDATE` and `RECURRENCE-ID`, plus the
+//! `SUMMARY` and `CATEGORIES` used to select events by tag. Recurrence rules
+//! may use `FREQ`, `INTERVAL`, `COUNT`, `UNTIL` and, for daily and weekly
+//! rules, `BYDAY` without ordinals; events with other rules are skipped and
+//! listed by [`Calendar::skipped`].
+//!
+//! Times with a `TZID` are read in that IANA time zone, `Z` times in UTC and
+//! all other times (including all-day events) in local time.
+
+use chrono::{
+    DateTime, Datelike, Days, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone,
+    Utc, Weekday,
+};
+use std::collections::HashMap;
+
+use crate::timespec::resolve_local;
+
+/// Upper bound on the occurrences generated per event, against runaway rules
+const MAX_OCCURRENCES: usize = 100_000;
+
+/// The time zone a time in the calendar is written in
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Zone {
+    /// Local time wherever KTMM runs
+    Floating,
+    Utc,
+    Named(chrono_tz::Tz),
+}
+
+/// A date and time as written in the calendar
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct EventTime {
+    local: NaiveDateTime,
+    zone: Zone,
+}
+
+impl EventTime {
+    /// The same wall-clock time on another date, in the same zone
+    fn with_local(self, local: NaiveDateTime) -> Self {
+        Self { local, ..self }
+    }
+
+    /// The real instant this time refers to, in the time zone `tz`
+    fn resolve<Tz: TimeZone>(&self, tz: &Tz) -> Option<DateTime<Tz>> {
+        match self.zone {
+            Zone::Floating => resolve_local(tz, self.local),
+            Zone::Utc => Some(Utc.from_utc_datetime(&self.local).with_timezone(tz)),
+            Zone::Named(zone) => {
+                resolve_local(&zone, self.local).map(|time| time.with_timezone(tz))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// A supported `RRULE`
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<usize>,
+    until: Option<EventTime>,
+    /// Days of the week, indexed from Monday; `None` when not restricted
+    by_day: Option<[bool; 7]>,
+}
+
+/// One `VEVENT`
+#[derive(Debug, Clone, PartialEq)]
+struct Event {
+    uid: Option<String>,
+    summary: String,
+    categories: Vec<String>,
+    start: EventTime,
+    length: TimeDelta,
+    rule: Option<Rule>,
+    exceptions: Vec<EventTime>,
+    /// The occurrence of a recurring event that this event replaces
+    recurrence_id: Option<EventTime>,
+}
+
+impl Event {
+    /// Whether the event carries `tag` as a category or a word of its summary
+    fn has_tag(&self, tag: &str) -> bool {
+        self.categories
+            .iter()
+            .any(|category| category.eq_ignore_ascii_case(tag))
+            || self
+                .summary
+                .split(|c: char| !c.is_alphanumeric() && c != '-' && c != '_')
+                .any(|word| word.eq_ignore_ascii_case(tag))
+    }
+
+    /// The local start times of every occurrence up to `until`, in order
+    fn starts(&self, until: NaiveDateTime) -> Vec<NaiveDateTime> {
+        let Some(rule) = &self.rule else {
+            return vec![self.start.local];
+        };
+        let first = self.start.local;
+        let interval = rule.interval.max(1);
+        let allowed = |date: NaiveDate| {
+            rule.by_day
+                .is_none_or(|days| days[date.weekday().num_days_from_monday() as usize])
+        };
+
+        let mut starts = Vec::new();
+        let emit = |start: NaiveDateTime, starts: &mut Vec<NaiveDateTime>| -> bool {
+            if start > until
+                || starts.len() >= MAX_OCCURRENCES
+                || rule.count.is_some_and(|count| starts.len() >= count)
+                || rule
+                    .until
+                    .is_some_and(|end| end.zone == self.start.zone && start > end.local)
+            {
+                return false;
+            }
+            starts.push(start);
+            true
+        };
+
+        match rule.frequency {
+            Frequency::Daily => {
+                for step in 0.. {
+                    let Some(date) = first
+                        .date()
+                        .checked_add_days(Days::new(step * interval as u64))
+                    else {
+                        break;
+                    };
+                    let start = date.and_time(first.time());
+                    if start > until || (allowed(date) && !emit(start, &mut starts)) {
+                        break;
+                    }
+                }
+            }
+            Frequency::Weekly => {
+                let days = rule.by_day.unwrap_or_else(|| {
+                    let mut days = [false; 7];
+                    days[first.weekday().num_days_from_monday() as usize] = true;
+                    days
+                });
+                let monday = first.date().week(Weekday::Mon).first_day();
+                'weeks: for week in 0.. {
+                    let Some(week_start) =
+                        monday.checked_add_days(Days::new(7 * week * interval as u64))
+                    else {
+                        break;
+                    };
+                    for (offset, _) in days.iter().enumerate().filter(|(_, &day)| day) {
+                        let date = week_start + Days::new(offset as u64);
+                        let start = date.and_time(first.time());
+                        if start < first {
+                            continue;
+                        }
+                        if !emit(start, &mut starts) {
+                            break 'weeks;
+                        }
+                    }
+                }
+            }
+            Frequency::Monthly | Frequency::Yearly => {
+                let months = if rule.frequency == Frequency::Monthly {
+                    interval
+                } else {
+                    12 * interval
+                };
+                for step in 0.. {
+                    // Months without the start's day (e.g. the 31st) are skipped
+                    let Some(month_start) = first
+                        .date()
+                        .with_day(1)
+                        .and_then(|date| date.checked_add_months(Months::new(step * months)))
+                    else {
+                        break;
+                    };
+                    if month_start.and_time(NaiveTime::MIN) > until {
+                        break;
+                    }
+                    if let Some(date) = month_start.with_day(first.day()) {
+                        if !emit(date.and_time(first.time()), &mut starts) {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        starts
+    }
+}
+
+/// The events of a parsed calendar file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Calendar {
+    events: Vec<Event>,
+    skipped: Vec<String>,
+}
+
+impl Calendar {
+    /// Parse the text of an `.ics` file
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut calendar = Calendar::default();
+        let mut current: Option<Vec<Property>> = None;
+        let mut found_calendar = false;
+
+        for (number, line) in unfold(text) {
+            let property = Property::parse(&line)
+                .ok_or_else(|| format!("line {}: cannot parse `{}`", number, line))?;
+            match (property.name.as_str(), property.value.as_str()) {
+                ("BEGIN", "VCALENDAR") => found_calendar = true,
+                ("BEGIN", "VEVENT") => current = Some(Vec::new()),
+                ("END", "VEVENT") => {
+                    let properties = current
+                        .take()
+                        .ok_or_else(|| format!("line {}: END:VEVENT without BEGIN", number))?;
+                    match Event::from_properties(&properties) {
+                        Ok(Some(event)) => calendar.events.push(event),
+                        Ok(None) => {}
+                        Err(reason) => calendar.skipped.push(reason),
+                    }
+                }
+                _ => {
+                    if let Some(properties) = &mut current {
+                        properties.push(property);
+                    }
+                }
+            }
+        }
+        if !found_calendar {
+            return Err("not an iCalendar file (no BEGIN:VCALENDAR)".to_string());
+        }
+        Ok(calendar)
+    }
+
+    /// How many events were read
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Whether no events were read
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Why events that cannot be scheduled were left out, one per event
+    pub fn skipped(&self) -> &[String] {
+        &self.skipped
+    }
+
+    /// The occurrences of events tagged `tag` (or of all events) that
+    /// overlap `from` to `to`, as start and end times in `from`'s time zone
+    pub fn spans<Tz: TimeZone>(
+        &self,
+        tag: Option<&str>,
+        from: &DateTime<Tz>,
+        to: &DateTime<Tz>,
+    ) -> Vec<(DateTime<Tz>, DateTime<Tz>)> {
+        let tz = from.timezone();
+        // Occurrences replaced by a modified instance, by UID
+        let mut replaced: HashMap<&str, Vec<DateTime<Tz>>> = HashMap::new();
+        for event in &self.events {
+            if let (Some(uid), Some(id)) = (&event.uid, &event.recurrence_id) {
+                if let Some(time) = id.resolve(&tz) {
+                    replaced.entry(uid).or_default().push(time);
+                }
+            }
+        }
+
+        let mut spans = Vec::new();
+        for event in &self.events {
+            if tag.is_some_and(|tag| !event.has_tag(tag)) {
+                continue;
+            }
+            let exceptions: Vec<DateTime<Tz>> = event
+                .exceptions
+                .iter()
+                .filter_map(|time| time.resolve(&tz))
+                .chain(
+                    event
+                        .uid
+                        .as_deref()
+                        .filter(|_| event.recurrence_id.is_none())
+                        .and_then(|uid| replaced.get(uid))
+                        .into_iter()
+                        .flatten()
+                        .cloned(),
+                )
+                .collect();
+            // One day of slack either side covers any time zone difference
+            let until = to.naive_utc() + TimeDelta::days(1);
+            for local in event.starts(until) {
+                let occurrence = event.start.with_local(local);
+                let (Some(start), Some(end)) = (
+                    occurrence.resolve(&tz),
+                    occurrence.with_local(local + event.length).resolve(&tz),
+                ) else {
+                    continue;
+                };
+                if end <= *from || start >= *to || exceptions.contains(&start) {
+                    continue;
+                }
+                if let Some(limit) = event.rule.as_ref().and_then(|rule| rule.until) {
+                    if limit.zone != event.start.zone
+                        && limit.resolve(&tz).is_some_and(|limit| start > limit)
+                    {
+                        continue;
+                    }
+                }
+                spans.push((start, end));
+            }
+        }
+        spans
+    }
+}
+
+/// A content line: `NAME;PARAM=VALUE:value`
+#[derive(Debug, Clone)]
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn parse(line: &str) -> Option<Self> {
+        // The value starts at the first colon outside a quoted parameter
+        let mut quoted = false;
+        let colon = line.char_indices().find_map(|(index, c)| match c {
+            '"' => {
+                quoted = !quoted;
+                None
+            }
+            ':' if !quoted => Some(index),
+            _ => None,
+        })?;
+        let mut head = line[..colon].split(';');
+        let name = head.next()?.trim().to_ascii_uppercase();
+        let params = head
+            .filter_map(|param| param.split_once('='))
+            .map(|(key, value)| {
+                (
+                    key.to_ascii_uppercase(),
+                    value.trim_matches('"').to_string(),
+                )
+            })
+            .collect();
+        Some(Self {
+            name,
+            params,
+            value: line[colon + 1..].to_string(),
+        })
+    }
+
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Parse the value as one or more date or date-time values
+    fn times(&self) -> Result<Vec<EventTime>, String> {
+        let zone = match self.param("TZID") {
+            // Unknown (e.g. Windows) zone names are read as local time
+            Some(tzid) => tzid
+                .parse::<chrono_tz::Tz>()
+                .map_or(Zone::Floating, Zone::Named),
+            None => Zone::Floating,
+        };
+        self.value
+            .split(',')
+            .map(|value| parse_time(value.trim(), zone))
+            .collect::<Option<_>>()
+            .ok_or_else(|| format!("invalid {} `{}`", self.name, self.value))
+    }
+
+    fn time(&self) -> Result<EventTime, String> {
+        self.times()?
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("empty {}", self.name))
+    }
+}
+
+/// Parse `20240510`, `20240510T090000` or `20240510T090000Z`
+fn parse_time(value: &str, zone: Zone) -> Option<EventTime> {
+    if let Some(utc) = value.strip_suffix('Z') {
+        let local = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(EventTime {
+            local,
+            zone: Zone::Utc,
+        });
+    }
+    if let Ok(local) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        return Some(EventTime { local, zone });
+    }
+    // All-day dates are always local
+    let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+    Some(EventTime {
+        local: date.and_time(NaiveTime::MIN),
+        zone: Zone::Floating,
+    })
+}
+
+/// Parse an ISO 8601 duration such as `PT1H30M` or `P1D`
+fn parse_length(value: &str) -> Option<TimeDelta> {
+    let value = value.strip_prefix('+').unwrap_or(value);
+    let rest = value.strip_prefix('P')?;
+    let mut total = TimeDelta::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in rest.chars() {
+        match c {
+            'T' => in_time = true,
+            '0'..='9' => number.push(c),
+            _ => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                total += match (c, in_time) {
+                    ('W', false) => TimeDelta::weeks(n),
+                    ('D', false) => TimeDelta::days(n),
+                    ('H', true) => TimeDelta::hours(n),
+                    ('M', true) => TimeDelta::minutes(n),
+                    ('S', true) => TimeDelta::seconds(n),
+                    _ => return None,
+                };
+            }
+        }
+    }
+    number.is_empty().then_some(total)
+}
+
+fn parse_rule(value: &str) -> Result<Rule, String> {
+    let mut rule = Rule {
+        frequency: Frequency::Daily,
+        interval: 1,
+        count: None,
+        until: None,
+        by_day: None,
+    };
+    let mut frequency = None;
+    for part in value.split(';').filter(|part| !part.is_empty()) {
+        let (key, value) = part
+            .split_once('=')
+            .ok_or_else(|| format!("invalid RRULE part `{}`", part))?;
+        match key.to_ascii_uppercase().as_str() {
+            "FREQ" => {
+                frequency = Some(match value.to_ascii_uppercase().as_str() {
+                    "DAILY" => Frequency::Daily,
+                    "WEEKLY" => Frequency::Weekly,
+                    "MONTHLY" => Frequency::Monthly,
+                    "YEARLY" => Frequency::Yearly,
+                    other => return Err(format!("unsupported RRULE frequency {}", other)),
+                })
+            }
+            "INTERVAL" => {
+                rule.interval = value
+                    .parse()
+                    .map_err(|_| format!("invalid RRULE interval `{}`", value))?
+            }
+            "COUNT" => {
+                rule.count = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid RRULE count `{}`", value))?,
+                )
+            }
+            "UNTIL" => {
+                let until = parse_time(value, Zone::Floating)
+                    .ok_or_else(|| format!("invalid RRULE until `{}`", value))?;
+                // A date-only UNTIL includes occurrences on that day
+                rule.until = Some(if value.len() == 8 {
+                    until.with_local(until.local + TimeDelta::days(1) - TimeDelta::seconds(1))
+                } else {
+                    until
+                });
+            }
+            "BYDAY" => {
+                let mut days = [false; 7];
+                for day in value.split(',') {
+                    let day = match day.to_ascii_uppercase().as_str() {
+                        "MO" => Weekday::Mon,
+                        "TU" => Weekday::Tue,
+                        "WE" => Weekday::Wed,
+                        "TH" => Weekday::Thu,
+                        "FR" => Weekday::Fri,
+                        "SA" => Weekday::Sat,
+                        "SU" => Weekday::Sun,
+                        _ => return Err(format!("unsupported RRULE BYDAY `{}`", day)),
+                    };
+                    days[day.num_days_from_monday() as usize] = true;
+                }
+                rule.by_day = Some(days);
+            }
+            "WKST" => {}
+            other => return Err(format!("unsupported RRULE part {}", other)),
+        }
+    }
+    rule.frequency = frequency.ok_or("RRULE without FREQ")?;
+    if rule.by_day.is_some() && !matches!(rule.frequency, Frequency::Daily | Frequency::Weekly) {
+        return Err("unsupported RRULE BYDAY for monthly or yearly events".to_string());
+    }
+    Ok(rule)
+}
+
+impl Event {
+    /// Build an event from its properties; `Ok(None)` for cancelled events
+    /// and `Err` with a reason for events that cannot be scheduled
+    fn from_properties(properties: &[Property]) -> Result<Option<Self>, String> {
+        let get = |name: &str| properties.iter().find(|property| property.name == name);
+        let summary = get("SUMMARY")
+            .map(|property| unescape(&property.value))
+            .unwrap_or_default();
+        let describe = |reason: String| {
+            if summary.is_empty() {
+                reason
+            } else {
+                format!("{}: {}", summary, reason)
+            }
+        };
+
+        if get("STATUS").is_some_and(|status| status.value.eq_ignore_ascii_case("CANCELLED")) {
+            return Ok(None);
+        }
+        let start_property = get("DTSTART").ok_or_else(|| describe("no DTSTART".to_string()))?;
+        let start = start_property.time().map_err(describe)?;
+        let all_day =
+            start_property.param("VALUE") == Some("DATE") || start_property.value.trim().len() == 8;
+        let length = match (get("DTEND"), get("DURATION")) {
+            (Some(end), _) => end.time().map_err(describe)?.local - start.local,
+            (None, Some(duration)) => parse_length(duration.value.trim())
+                .ok_or_else(|| describe(format!("invalid DURATION `{}`", duration.value)))?,
+            (None, None) if all_day => TimeDelta::days(1),
+            (None, None) => TimeDelta::zero(),
+        };
+        let rule = get("RRULE")
+            .map(|rule| parse_rule(&rule.value))
+            .transpose()
+            .map_err(describe)?;
+
+        let mut exceptions = Vec::new();
+        for exdate in properties
+            .iter()
+            .filter(|property| property.name == "EXDATE")
+        {
+            exceptions.extend(exdate.times().map_err(describe)?);
+        }
+        let categories = properties
+            .iter()
+            .filter(|property| property.name == "CATEGORIES")
+            .flat_map(|property| split_list(&property.value))
+            .collect();
+
+        Ok(Some(Self {
+            uid: get("UID").map(|uid| uid.value.trim().to_string()),
+            summary: summary.clone(),
+            categories,
+            start,
+            length,
+            rule,
+            exceptions,
+            recurrence_id: get("RECURRENCE-ID")
+                .map(Property::time)
+                .transpose()
+                .map_err(describe)?,
+        }))
+    }
+}
+
+/// Join folded lines, returning each logical line with its starting line number
+fn unfold(text: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some((_, previous))) => previous.push_str(continuation),
+            _ if line.trim().is_empty() => {}
+            _ => lines.push((index + 1, line.to_string())),
+        }
+    }
+    lines
+}
+
+/// Undo iCalendar text escaping
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => result.push('\n'),
+            Some(other) => result.push(other),
+            None => {}
+        }
+    }
+    result
+}
+
+/// Split a comma-separated text list, honouring escaped commas
+fn split_list(text: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut escaped = false;
+    for c in text.chars() {
+        match c {
+            ',' if !escaped => items.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    items.push(current);
+    items
+        .iter()
+        .map(|item| unescape(item.trim()))
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn calendar(events: &str) -> Calendar {
+        Calendar::parse(&format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{}END:VCALENDAR\r\n",
+            events
+        ))
+        .unwrap()
+    }
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<FixedOffset> {
+        FixedOffset::east_opt(0)
+            .unwrap()
+            .with_ymd_and_hms(y, m, d, h, min, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_single_event_and_tags() {
+        let cal = calendar(
+            "BEGIN:VEVENT\nUID:1\nSUMMARY:Customer demo\nDTSTART:20240510T140000Z\n\
+             DTEND:20240510T150000Z\nEND:VEVENT\n\
+             BEGIN:VEVENT\nUID:2\nSUMMARY:Lunch\nCATEGORIES:food,social\n\
+             DTSTART:20240510T120000Z\nDURATION:PT45M\nEND:VEVENT\n",
+        );
+        let (from, to) = (utc(2024, 5, 10, 0, 0), utc(2024, 5, 11, 0, 0));
+        assert_eq!(
+            cal.spans(Some("demo"), &from, &to),
+            [(utc(2024, 5, 10, 14, 0), utc(2024, 5, 10, 15, 0))]
+        );
+        assert_eq!(
+            cal.spans(Some("SOCIAL"), &from, &to),
+            [(utc(2024, 5, 10, 12, 0), utc(2024, 5, 10, 12, 45))]
+        );
+        assert_eq!(cal.spans(None, &from, &to).len(), 2);
+        assert!(cal.spans(Some("dem"), &from, &to).is_empty());
+    }
+
+    #[test]
+    fn test_weekly_rule_with_exceptions_and_overrides() {
+        let cal = calendar(
+            "BEGIN:VEVENT\nUID:standup\nSUMMARY:Standup\nDTSTART;TZID=Europe/Berlin:20240506T090000\n\
+             DTEND;TZID=Europe/Berlin:20240506T091500\n\
+             RRULE:FREQ=WEEKLY;BYDAY=MO,WE,FR;COUNT=6\n\
+             EXDATE;TZID=Europe/Berlin:20240508T090000\nEND:VEVENT\n\
+             BEGIN:VEVENT\nUID:standup\nRECURRENCE-ID;TZID=Europe/Berlin:20240510T090000\n\
+             DTSTART;TZID=Europe/Berlin:20240510T100000\n\
+             DTEND;TZID=Europe/Berlin:20240510T101500\nEND:VEVENT\n",
+        );
+        let spans = cal.spans(None, &utc(2024, 5, 1, 0, 0), &utc(2024, 6, 1, 0, 0));
+        let starts: Vec<_> = spans.iter().map(|(start, _)| *start).collect();
+        // Berlin is two hours ahead of UTC in May
+        assert_eq!(
+            starts,
+            [
+                utc(2024, 5, 6, 7, 0),
+                utc(2024, 5, 13, 7, 0),
+                utc(2024, 5, 15, 7, 0),
+                utc(2024, 5, 17, 7, 0),
+                utc(2024, 5, 10, 8, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_daily_and_monthly_rules() {
+        let cal = calendar(
+            "BEGIN:VEVENT\nSUMMARY:Backup\nDTSTART:20240101T230000Z\nDURATION:PT2H\n\
+             RRULE:FREQ=DAILY;INTERVAL=2;UNTIL=20240107T230000Z\nEND:VEVENT\n\
+             BEGIN:VEVENT\nSUMMARY:Review\nDTSTART:20240131\n\
+             RRULE:FREQ=MONTHLY;COUNT=3\nEND:VEVENT\n",
+        );
+        let from = utc(2024, 1, 1, 0, 0);
+        let backups = cal.spans(Some("backup"), &from, &utc(2024, 2, 1, 0, 0));
+        assert_eq!(backups.len(), 4);
+        assert_eq!(backups[3], (utc(2024, 1, 7, 23, 0), utc(2024, 1, 8, 1, 0)));
+
+        // February has no 31st, so the third occurrence is in March
+        let reviews = cal.spans(Some("review"), &from, &utc(2025, 1, 1, 0, 0));
+        let starts: Vec<_> = reviews.iter().map(|(start, _)| *start).collect();
+        assert_eq!(
+            starts,
+            [
+                utc(2024, 1, 31, 0, 0),
+                utc(2024, 3, 31, 0, 0),
+                utc(2024, 5, 31, 0, 0),
+            ]
+        );
+        assert_eq!(reviews[0].1, utc(2024, 2, 1, 0, 0));
+    }
+
+    #[test]
+    fn test_unsupported_and_invalid_input() {
+        let cal = calendar(
+            "BEGIN:VEVENT\nSUMMARY:Board meeting\nDTSTART:20240101T090000Z\n\
+             RRULE:FREQ=MONTHLY;BYDAY=1MO\nEND:VEVENT\n\
+             BEGIN:VEVENT\nSUMMARY:Cancelled\nSTATUS:CANCELLED\nDTSTART:20240101T090000Z\nEND:VEVENT\n",
+        );
+        assert!(cal.is_empty());
+        assert_eq!(cal.skipped().len(), 1);
+        assert!(
+            cal.skipped()[0].starts_with("Board meeting:"),
+            "{:?}",
+            cal.skipped()
+        );
+
+        assert!(Calendar::parse("hello").is_err());
+    }
+
+    #[test]
+    fn test_folded_lines_and_escapes() {
+        let cal = calendar(
+            "BEGIN:VEVENT\nSUMMARY:Quarterly\\, all-hands\nCATEGORIES:Demo\\,Prep,Meet\n ings\n\
+             DTSTART:20240510T140000Z\nDTEND:20240510T150000Z\nEND:VEVENT\n",
+        );
+        assert_eq!(cal.events[0].summary, "Quarterly, all-hands");
+        assert_eq!(cal.events[0].categories, ["Demo,Prep", "Meetings"]);
+    }
+}
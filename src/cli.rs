@@ -0,0 +1,463 @@
+//! Command-line interface for KTMM
+//!
+//! Every option is optional so that only the flags actually passed on the
+//! command line override the configuration they are applied to.
+
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::config::ConfigLayer;
+use crate::cron::CronExpr;
+use crate::jitter::{IntervalJitter, OffsetRange};
+use crate::keys::NudgeKey;
+use crate::pattern::Pattern;
+use crate::schedule::{Schedule, Window};
+use crate::timespec::{self, TimeOfDay};
+use crate::{
+    BackendKind, KeepAwakeMode, KtmmError, MouseMoverConfig, NudgeKind, ScrollAxis, Verbosity,
+};
+
+/// Keep That Mouse Moving - prevents system sleep by making periodic mouse movements
+#[derive(Debug, Clone, Default, Parser)]
+#[command(name = "ktmm", version, disable_version_flag = true)]
+pub struct Cli {
+    /// Read settings from this config file instead of the default location
+    #[arg(short, long, value_name = "PATH", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Use the settings of a named profile from the config file
+    #[arg(short, long, value_name = "NAME", global = true)]
+    pub profile: Option<String>,
+
+    /// Control socket of `ktmm daemon` [default: $XDG_RUNTIME_DIR/ktmm.sock]
+    #[arg(long, value_name = "PATH", global = true)]
+    pub socket: Option<PathBuf>,
+
+    /// Stop the instance that is already running and take over from it
+    #[arg(long, global = true)]
+    pub replace: bool,
+
+    /// Seconds to wait between mouse movements [default: 60]
+    #[arg(short, long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub interval: Option<u64>,
+
+    /// Vary the interval: `fixed`, a range of seconds such as `30-90`, or `exponential[:MAX]` around it [default: fixed]
+    #[arg(long, value_name = "JITTER")]
+    pub interval_jitter: Option<IntervalJitter>,
+
+    /// Horizontal distance to move the mouse in pixels [default: 1]
+    #[arg(long, value_name = "PIXELS", allow_negative_numbers = true)]
+    pub dx: Option<i32>,
+
+    /// Vertical distance to move the mouse in pixels [default: 1]
+    #[arg(long, value_name = "PIXELS", allow_negative_numbers = true)]
+    pub dy: Option<i32>,
+
+    /// Move a random distance in pixels, e.g. `1-5`, in a random direction instead of by dx and dy
+    #[arg(long, value_name = "MIN-MAX")]
+    pub random_offset: Option<OffsetRange>,
+
+    /// Seed the random intervals and offsets to repeat the same sequence
+    #[arg(long, value_name = "N")]
+    pub seed: Option<u64>,
+
+    /// Path to trace on each nudge: `line`, `circle`, `square`, `bezier` or `zigzag` [default: line]
+    #[arg(long, value_name = "PATTERN")]
+    pub pattern: Option<Pattern>,
+
+    /// What to nudge: move the `pointer`, tap a harmless `key` for apps that only watch the keyboard, `scroll` one notch and back, or reset the idle timer with `synthetic` motion where possible [default: pointer]
+    #[arg(long, value_name = "KIND")]
+    pub nudge: Option<NudgeKind>,
+
+    /// Key tapped by `--nudge key`: F13-F24, shift, control or scroll-lock [default: F15]
+    #[arg(long, value_name = "KEY")]
+    pub nudge_key: Option<NudgeKey>,
+
+    /// Wheel turned by `--nudge scroll`: `vertical` or `horizontal` [default: vertical]
+    #[arg(long, value_name = "AXIS")]
+    pub scroll_axis: Option<ScrollAxis>,
+
+    /// Milliseconds to wait before moving the mouse back, or to trace a whole pattern [default: 6]
+    #[arg(short, long, value_name = "MS")]
+    pub delay: Option<u64>,
+
+    /// How to keep the system awake: `mouse` movements or D-Bus `inhibit`ors [default: mouse]
+    #[arg(short, long, value_name = "MODE")]
+    pub mode: Option<KeepAwakeMode>,
+
+    /// Input backend: `native`, `uinput` (virtual pointer, works on Wayland) or `auto` [default: auto]
+    #[arg(short, long, value_name = "BACKEND")]
+    pub backend: Option<BackendKind>,
+
+    /// Move the mouse once and exit
+    #[arg(long, conflicts_with = "duration")]
+    pub once: bool,
+
+    /// Exit after this long instead of running until interrupted, e.g. `2h30m` or `90` seconds
+    #[arg(long, visible_alias = "for", value_name = "DURATION", value_parser = parse_duration)]
+    pub duration: Option<u64>,
+
+    /// Exit when the local clock next shows this time, e.g. `17:30`
+    #[arg(long, value_name = "HH:MM", conflicts_with = "once")]
+    pub until: Option<TimeOfDay>,
+
+    /// Only move the mouse once the user has been idle for this many seconds
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub idle_threshold: Option<u64>,
+
+    /// Only keep awake inside this weekly window, e.g. `Mon-Fri 09:00-18:00`; repeat for several
+    #[arg(long, value_name = "WINDOW", global = true)]
+    pub schedule: Vec<Window>,
+
+    /// Also keep awake in the minutes a cron expression matches, e.g. `* 9-17 * * Mon-Fri`
+    #[arg(long, value_name = "EXPR", global = true)]
+    pub cron: Vec<CronExpr>,
+
+    /// Also keep awake during the events of this iCalendar (.ics) file
+    #[arg(long, value_name = "PATH", global = true)]
+    pub calendar: Option<PathBuf>,
+
+    /// Only use calendar events with this category, or this word in their title
+    #[arg(long, value_name = "TAG", global = true)]
+    pub calendar_tag: Option<String>,
+
+    /// Keep awake until the process with this PID exits; repeat to wait for several
+    #[arg(
+        long = "wait-pid",
+        value_name = "PID",
+        value_parser = clap::value_parser!(u32).range(1..=i32::MAX as i64),
+        conflicts_with_all = ["once", "exec"]
+    )]
+    pub wait_pid: Vec<u32>,
+
+    /// Report every mouse movement
+    #[arg(long, conflicts_with = "quiet")]
+    pub verbose: bool,
+
+    /// Only print errors
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// Print version
+    #[arg(short = 'V', visible_short_alias = 'v', long, action = clap::ArgAction::Version)]
+    version: Option<bool>,
+
+    /// Run this command and keep the system awake until it exits, exiting with its status
+    #[arg(last = true, value_name = "COMMAND", conflicts_with = "once")]
+    pub exec: Vec<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Commands other than running the mouse mover
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// List the profiles in the config file with their effective settings
+    Profiles,
+    /// Show the configured schedule and whether it is active now
+    Schedule {
+        /// Also print when the schedule next turns on and off
+        #[arg(long)]
+        next: bool,
+    },
+    /// Keep the system awake and accept commands on the control socket
+    Daemon,
+    /// Show whether the daemon is running or paused
+    Status,
+    /// Tell the daemon to stop nudging until resumed
+    Pause,
+    /// Tell the daemon to start nudging again
+    Resume,
+    /// Tell the daemon to shut down
+    Stop,
+    /// Change the daemon's interval without restarting it
+    SetInterval {
+        /// Seconds to wait between mouse movements
+        #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+        secs: u64,
+    },
+    /// Write a systemd user unit that runs `ktmm daemon`
+    InstallService {
+        /// Overwrite an existing unit file
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+impl Cli {
+    /// The settings given on the command line as a configuration layer
+    pub fn layer(&self) -> ConfigLayer {
+        let verbosity = if self.verbose {
+            Some(Verbosity::Verbose)
+        } else if self.quiet {
+            Some(Verbosity::Quiet)
+        } else {
+            None
+        };
+        ConfigLayer {
+            interval_secs: self.interval,
+            interval_jitter: self.interval_jitter,
+            dx: self.dx,
+            dy: self.dy,
+            random_offset: self.random_offset,
+            seed: self.seed,
+            pattern: self.pattern,
+            nudge: self.nudge,
+            nudge_key: self.nudge_key,
+            scroll_axis: self.scroll_axis,
+            return_delay_ms: self.delay,
+            duration_secs: self.duration,
+            until: self.until,
+            idle_threshold_secs: self.idle_threshold,
+            schedule: (!self.schedule.is_empty()).then(|| Schedule::new(self.schedule.clone())),
+            cron: (!self.cron.is_empty()).then(|| self.cron.clone()),
+            calendar: self.calendar.clone(),
+            calendar_tag: self.calendar_tag.clone(),
+            verbosity,
+            mode: self.mode,
+            backend: self.backend,
+        }
+    }
+
+    /// Override the given configuration with the options present on the command line
+    pub fn apply_to(&self, config: &mut MouseMoverConfig) {
+        self.layer().apply_to(config);
+        if self.once {
+            config.once = true;
+        }
+    }
+
+    /// Build the validated configuration, layering the defaults, the config
+    /// file, `KTMM_*` environment variables and the command line
+    pub fn to_config(&self) -> Result<MouseMoverConfig, KtmmError> {
+        crate::config::load(self)
+    }
+}
+
+/// Parse a `--duration` value, which must be at least a second
+fn parse_duration(text: &str) -> Result<u64, String> {
+    match timespec::parse_duration(text)? {
+        0 => Err("duration must be at least 1 second".to_string()),
+        secs => Ok(secs),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    fn cli_config(cli: &Cli) -> Result<MouseMoverConfig, KtmmError> {
+        let mut config = MouseMoverConfig::default();
+        cli.apply_to(&mut config);
+        config.validate()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_no_args_gives_default_config() {
+        let config = cli_config(&Cli::try_parse_from(["ktmm"]).unwrap()).unwrap();
+        assert_eq!(config.interval_secs, 60);
+        assert_eq!(config.movement_pixels, (1, 1));
+        assert_eq!(config.return_delay_ms, 6);
+        assert_eq!(config.verbosity, Verbosity::Normal);
+    }
+
+    #[test]
+    fn test_flags_map_onto_config() {
+        let cli = Cli::try_parse_from([
+            "ktmm",
+            "--interval",
+            "30",
+            "--dx",
+            "-2",
+            "--dy",
+            "3",
+            "--delay",
+            "10",
+            "--duration",
+            "120",
+            "--pattern",
+            "bezier",
+            "--verbose",
+        ])
+        .unwrap();
+        let config = cli_config(&cli).unwrap();
+        assert_eq!(config.interval_secs, 30);
+        assert_eq!(config.movement_pixels, (-2, 3));
+        assert_eq!(config.return_delay_ms, 10);
+        assert_eq!(config.duration_secs, Some(120));
+        assert_eq!(config.pattern, Pattern::Bezier);
+        assert!(!config.once);
+        assert_eq!(config.verbosity, Verbosity::Verbose);
+    }
+
+    #[test]
+    fn test_invalid_values_are_rejected() {
+        assert!(Cli::try_parse_from(["ktmm", "--interval", "0"]).is_err());
+        assert!(Cli::try_parse_from(["ktmm", "--once", "--duration", "5"]).is_err());
+        assert!(Cli::try_parse_from(["ktmm", "--verbose", "--quiet"]).is_err());
+
+        let cli = Cli::try_parse_from(["ktmm", "--dx", "0", "--dy", "0"]).unwrap();
+        assert!(matches!(cli_config(&cli), Err(KtmmError::ConfigError(_))));
+
+        let cli = Cli::try_parse_from(["ktmm", "--interval", "1", "--delay", "1000"]).unwrap();
+        assert!(matches!(cli_config(&cli), Err(KtmmError::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_wrapped_command() {
+        let cli =
+            Cli::try_parse_from(["ktmm", "-i", "30", "--", "make", "-j4", "--quiet"]).unwrap();
+        assert_eq!(cli.exec, ["make", "-j4", "--quiet"]);
+        assert_eq!(cli.interval, Some(30));
+        assert!(!cli.quiet);
+
+        assert!(Cli::try_parse_from(["ktmm", "--once", "--", "make"]).is_err());
+    }
+
+    #[test]
+    fn test_for_and_until() {
+        let cli = Cli::try_parse_from(["ktmm", "--for", "2h30m", "--until", "17:30"]).unwrap();
+        assert_eq!(cli.duration, Some(9_000));
+        assert_eq!(cli.until, TimeOfDay::hm(17, 30));
+
+        let cli = Cli::try_parse_from(["ktmm", "--duration", "90"]).unwrap();
+        assert_eq!(cli.duration, Some(90));
+
+        assert!(Cli::try_parse_from(["ktmm", "--for", "0s"]).is_err());
+        assert!(Cli::try_parse_from(["ktmm", "--until", "25:00"]).is_err());
+        assert!(Cli::try_parse_from(["ktmm", "--once", "--until", "17:30"]).is_err());
+    }
+
+    #[test]
+    fn test_schedule_windows() {
+        let cli = Cli::try_parse_from([
+            "ktmm",
+            "--schedule",
+            "Mon-Fri 09:00-12:00",
+            "--schedule",
+            "Mon-Fri 13:00-18:00",
+        ])
+        .unwrap();
+        let config = cli_config(&cli).unwrap();
+        assert_eq!(
+            config.schedule.to_string(),
+            "Mon-Fri 09:00-12:00; Mon-Fri 13:00-18:00"
+        );
+
+        let cli = Cli::try_parse_from([
+            "ktmm",
+            "schedule",
+            "--next",
+            "--schedule",
+            "Sat 10:00-12:00",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Schedule { next: true })
+        ));
+        assert_eq!(cli.schedule.len(), 1);
+
+        assert!(Cli::try_parse_from(["ktmm", "--schedule", "weekdays"]).is_err());
+    }
+
+    #[test]
+    fn test_daemon_commands() {
+        let cli = Cli::try_parse_from(["ktmm", "daemon", "--socket", "/tmp/k.sock"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Daemon)));
+        assert_eq!(cli.socket, Some(PathBuf::from("/tmp/k.sock")));
+
+        let cli = Cli::try_parse_from(["ktmm", "set-interval", "30"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::SetInterval { secs: 30 })
+        ));
+        assert!(Cli::try_parse_from(["ktmm", "set-interval", "0"]).is_err());
+        assert!(Cli::try_parse_from(["ktmm", "pause"]).is_ok());
+    }
+
+    #[test]
+    fn test_jitter_options() {
+        let cli = Cli::try_parse_from([
+            "ktmm",
+            "--interval-jitter",
+            "30-90",
+            "--random-offset",
+            "1-4",
+            "--seed",
+            "42",
+        ])
+        .unwrap();
+        let config = cli_config(&cli).unwrap();
+        assert_eq!(
+            config.interval_jitter,
+            IntervalJitter::Uniform {
+                min_secs: 30,
+                max_secs: 90
+            }
+        );
+        assert_eq!(config.random_offset, Some(OffsetRange { min: 1, max: 4 }));
+        assert_eq!(config.seed, Some(42));
+
+        assert!(Cli::try_parse_from(["ktmm", "--interval-jitter", "90-30"]).is_err());
+        assert!(Cli::try_parse_from(["ktmm", "--random-offset", "0-2"]).is_err());
+
+        // The return delay must fit in the shortest interval of the range
+        let cli =
+            Cli::try_parse_from(["ktmm", "--interval-jitter", "1-10", "--delay", "1000"]).unwrap();
+        assert!(matches!(cli_config(&cli), Err(KtmmError::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_key_nudge() {
+        let cli = Cli::try_parse_from(["ktmm", "--nudge", "key", "--nudge-key", "shift"]).unwrap();
+        let config = cli_config(&cli).unwrap();
+        assert_eq!(config.nudge, NudgeKind::Key);
+        assert_eq!(config.nudge_key, NudgeKey::Shift);
+
+        assert!(Cli::try_parse_from(["ktmm", "--nudge-key", "a"]).is_err());
+        assert!(Cli::try_parse_from(["ktmm", "--nudge-key", "enter"]).is_err());
+
+        let cli = Cli::try_parse_from(["ktmm", "--nudge", "key", "--backend", "uinput"]).unwrap();
+        assert!(matches!(cli_config(&cli), Err(KtmmError::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_scroll_nudge() {
+        let cli = Cli::try_parse_from(["ktmm", "--nudge", "scroll", "--scroll-axis", "horizontal"])
+            .unwrap();
+        let config = cli_config(&cli).unwrap();
+        assert_eq!(config.nudge, NudgeKind::Scroll);
+        assert_eq!(config.scroll_axis, ScrollAxis::Horizontal);
+
+        assert!(Cli::try_parse_from(["ktmm", "--scroll-axis", "diagonal"]).is_err());
+
+        let cli = Cli::try_parse_from(["ktmm", "--nudge", "synthetic"]).unwrap();
+        assert_eq!(cli_config(&cli).unwrap().nudge, NudgeKind::Synthetic);
+    }
+
+    #[test]
+    fn test_wait_pid_is_repeatable() {
+        let cli = Cli::try_parse_from(["ktmm", "--wait-pid", "12", "--wait-pid", "34"]).unwrap();
+        assert_eq!(cli.wait_pid, [12, 34]);
+
+        assert!(Cli::try_parse_from(["ktmm", "--wait-pid", "0"]).is_err());
+        assert!(Cli::try_parse_from(["ktmm", "--wait-pid", "12", "--", "make"]).is_err());
+    }
+
+    #[test]
+    fn test_version_flag() {
+        for flag in ["--version", "-V", "-v"] {
+            let err = Cli::try_parse_from(["ktmm", flag]).unwrap_err();
+            assert_eq!(err.kind(), clap::error::ErrorKind::DisplayVersion);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Time as seen by the run loop
+//!
+//! [`MouseMover`](crate::MouseMover) reads the time, for its schedule and its
+//! idle tracking as well as for its run loop, and blocks through a [`Clock`].
+//! Between nudges, the loop makes a single blocking wait that ends at the next
+//! nudge, schedule change or deadline, or as soon as its [`Handle`] changes;
+//! nothing wakes it otherwise. [`SystemClock`] is real time, while
+//! [`VirtualClock`] jumps straight to the end of every wait and counts them,
+//! so tests can check how often the loop wakes up without actually waiting.
+//! It can also act on the handle at set simulated times, standing in for
+//! another thread controlling the loop.
+
+use chrono::{DateTime, Local, TimeDelta};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{Handle, HandleState};
+
+/// A source of time for the run loop
+pub trait Clock: Send + Sync {
+    /// The current time
+    fn now(&self) -> Instant;
+
+    /// The current local date and time, which schedules are checked against
+    fn local_now(&self) -> DateTime<Local>;
+
+    /// Block until `deadline`, such as between the steps of a nudge, without
+    /// regard to the handle
+    fn sleep_until(&self, deadline: Instant);
+
+    /// Block until the state of `handle` differs from `current` or `until`
+    /// has passed, waiting indefinitely for a change when `until` is `None`
+    ///
+    /// Returns the state at the time of waking.
+    fn wait(&self, handle: &Handle, current: HandleState, until: Option<Instant>) -> HandleState;
+}
+
+/// Real time, waiting on the handle's condition variable
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn local_now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        let now = Instant::now();
+        if deadline > now {
+            thread::sleep(deadline - now);
+        }
+    }
+
+    fn wait(&self, handle: &Handle, current: HandleState, until: Option<Instant>) -> HandleState {
+        handle.wait_for_change(current, until)
+    }
+}
+
+/// Something to do to the handle at a given simulated time
+type Action = Box<dyn FnOnce(&Handle) + Send>;
+
+/// Simulated time that passes only when the loop waits
+///
+/// Each wait returns at once with the clock moved on to its end, or to the
+/// first scheduled action that changes the handle's state. Once a wait would
+/// go past the end of the simulated run, the clock stops at the end and stops
+/// the handle instead.
+pub struct VirtualClock {
+    start: Instant,
+    local_start: DateTime<Local>,
+    now: Mutex<Instant>,
+    end: Instant,
+    wakeups: AtomicUsize,
+    actions: Mutex<Vec<(Instant, Action)>>,
+}
+
+impl std::fmt::Debug for VirtualClock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VirtualClock")
+            .field("start", &self.start)
+            .field("local_start", &self.local_start)
+            .field("now", &self.now)
+            .field("end", &self.end)
+            .field("wakeups", &self.wakeups)
+            .finish_non_exhaustive()
+    }
+}
+
+impl VirtualClock {
+    /// A clock that runs for `length` of simulated time from now
+    pub fn new(length: Duration) -> Self {
+        Self::starting_at(Local::now(), length)
+    }
+
+    /// A clock that runs for `length` of simulated time from the local date
+    /// and time `local_start`
+    pub fn starting_at(local_start: DateTime<Local>, length: Duration) -> Self {
+        let start = Instant::now();
+        Self {
+            start,
+            local_start,
+            now: Mutex::new(start),
+            end: start + length,
+            wakeups: AtomicUsize::new(0),
+            actions: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Apply `action` to the waiting loop's handle once `after` of simulated
+    /// time has passed, such as to pause or reload it
+    pub fn schedule(&self, after: Duration, action: impl FnOnce(&Handle) + Send + 'static) {
+        self.actions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((self.start + after, Box::new(action)));
+    }
+
+    /// How many times the loop has woken up from a wait
+    pub fn wakeups(&self) -> usize {
+        self.wakeups.load(Ordering::SeqCst)
+    }
+
+    /// Simulated time since the clock was created
+    pub fn elapsed(&self) -> Duration {
+        self.now() - self.start
+    }
+
+    /// Take the earliest scheduled action due by `by`
+    fn next_action(&self, by: Instant) -> Option<(Instant, Action)> {
+        let mut actions = self.actions.lock().unwrap_or_else(|e| e.into_inner());
+        let index = (0..actions.len())
+            .filter(|&index| actions[index].0 <= by)
+            .min_by_key(|&index| actions[index].0)?;
+        Some(actions.remove(index))
+    }
+
+    fn advance_to(&self, time: Instant) {
+        let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+        *now = time.max(*now);
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn local_now(&self) -> DateTime<Local> {
+        self.local_start + TimeDelta::from_std(self.elapsed()).unwrap_or(TimeDelta::MAX)
+    }
+
+    /// Moves the clock on to `deadline` at once
+    fn sleep_until(&self, deadline: Instant) {
+        self.advance_to(deadline);
+    }
+
+    fn wait(&self, handle: &Handle, current: HandleState, until: Option<Instant>) -> HandleState {
+        self.wakeups.fetch_add(1, Ordering::SeqCst);
+        let until = until.filter(|until| *until <= self.end);
+        loop {
+            let state = handle.state();
+            if state != current {
+                return state;
+            }
+            match self.next_action(until.unwrap_or(self.end)) {
+                Some((at, action)) => {
+                    self.advance_to(at);
+                    action(handle);
+                }
+                None => break,
+            }
+        }
+        match until {
+            Some(until) => {
+                self.advance_to(until);
+                handle.state()
+            }
+            None => {
+                self.advance_to(self.end);
+                handle.stop();
+                handle.state()
+            }
+        }
+    }
+}
@@ -0,0 +1,719 @@
+//! Layered configuration loading
+//!
+//! The effective [`MouseMoverConfig`] is built from, in increasing order of
+//! precedence: the built-in defaults, the TOML config file, `KTMM_*`
+//! environment variables and finally the command-line flags.
+//!
+//! The config file lives at `$XDG_CONFIG_HOME/ktmm/config.toml` (falling back
+//! to `~/.config/ktmm/config.toml`) unless another path is given with
+//! `--config`. It uses the same names as the command-line flags:
+//!
+//! ```toml
+//! interval = 120
+//! interval_jitter = "exponential"
+//! dx = 2
+//! dy = 0
+//! random_offset = "1-3"
+//! pattern = "circle"
+//! nudge = "pointer"
+//! nudge_key = "F15"
+//! scroll_axis = "vertical"
+//! delay = 10
+//! idle_threshold = 30
+//! schedule = ["Mon-Fri 09:00-18:00"]
+//! cron = ["* 7-8 * * Sat"]
+//! calendar = "~/calendars/work.ics"
+//! calendar_tag = "demo"
+//! verbosity = "quiet"
+//! mode = "mouse"
+//! backend = "auto"
+//!
+//! [profile.presentation]
+//! interval = 20
+//!
+//! [profile.download]
+//! interval = 240
+//! ```
+//!
+//! Profiles start from the top-level settings, or from another profile named
+//! with `inherits = "NAME"`, and are selected with `--profile` or
+//! `KTMM_PROFILE`.
+
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::Spanned;
+
+use crate::cli::Cli;
+use crate::cron::CronExpr;
+use crate::jitter::{IntervalJitter, OffsetRange};
+use crate::keys::NudgeKey;
+use crate::pattern::Pattern;
+use crate::schedule::{Schedule, Window};
+use crate::timespec::TimeOfDay;
+use crate::{
+    BackendKind, KeepAwakeMode, KtmmError, MouseMoverConfig, NudgeKind, ScrollAxis, Verbosity,
+};
+
+/// A partial configuration from a single source
+///
+/// Only the fields that are set override the configuration the layer is
+/// applied to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigLayer {
+    pub interval_secs: Option<u64>,
+    pub interval_jitter: Option<IntervalJitter>,
+    pub dx: Option<i32>,
+    pub dy: Option<i32>,
+    pub random_offset: Option<OffsetRange>,
+    pub seed: Option<u64>,
+    pub pattern: Option<Pattern>,
+    pub nudge: Option<NudgeKind>,
+    pub nudge_key: Option<NudgeKey>,
+    pub scroll_axis: Option<ScrollAxis>,
+    pub return_delay_ms: Option<u64>,
+    pub duration_secs: Option<u64>,
+    pub until: Option<TimeOfDay>,
+    pub idle_threshold_secs: Option<u64>,
+    pub schedule: Option<Schedule>,
+    pub cron: Option<Vec<CronExpr>>,
+    pub calendar: Option<PathBuf>,
+    pub calendar_tag: Option<String>,
+    pub verbosity: Option<Verbosity>,
+    pub mode: Option<KeepAwakeMode>,
+    pub backend: Option<BackendKind>,
+}
+
+impl ConfigLayer {
+    /// Override the given configuration with the fields set in this layer
+    pub fn apply_to(&self, config: &mut MouseMoverConfig) {
+        if let Some(interval) = self.interval_secs {
+            config.interval_secs = interval;
+        }
+        if let Some(dx) = self.dx {
+            config.movement_pixels.0 = dx;
+        }
+        if let Some(dy) = self.dy {
+            config.movement_pixels.1 = dy;
+        }
+        if let Some(jitter) = self.interval_jitter {
+            config.interval_jitter = jitter;
+        }
+        if let Some(offset) = self.random_offset {
+            config.random_offset = Some(offset);
+        }
+        if let Some(seed) = self.seed {
+            config.seed = Some(seed);
+        }
+        if let Some(pattern) = self.pattern {
+            config.pattern = pattern;
+        }
+        if let Some(nudge) = self.nudge {
+            config.nudge = nudge;
+        }
+        if let Some(key) = self.nudge_key {
+            config.nudge_key = key;
+        }
+        if let Some(axis) = self.scroll_axis {
+            config.scroll_axis = axis;
+        }
+        if let Some(delay) = self.return_delay_ms {
+            config.return_delay_ms = delay;
+        }
+        if let Some(duration) = self.duration_secs {
+            config.duration_secs = Some(duration);
+        }
+        if let Some(until) = self.until {
+            config.until = Some(until);
+        }
+        if let Some(threshold) = self.idle_threshold_secs {
+            config.idle_threshold_secs = Some(threshold);
+        }
+        if let Some(schedule) = &self.schedule {
+            config.schedule = schedule.clone();
+        }
+        if let Some(cron) = &self.cron {
+            config.cron = cron.clone();
+        }
+        if let Some(calendar) = &self.calendar {
+            config.calendar = Some(calendar.clone());
+        }
+        if let Some(tag) = &self.calendar_tag {
+            config.calendar_tag = Some(tag.clone());
+        }
+        if let Some(verbosity) = self.verbosity {
+            config.verbosity = verbosity;
+        }
+        if let Some(mode) = self.mode {
+            config.mode = mode;
+        }
+        if let Some(backend) = self.backend {
+            config.backend = backend;
+        }
+    }
+
+    /// Override the fields set in this layer with the fields set in `other`
+    pub fn merge(&mut self, other: &ConfigLayer) {
+        self.interval_secs = other.interval_secs.or(self.interval_secs);
+        self.dx = other.dx.or(self.dx);
+        self.dy = other.dy.or(self.dy);
+        self.interval_jitter = other.interval_jitter.or(self.interval_jitter);
+        self.random_offset = other.random_offset.or(self.random_offset);
+        self.seed = other.seed.or(self.seed);
+        self.pattern = other.pattern.or(self.pattern);
+        self.nudge = other.nudge.or(self.nudge);
+        self.nudge_key = other.nudge_key.or(self.nudge_key);
+        self.scroll_axis = other.scroll_axis.or(self.scroll_axis);
+        self.return_delay_ms = other.return_delay_ms.or(self.return_delay_ms);
+        self.duration_secs = other.duration_secs.or(self.duration_secs);
+        self.until = other.until.or(self.until);
+        self.idle_threshold_secs = other.idle_threshold_secs.or(self.idle_threshold_secs);
+        self.schedule = other.schedule.clone().or(self.schedule.take());
+        self.cron = other.cron.clone().or(self.cron.take());
+        self.calendar = other.calendar.clone().or(self.calendar.take());
+        self.calendar_tag = other.calendar_tag.clone().or(self.calendar_tag.take());
+        self.verbosity = other.verbosity.or(self.verbosity);
+        self.mode = other.mode.or(self.mode);
+        self.backend = other.backend.or(self.backend);
+    }
+
+    /// Build a layer from the `KTMM_*` variables in the given environment
+    pub fn from_env(env: &HashMap<String, String>) -> Result<Self, KtmmError> {
+        Ok(Self {
+            interval_secs: env_var(env, "KTMM_INTERVAL")?,
+            interval_jitter: env_var(env, "KTMM_INTERVAL_JITTER")?,
+            dx: env_var(env, "KTMM_DX")?,
+            dy: env_var(env, "KTMM_DY")?,
+            random_offset: env_var(env, "KTMM_RANDOM_OFFSET")?,
+            seed: env_var(env, "KTMM_SEED")?,
+            pattern: env_var(env, "KTMM_PATTERN")?,
+            nudge: env_var(env, "KTMM_NUDGE")?,
+            nudge_key: env_var(env, "KTMM_NUDGE_KEY")?,
+            scroll_axis: env_var(env, "KTMM_SCROLL_AXIS")?,
+            return_delay_ms: env_var(env, "KTMM_DELAY")?,
+            duration_secs: env_var(env, "KTMM_DURATION")?,
+            until: env_var(env, "KTMM_UNTIL")?,
+            idle_threshold_secs: env_var(env, "KTMM_IDLE_THRESHOLD")?,
+            schedule: env_var(env, "KTMM_SCHEDULE")?,
+            cron: env_list(env, "KTMM_CRON")?,
+            calendar: env_var(env, "KTMM_CALENDAR")?,
+            calendar_tag: env_var(env, "KTMM_CALENDAR_TAG")?,
+            verbosity: env_var(env, "KTMM_VERBOSITY")?,
+            mode: env_var(env, "KTMM_MODE")?,
+            backend: env_var(env, "KTMM_BACKEND")?,
+        })
+    }
+}
+
+fn env_var<T>(env: &HashMap<String, String>, name: &str) -> Result<Option<T>, KtmmError>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match env.get(name).map(|value| value.trim()) {
+        None | Some("") => Ok(None),
+        Some(value) => value.parse().map(Some).map_err(|e| {
+            KtmmError::ConfigError(format!("{}: invalid value `{}`: {}", name, value, e))
+        }),
+    }
+}
+
+/// Read a `;`-separated list from the environment
+fn env_list<T>(env: &HashMap<String, String>, name: &str) -> Result<Option<Vec<T>>, KtmmError>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match env.get(name).map(|value| value.trim()) {
+        None | Some("") => Ok(None),
+        Some(value) => value
+            .split(';')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| {
+                item.parse().map_err(|e| {
+                    KtmmError::ConfigError(format!("{}: invalid value `{}`: {}", name, item, e))
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map(Some),
+    }
+}
+
+/// The keys accepted at the top level of the config file and in profiles
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileLayer {
+    interval: Option<Spanned<u64>>,
+    interval_jitter: Option<IntervalJitter>,
+    dx: Option<i32>,
+    dy: Option<i32>,
+    random_offset: Option<OffsetRange>,
+    seed: Option<u64>,
+    pattern: Option<Pattern>,
+    nudge: Option<NudgeKind>,
+    nudge_key: Option<NudgeKey>,
+    scroll_axis: Option<ScrollAxis>,
+    delay: Option<u64>,
+    duration: Option<Spanned<u64>>,
+    until: Option<TimeOfDay>,
+    idle_threshold: Option<Spanned<u64>>,
+    schedule: Option<Vec<Window>>,
+    cron: Option<Vec<CronExpr>>,
+    calendar: Option<PathBuf>,
+    calendar_tag: Option<String>,
+    verbosity: Option<Verbosity>,
+    mode: Option<KeepAwakeMode>,
+    backend: Option<BackendKind>,
+    /// Only valid inside a profile
+    inherits: Option<Spanned<String>>,
+    /// Only valid at the top level
+    profile: Option<Spanned<BTreeMap<String, FileLayer>>>,
+}
+
+impl FileLayer {
+    fn to_layer(
+        &self,
+        text: &str,
+        path: &Path,
+        env: &HashMap<String, String>,
+    ) -> Result<ConfigLayer, KtmmError> {
+        for (key, value) in [
+            ("interval", &self.interval),
+            ("duration", &self.duration),
+            ("idle_threshold", &self.idle_threshold),
+        ] {
+            if let Some(value) = value {
+                if *value.get_ref() == 0 {
+                    return Err(file_error(
+                        text,
+                        path,
+                        Some(value.span().start),
+                        &format!("`{}` must be at least 1 second", key),
+                    ));
+                }
+            }
+        }
+
+        Ok(ConfigLayer {
+            interval_secs: self.interval.as_ref().map(|v| *v.get_ref()),
+            interval_jitter: self.interval_jitter,
+            dx: self.dx,
+            dy: self.dy,
+            random_offset: self.random_offset,
+            seed: self.seed,
+            pattern: self.pattern,
+            nudge: self.nudge,
+            nudge_key: self.nudge_key,
+            scroll_axis: self.scroll_axis,
+            return_delay_ms: self.delay,
+            duration_secs: self.duration.as_ref().map(|v| *v.get_ref()),
+            until: self.until,
+            idle_threshold_secs: self.idle_threshold.as_ref().map(|v| *v.get_ref()),
+            schedule: self.schedule.clone().map(Schedule::new),
+            cron: self.cron.clone(),
+            calendar: self
+                .calendar
+                .as_deref()
+                .map(|calendar| resolve_path(calendar, path, env)),
+            calendar_tag: self.calendar_tag.clone(),
+            verbosity: self.verbosity,
+            mode: self.mode,
+            backend: self.backend,
+        })
+    }
+}
+
+/// A named set of settings from a `[profile.NAME]` table
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    /// The settings in the profile table itself
+    pub layer: ConfigLayer,
+    /// The profile this one builds on; profiles without one build on the
+    /// top-level settings directly
+    pub inherits: Option<String>,
+}
+
+/// A parsed config file
+#[derive(Debug, Clone, Default)]
+pub struct ConfigFile {
+    /// Where the file was read from
+    pub path: PathBuf,
+    /// The top-level settings, which every profile inherits
+    pub layer: ConfigLayer,
+    /// The named profiles
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl ConfigFile {
+    /// Read and parse the config file at `path`
+    pub fn load(path: &Path, env: &HashMap<String, String>) -> Result<Self, KtmmError> {
+        let text = fs::read_to_string(path).map_err(|e| {
+            KtmmError::ConfigError(format!("cannot read {}: {}", path.display(), e))
+        })?;
+        Self::parse(&text, path, env)
+    }
+
+    /// Parse config file contents
+    ///
+    /// `path` is used in error messages and to resolve relative paths, and
+    /// `$HOME` from `env` to resolve paths starting with `~/`.
+    pub fn parse(
+        text: &str,
+        path: &Path,
+        env: &HashMap<String, String>,
+    ) -> Result<Self, KtmmError> {
+        let raw: FileLayer = toml::from_str(text).map_err(|e| {
+            let location = e.span().map(|span| span.start);
+            file_error(text, path, location, e.message().trim_end())
+        })?;
+
+        if let Some(inherits) = &raw.inherits {
+            return Err(file_error(
+                text,
+                path,
+                Some(inherits.span().start),
+                "`inherits` is only valid inside a [profile.NAME] table",
+            ));
+        }
+
+        let mut file = Self {
+            path: path.to_path_buf(),
+            layer: raw.to_layer(text, path, env)?,
+            profiles: BTreeMap::new(),
+        };
+
+        let raw_profiles = raw.profile.map(Spanned::into_inner).unwrap_or_default();
+        for (name, raw_profile) in &raw_profiles {
+            if let Some(nested) = &raw_profile.profile {
+                return Err(file_error(
+                    text,
+                    path,
+                    Some(nested.span().start),
+                    &format!("profile `{}` cannot contain other profiles", name),
+                ));
+            }
+            if let Some(parent) = &raw_profile.inherits {
+                if !raw_profiles.contains_key(parent.get_ref()) {
+                    return Err(file_error(
+                        text,
+                        path,
+                        Some(parent.span().start),
+                        &format!(
+                            "profile `{}` inherits from unknown profile `{}`",
+                            name,
+                            parent.get_ref()
+                        ),
+                    ));
+                }
+            }
+            let profile = Profile {
+                layer: raw_profile.to_layer(text, path, env)?,
+                inherits: raw_profile.inherits.as_ref().map(|v| v.get_ref().clone()),
+            };
+            file.profiles.insert(name.clone(), profile);
+        }
+
+        // Reject inheritance cycles up front rather than when a profile is selected
+        for name in file.profiles.keys() {
+            file.profile_layer(name)?;
+        }
+
+        Ok(file)
+    }
+
+    /// The settings of a profile with its inheritance chain resolved
+    pub fn profile_layer(&self, name: &str) -> Result<ConfigLayer, KtmmError> {
+        let mut chain: Vec<&str> = Vec::new();
+        let mut current = Some(name);
+        while let Some(name) = current {
+            if chain.contains(&name) {
+                chain.push(name);
+                return Err(KtmmError::ConfigError(format!(
+                    "{}: profile inheritance cycle: {}",
+                    self.path.display(),
+                    chain.join(" -> ")
+                )));
+            }
+            let profile = self
+                .profiles
+                .get(name)
+                .ok_or_else(|| self.unknown_profile(name))?;
+            chain.push(name);
+            current = profile.inherits.as_deref();
+        }
+
+        let mut layer = self.layer.clone();
+        for name in chain.iter().rev() {
+            layer.merge(&self.profiles[*name].layer);
+        }
+        Ok(layer)
+    }
+
+    fn unknown_profile(&self, name: &str) -> KtmmError {
+        let available = if self.profiles.is_empty() {
+            "none".to_string()
+        } else {
+            self.profiles
+                .keys()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        KtmmError::ConfigError(format!(
+            "unknown profile `{}` in {} (available profiles: {})",
+            name,
+            self.path.display(),
+            available
+        ))
+    }
+}
+
+/// Resolve a path from the config file at `config_path`
+///
+/// A leading `~/` stands for `$HOME` from `env` and relative paths are
+/// relative to the directory of the config file.
+fn resolve_path(path: &Path, config_path: &Path, env: &HashMap<String, String>) -> PathBuf {
+    if let Ok(rest) = path.strip_prefix("~") {
+        if let Some(home) = env.get("HOME").filter(|home| !home.is_empty()) {
+            return PathBuf::from(home).join(rest);
+        }
+    }
+    match config_path.parent() {
+        Some(dir) if path.is_relative() => dir.join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Build an error message pointing at the line (and key) at byte `offset`
+fn file_error(text: &str, path: &Path, offset: Option<usize>, message: &str) -> KtmmError {
+    let Some(offset) = offset else {
+        return KtmmError::ConfigError(format!("{}: {}", path.display(), message));
+    };
+    let offset = offset.min(text.len());
+    let line_number = text[..offset].matches('\n').count() + 1;
+    let line = text.lines().nth(line_number - 1).unwrap_or("");
+    let key = line
+        .split_once('=')
+        .map(|(key, _)| key.trim())
+        .filter(|key| !key.is_empty() && !message.contains(&format!("`{}`", key)));
+    match key {
+        Some(key) => KtmmError::ConfigError(format!(
+            "{}:{}: `{}`: {}",
+            path.display(),
+            line_number,
+            key,
+            message
+        )),
+        None => KtmmError::ConfigError(format!("{}:{}: {}", path.display(), line_number, message)),
+    }
+}
+
+/// The default config file location for the given environment
+///
+/// This is `$XDG_CONFIG_HOME/ktmm/config.toml`, falling back to
+/// `$HOME/.config/ktmm/config.toml` (or `%APPDATA%\ktmm\config.toml` on Windows).
+pub fn default_config_path(env: &HashMap<String, String>) -> Option<PathBuf> {
+    let non_empty = |name: &str| {
+        env.get(name)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    };
+    let base = non_empty("XDG_CONFIG_HOME")
+        .or_else(|| non_empty("HOME").map(|home| home.join(".config")))
+        .or_else(|| non_empty("APPDATA"))?;
+    Some(base.join("ktmm").join("config.toml"))
+}
+
+/// Find and load the config file
+///
+/// An explicitly requested file must exist; the default file is optional.
+pub fn find_config_file(
+    explicit: Option<&Path>,
+    env: &HashMap<String, String>,
+) -> Result<Option<ConfigFile>, KtmmError> {
+    match explicit {
+        Some(path) => ConfigFile::load(path, env).map(Some),
+        None => match default_config_path(env) {
+            Some(path) if path.is_file() => ConfigFile::load(&path, env).map(Some),
+            _ => Ok(None),
+        },
+    }
+}
+
+/// Build the effective configuration for the current process
+pub fn load(cli: &Cli) -> Result<MouseMoverConfig, KtmmError> {
+    load_from(cli, &std::env::vars().collect())
+}
+
+/// Build the effective configuration from the given command line and environment
+pub fn load_from(cli: &Cli, env: &HashMap<String, String>) -> Result<MouseMoverConfig, KtmmError> {
+    let file = find_config_file(cli.config.as_deref(), env)?;
+    let profile = match &cli.profile {
+        Some(profile) => Some(profile.clone()),
+        None => env_var::<String>(env, "KTMM_PROFILE")?,
+    };
+    effective_config(file.as_ref(), profile.as_deref(), env, cli)
+}
+
+/// Layer the defaults, a config file profile, the environment and the command line
+///
+/// Without a profile only the top-level settings of the file are used.
+pub fn effective_config(
+    file: Option<&ConfigFile>,
+    profile: Option<&str>,
+    env: &HashMap<String, String>,
+    cli: &Cli,
+) -> Result<MouseMoverConfig, KtmmError> {
+    let mut config = MouseMoverConfig::default();
+    match (file, profile) {
+        (Some(file), Some(profile)) => file.profile_layer(profile)?.apply_to(&mut config),
+        (Some(file), None) => file.layer.apply_to(&mut config),
+        (None, Some(profile)) => {
+            return Err(KtmmError::ConfigError(format!(
+                "profile `{}` requested but no config file was found",
+                profile
+            )))
+        }
+        (None, None) => {}
+    }
+    ConfigLayer::from_env(env)?.apply_to(&mut config);
+    cli.apply_to(&mut config);
+    config.validate()?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<ConfigFile, KtmmError> {
+        ConfigFile::parse(text, Path::new("config.toml"), &HashMap::new())
+    }
+
+    fn error_message(result: Result<ConfigFile, KtmmError>) -> String {
+        match result {
+            Err(KtmmError::ConfigError(msg)) => msg,
+            other => panic!("expected a config error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_config_file() {
+        let file =
+            parse("interval = 120\ndx = 2\ndy = -1\ndelay = 10\nverbosity = \"quiet\"\n").unwrap();
+        assert_eq!(
+            file.layer,
+            ConfigLayer {
+                interval_secs: Some(120),
+                interval_jitter: None,
+                dx: Some(2),
+                dy: Some(-1),
+                random_offset: None,
+                seed: None,
+                pattern: None,
+                nudge: None,
+                nudge_key: None,
+                scroll_axis: None,
+                return_delay_ms: Some(10),
+                duration_secs: None,
+                until: None,
+                idle_threshold_secs: None,
+                schedule: None,
+                cron: None,
+                calendar: None,
+                calendar_tag: None,
+                verbosity: Some(Verbosity::Quiet),
+                mode: None,
+                backend: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_unknown_key_names_key_and_line() {
+        let msg = error_message(parse("interval = 30\n\nintervall = 30\n"));
+        assert!(msg.starts_with("config.toml:3:"), "{}", msg);
+        assert!(msg.contains("intervall"), "{}", msg);
+    }
+
+    #[test]
+    fn test_bad_value_names_key_and_line() {
+        let msg = error_message(parse("dx = 1\ninterval = \"often\"\n"));
+        assert!(msg.starts_with("config.toml:2: `interval`:"), "{}", msg);
+
+        let msg = error_message(parse("# comment\ninterval = 0\n"));
+        assert!(msg.starts_with("config.toml:2:"), "{}", msg);
+        assert!(msg.contains("`interval`"), "{}", msg);
+    }
+
+    #[test]
+    fn test_env_layer() {
+        let env = HashMap::from([
+            ("KTMM_INTERVAL".to_string(), "15".to_string()),
+            ("KTMM_VERBOSITY".to_string(), "verbose".to_string()),
+            ("KTMM_DX".to_string(), String::new()),
+            ("KTMM_IDLE_THRESHOLD".to_string(), "90".to_string()),
+        ]);
+        let layer = ConfigLayer::from_env(&env).unwrap();
+        assert_eq!(layer.interval_secs, Some(15));
+        assert_eq!(layer.idle_threshold_secs, Some(90));
+        assert_eq!(layer.verbosity, Some(Verbosity::Verbose));
+        assert_eq!(layer.dx, None);
+
+        let env = HashMap::from([("KTMM_DELAY".to_string(), "soon".to_string())]);
+        assert!(matches!(
+            ConfigLayer::from_env(&env),
+            Err(KtmmError::ConfigError(msg)) if msg.contains("KTMM_DELAY")
+        ));
+    }
+
+    #[test]
+    fn test_default_config_path() {
+        let env = HashMap::from([
+            ("XDG_CONFIG_HOME".to_string(), "/xdg".to_string()),
+            ("HOME".to_string(), "/home/user".to_string()),
+        ]);
+        assert_eq!(
+            default_config_path(&env),
+            Some(PathBuf::from("/xdg/ktmm/config.toml"))
+        );
+
+        let env = HashMap::from([("HOME".to_string(), "/home/user".to_string())]);
+        assert_eq!(
+            default_config_path(&env),
+            Some(PathBuf::from("/home/user/.config/ktmm/config.toml"))
+        );
+    }
+
+    #[test]
+    fn test_calendar_paths_resolve_against_home_and_config_dir() {
+        let env = HashMap::from([("HOME".to_string(), "/home/user".to_string())]);
+        let path = Path::new("/etc/ktmm/config.toml");
+        let calendar = |text: &str| ConfigFile::parse(text, path, &env).unwrap().layer.calendar;
+        assert_eq!(
+            calendar("calendar = \"~/work.ics\"\n"),
+            Some(PathBuf::from("/home/user/work.ics"))
+        );
+        assert_eq!(
+            calendar("calendar = \"work.ics\"\n"),
+            Some(PathBuf::from("/etc/ktmm/work.ics"))
+        );
+    }
+
+    #[test]
+    fn test_profile_inheritance_errors() {
+        let msg = error_message(parse("[profile.a]\ninherits = \"b\"\n"));
+        assert!(msg.starts_with("config.toml:2:"), "{}", msg);
+        assert!(msg.contains("unknown profile `b`"), "{}", msg);
+
+        let msg = error_message(parse(
+            "[profile.a]\ninherits = \"b\"\n[profile.b]\ninherits = \"a\"\n",
+        ));
+        assert!(msg.contains("cycle: a -> b -> a"), "{}", msg);
+
+        let msg = error_message(parse("inherits = \"a\"\n"));
+        assert!(msg.starts_with("config.toml:1:"), "{}", msg);
+    }
+}
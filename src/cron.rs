@@ -0,0 +1,381 @@
+//! Cron expressions as keep-awake windows
+//!
+//! A cron expression such as `* 9-17 * * Mon-Fri` keeps the system awake
+//! during every local minute it matches, so unlike in a crontab, the minute
+//! field is usually `*`. The five fields are minute, hour, day of month, month
+//! and day of week, and each accepts `*`, numbers, ranges (`1-5`), steps
+//! (`*/15`, `0-30/10`) and comma-separated lists. Months and days of the week
+//! may also be given by name (`Jan`, `Mon`); Sunday is both `0` and `7`. As
+//! in cron, when both the day of month and the day of week are restricted,
+//! a day matching either one matches; a field starting with `*`, even a step
+//! such as `*/2`, does not count as restricted.
+
+use chrono::{
+    DateTime, Datelike, DurationRound, LocalResult, Months, NaiveDate, NaiveDateTime, TimeDelta,
+    TimeZone, Timelike,
+};
+use serde::Deserialize;
+use std::fmt;
+
+/// How far ahead to look for the next change before giving up; long enough
+/// to reach an expression that only matches on 29 February
+const SEARCH_DAYS: i64 = 8 * 366;
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const DAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A parsed five-field cron expression
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct CronExpr {
+    text: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// Whether the day of month field did not start with `*`
+    dom_restricted: bool,
+    /// Whether the day of week field did not start with `*`
+    dow_restricted: bool,
+}
+
+impl CronExpr {
+    /// Whether the local date and time falls in a matching minute
+    pub fn matches(&self, time: &NaiveDateTime) -> bool {
+        self.day_matches(time.date())
+            && has(self.minutes, time.minute())
+            && has(self.hours, time.hour())
+    }
+
+    /// Whether any minute of the local date can match
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let dom = has(self.days_of_month, date.day());
+        let dow = has(self.days_of_week, date.weekday().num_days_from_sunday());
+        let day = if self.dom_restricted && self.dow_restricted {
+            dom || dow
+        } else {
+            dom && dow
+        };
+        day && has(self.months, date.month())
+    }
+
+    /// Whether the expression matches the minute containing `now`
+    pub fn is_active_at<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> bool {
+        self.matches(&now.naive_local())
+    }
+
+    /// The start of the first minute after `now` in which the match changes
+    ///
+    /// Local minutes skipped by a DST change never match, and ones repeated
+    /// by it are only found once. Returns `None` if nothing changes within
+    /// the next eight years.
+    pub fn next_change<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let active = self.is_active_at(now);
+        let minute = now
+            .naive_local()
+            .duration_trunc(TimeDelta::minutes(1))
+            .ok()?;
+        let limit = minute + TimeDelta::days(SEARCH_DAYS);
+        let mut from = minute + TimeDelta::minutes(1);
+        loop {
+            let local = if active {
+                self.first_mismatch(from, limit)
+            } else {
+                self.first_match(from, limit)
+            }?;
+            match now.timezone().from_local_datetime(&local) {
+                LocalResult::Single(time) if time > *now => return Some(time),
+                LocalResult::Ambiguous(first, second) => {
+                    if first > *now {
+                        return Some(first);
+                    }
+                    if second > *now {
+                        return Some(second);
+                    }
+                }
+                // Skipped by a DST change: look on from the next minute
+                _ => {}
+            }
+            from = local + TimeDelta::minutes(1);
+        }
+    }
+
+    /// The first local minute from `from` up to `limit` that matches
+    ///
+    /// Months, days and hours that cannot match are skipped whole.
+    fn first_match(&self, from: NaiveDateTime, limit: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut time = from;
+        while time <= limit {
+            let date = time.date();
+            time = if !has(self.months, date.month()) {
+                date.with_day(1)?
+                    .checked_add_months(Months::new(1))?
+                    .and_hms_opt(0, 0, 0)?
+            } else if !self.day_matches(date) {
+                date.succ_opt()?.and_hms_opt(0, 0, 0)?
+            } else if !has(self.hours, time.hour()) {
+                next_hour(time)?
+            } else {
+                match (time.minute()..60).find(|&minute| has(self.minutes, minute)) {
+                    Some(minute) => return time.with_minute(minute),
+                    None => next_hour(time)?,
+                }
+            };
+        }
+        None
+    }
+
+    /// The first local minute from `from` up to `limit` that does not match
+    ///
+    /// Runs of matching minutes are skipped to the end of their hour or day.
+    fn first_mismatch(&self, from: NaiveDateTime, limit: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut time = from;
+        while time <= limit {
+            if !self.matches(&time) {
+                return Some(time);
+            }
+            let date = time.date();
+            if let Some(minute) = (time.minute()..60).find(|&minute| !has(self.minutes, minute)) {
+                return time.with_minute(minute);
+            }
+            time = if !is_full(self.minutes, 0, 59) {
+                next_hour(time)?
+            } else {
+                match (time.hour()..24).find(|&hour| !has(self.hours, hour)) {
+                    Some(hour) => return date.and_hms_opt(hour, 0, 0),
+                    None => date.succ_opt()?.and_hms_opt(0, 0, 0)?,
+                }
+            };
+        }
+        None
+    }
+}
+
+/// Whether the bit set contains `value`
+fn has(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Whether the bit set contains every value from `min` to `max`
+fn is_full(set: u64, min: u32, max: u32) -> bool {
+    (min..=max).all(|value| has(set, value))
+}
+
+/// The start of the hour after the one containing `time`
+fn next_hour(time: NaiveDateTime) -> Option<NaiveDateTime> {
+    Some(time.date().and_hms_opt(time.hour(), 0, 0)? + TimeDelta::hours(1))
+}
+
+impl std::str::FromStr for CronExpr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields[..] else {
+            return Err(format!(
+                "invalid cron expression `{}`: expected 5 fields (minute hour day month weekday)",
+                s.trim()
+            ));
+        };
+        let field = |text: &str, min: u32, max: u32, names: &[&str], what: &str| {
+            parse_field(text, min, max, names)
+                .map_err(|e| format!("invalid cron expression `{}`: {}: {}", s.trim(), what, e))
+        };
+        let mut days_of_week = field(dow, 0, 7, &DAYS, "day of week")?;
+        // 7 is another name for Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            text: fields.join(" "),
+            minutes: field(minute, 0, 59, &[], "minute")?,
+            hours: field(hour, 0, 23, &[], "hour")?,
+            days_of_month: field(dom, 1, 31, &[], "day of month")?,
+            months: field(month, 1, 12, &MONTHS, "month")?,
+            days_of_week,
+            dom_restricted: !dom.starts_with('*'),
+            dow_restricted: !dow.starts_with('*'),
+        })
+    }
+}
+
+impl TryFrom<String> for CronExpr {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for CronExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/// Parse one field into a bit set of the values it matches
+///
+/// `names` spells out the values from `min` upwards, if the field has names.
+fn parse_field(text: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |part: &str| -> Result<u32, String> {
+        let lower = part.to_ascii_lowercase();
+        let value = match names.iter().position(|name| *name == lower) {
+            Some(index) => index as u32 + min,
+            None => part
+                .parse()
+                .map_err(|_| format!("`{}` is not a number", part))?,
+        };
+        if (min..=max).contains(&value) {
+            Ok(value)
+        } else {
+            Err(format!("{} is out of range {}-{}", value, min, max))
+        }
+    };
+
+    let mut set = 0;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(format!("invalid step `{}`", step)),
+            },
+            None => (part, 1),
+        };
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (value(first)?, value(last)?),
+            // `5/15` runs from 5 to the end of the range
+            None if step > 1 => (value(range)?, max),
+            None => (value(range)?, value(range)?),
+        };
+        if first > last {
+            return Err(format!("range `{}` runs backwards", range));
+        }
+        for value in (first..=last).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, NaiveDate};
+    use chrono_tz::Europe::Berlin;
+
+    fn at(date: (i32, u32, u32), hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(date.0, date.1, date.2)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        let working: CronExpr = "* 9-17 * * Mon-Fri".parse().unwrap();
+        // 2024-05-10 is a Friday
+        assert!(working.matches(&at((2024, 5, 10), 9, 0)));
+        assert!(working.matches(&at((2024, 5, 10), 17, 59)));
+        assert!(!working.matches(&at((2024, 5, 10), 18, 0)));
+        assert!(!working.matches(&at((2024, 5, 11), 12, 0)));
+
+        let quarters: CronExpr = "*/15 * * * *".parse().unwrap();
+        assert!(quarters.matches(&at((2024, 5, 10), 3, 45)));
+        assert!(!quarters.matches(&at((2024, 5, 10), 3, 46)));
+
+        let sundays: CronExpr = "0 12 * jan,DEC 7".parse().unwrap();
+        assert!(sundays.matches(&at((2024, 1, 7), 12, 0)));
+        assert!(!sundays.matches(&at((2024, 2, 4), 12, 0)));
+
+        for bad in [
+            "* * * *",
+            "60 * * * *",
+            "* * * * Funday",
+            "*/0 * * * *",
+            "5-1 * * * *",
+        ] {
+            assert!(bad.parse::<CronExpr>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_day_of_month_or_day_of_week() {
+        // The 1st of the month or any Monday
+        let expr: CronExpr = "* * 1 * Mon".parse().unwrap();
+        assert!(expr.matches(&at((2024, 5, 1), 8, 0)));
+        assert!(expr.matches(&at((2024, 5, 13), 8, 0)));
+        assert!(!expr.matches(&at((2024, 5, 14), 8, 0)));
+
+        // A step over `*` leaves the field unrestricted, so odd days that are
+        // also Mondays
+        let expr: CronExpr = "* 9-17 */2 * Mon".parse().unwrap();
+        assert!(expr.matches(&at((2024, 5, 13), 9, 0)));
+        assert!(!expr.matches(&at((2024, 5, 6), 9, 0)));
+        assert!(!expr.matches(&at((2024, 5, 15), 9, 0)));
+    }
+
+    #[test]
+    fn test_next_change() {
+        let tz = FixedOffset::east_opt(0).unwrap();
+        let working: CronExpr = "* 9-17 * * Mon-Fri".parse().unwrap();
+        let friday = tz.with_ymd_and_hms(2024, 5, 10, 12, 30, 20).unwrap();
+        assert_eq!(
+            working.next_change(&friday),
+            tz.with_ymd_and_hms(2024, 5, 10, 18, 0, 0).single()
+        );
+        let evening = tz.with_ymd_and_hms(2024, 5, 10, 18, 0, 0).unwrap();
+        assert_eq!(
+            working.next_change(&evening),
+            tz.with_ymd_and_hms(2024, 5, 13, 9, 0, 0).single()
+        );
+        let always: CronExpr = "* * * * *".parse().unwrap();
+        assert_eq!(always.next_change(&friday), None);
+    }
+
+    #[test]
+    fn test_next_change_of_sparse_expressions() {
+        let tz = FixedOffset::east_opt(0).unwrap();
+        let friday = tz.with_ymd_and_hms(2024, 5, 10, 12, 30, 20).unwrap();
+        let new_year: CronExpr = "0 0 1 1 *".parse().unwrap();
+        assert_eq!(
+            new_year.next_change(&friday),
+            tz.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).single()
+        );
+        let midnight = tz.with_ymd_and_hms(2025, 1, 1, 0, 0, 30).unwrap();
+        assert_eq!(
+            new_year.next_change(&midnight),
+            tz.with_ymd_and_hms(2025, 1, 1, 0, 1, 0).single()
+        );
+
+        let leap_day: CronExpr = "30 6 29 2 *".parse().unwrap();
+        let march = tz.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            leap_day.next_change(&march),
+            tz.with_ymd_and_hms(2028, 2, 29, 6, 30, 0).single()
+        );
+        let never: CronExpr = "* * 31 2 *".parse().unwrap();
+        assert_eq!(never.next_change(&march), None);
+
+        // Whole matching days are skipped at once
+        let weekend: CronExpr = "* * * * Sat,Sun".parse().unwrap();
+        assert_eq!(
+            weekend.next_change(&tz.with_ymd_and_hms(2024, 5, 11, 8, 0, 0).unwrap()),
+            tz.with_ymd_and_hms(2024, 5, 13, 0, 0, 0).single()
+        );
+    }
+
+    #[test]
+    fn test_skipped_minutes_never_match() {
+        // Clocks go forward in Berlin on 2024-03-31 from 02:00 to 03:00
+        let expr: CronExpr = "* 2 * * *".parse().unwrap();
+        let before = Berlin.with_ymd_and_hms(2024, 3, 31, 1, 30, 0).unwrap();
+        assert_eq!(
+            expr.next_change(&before),
+            Berlin.with_ymd_and_hms(2024, 4, 1, 2, 0, 0).single()
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! The control socket of `ktmm daemon`
+//!
+//! A daemon listens on a Unix socket, by default `$XDG_RUNTIME_DIR/ktmm.sock`,
+//! and `ktmm status`, `pause`, `resume`, `stop` and `set-interval` talk to it.
+//! Each connection carries a single exchange: the client writes one JSON
+//! request line and the daemon answers with one JSON response line.
+//!
+//! ```text
+//! {"version":1,"command":"set-interval","secs":30}
+//! {"version":1,"ok":true,"status":{"pid":4242,"paused":false,"interval_secs":30,"mode":"mouse","uptime_secs":75}}
+//! ```
+//!
+//! Every message carries the [`PROTOCOL_VERSION`]; a daemon refuses requests
+//! of any other version with an error response rather than guessing.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::{instance, Handle, HandleState, KtmmError};
+
+/// The version of the request/response protocol spoken on the socket
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// How long either side waits for the other's line
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Something a client asks the daemon to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    /// Report the daemon's state
+    Status,
+    /// Stop nudging until resumed
+    Pause,
+    /// Start nudging again
+    Resume,
+    /// Shut the daemon down
+    Stop,
+    /// Change the time between nudges
+    SetInterval { secs: u64 },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RequestMessage {
+    version: u32,
+    #[serde(flatten)]
+    request: Request,
+}
+
+/// What a daemon reports about itself
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Status {
+    pub pid: u32,
+    pub paused: bool,
+    pub interval_secs: u64,
+    pub mode: String,
+    pub uptime_secs: u64,
+}
+
+/// The daemon's answer to a request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Response {
+    pub version: u32,
+    pub ok: bool,
+    /// The state after handling the request, when it succeeded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+    /// Why the request failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Response {
+    fn success(status: Status) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            ok: true,
+            status: Some(status),
+            error: None,
+        }
+    }
+
+    fn failure(error: String) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            ok: false,
+            status: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// The socket path: `$XDG_RUNTIME_DIR/ktmm.sock`, or a per-user path in the
+/// temporary directory when there is no runtime directory
+pub fn socket_path(env: &HashMap<String, String>) -> PathBuf {
+    instance::runtime_path(env, "sock")
+}
+
+/// A listening control socket, removed again when dropped
+#[derive(Debug)]
+pub struct Server {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl Server {
+    /// Listen on `path`, replacing a stale socket left behind by a daemon
+    /// that did not shut down cleanly
+    pub fn bind(path: &Path) -> Result<Self, KtmmError> {
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(KtmmError::ControlError(format!(
+                    "a ktmm daemon is already listening on {}",
+                    path.display()
+                )));
+            }
+            let _ = fs::remove_file(path);
+        }
+        let bind_error = |e: io::Error| {
+            KtmmError::ControlError(format!("cannot listen on {}: {}", path.display(), e))
+        };
+        let listener = UnixListener::bind(path).map_err(bind_error)?;
+        // Only the owner may control the daemon, wherever the socket lives
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600)).map_err(bind_error)?;
+        Ok(Self {
+            listener,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// The path the server listens on
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Answer requests on a new thread by applying them to `handle`
+    ///
+    /// `status` describes the daemon in a given handle state. Each connection
+    /// is answered on a thread of its own, so a client that is slow to send
+    /// its request does not hold up the others. The accepting thread runs
+    /// until the process exits.
+    pub fn serve<F>(&self, handle: Handle, status: F) -> Result<(), KtmmError>
+    where
+        F: Fn(&HandleState) -> Status + Send + Sync + 'static,
+    {
+        let listener = self
+            .listener
+            .try_clone()
+            .map_err(|e| KtmmError::ControlError(format!("cannot accept connections: {}", e)))?;
+        let status = Arc::new(status);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let handle = handle.clone();
+                let status = status.clone();
+                thread::spawn(move || {
+                    // A misbehaving client only loses its own connection
+                    let _ = answer(stream, &handle, &*status);
+                });
+            }
+        });
+        Ok(())
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Read one request from `stream`, apply it and write the response
+fn answer(
+    stream: UnixStream,
+    handle: &Handle,
+    status: &impl Fn(&HandleState) -> Status,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+
+    let request = match serde_json::from_str::<RequestMessage>(&line) {
+        Ok(message) if message.version == PROTOCOL_VERSION => Ok(message.request),
+        Ok(message) => Err(format!(
+            "unsupported protocol version {} (this daemon speaks version {})",
+            message.version, PROTOCOL_VERSION
+        )),
+        Err(e) => Err(format!("invalid request: {}", e)),
+    };
+    let response = match request {
+        Ok(Request::Status) => Response::success(status(&handle.state())),
+        Ok(Request::Pause) => {
+            handle.pause();
+            Response::success(status(&handle.state()))
+        }
+        Ok(Request::Resume) => {
+            handle.resume();
+            Response::success(status(&handle.state()))
+        }
+        Ok(Request::SetInterval { secs }) => match handle.set_interval(secs) {
+            Ok(()) => Response::success(status(&handle.state())),
+            Err(e) => Response::failure(e.to_string()),
+        },
+        Ok(Request::Stop) => {
+            // Answer before stopping, as the daemon exits right after
+            let stopping = HandleState {
+                stopped: true,
+                ..handle.state()
+            };
+            let result = write_line(&stream, &Response::success(status(&stopping)));
+            handle.stop();
+            return result;
+        }
+        Err(error) => Response::failure(error),
+    };
+    write_line(&stream, &response)
+}
+
+fn write_line<T: Serialize>(mut stream: &UnixStream, message: &T) -> io::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    stream.write_all(&line)?;
+    stream.flush()
+}
+
+/// Send `request` to the daemon listening on `path` and return its status
+/// afterwards
+pub fn send(path: &Path, request: Request) -> Result<Status, KtmmError> {
+    let error = |e: io::Error| {
+        KtmmError::ControlError(format!(
+            "cannot talk to a ktmm daemon on {}: {}",
+            path.display(),
+            e
+        ))
+    };
+    let stream = UnixStream::connect(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused => {
+            KtmmError::ControlError(format!("no ktmm daemon is listening on {}", path.display()))
+        }
+        _ => error(e),
+    })?;
+    stream.set_read_timeout(Some(IO_TIMEOUT)).map_err(error)?;
+    stream.set_write_timeout(Some(IO_TIMEOUT)).map_err(error)?;
+    let message = RequestMessage {
+        version: PROTOCOL_VERSION,
+        request,
+    };
+    write_line(&stream, &message).map_err(error)?;
+
+    let mut line = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut line)
+        .map_err(error)?;
+    let response: Response = serde_json::from_str(&line)
+        .map_err(|e| KtmmError::ControlError(format!("invalid response from the daemon: {}", e)))?;
+    if response.version != PROTOCOL_VERSION {
+        return Err(KtmmError::ControlError(format!(
+            "the daemon speaks protocol version {}, but this ktmm speaks version {}",
+            response.version, PROTOCOL_VERSION
+        )));
+    }
+    match (response.ok, response.status, response.error) {
+        (true, Some(status), _) => Ok(status),
+        (_, _, Some(error)) => Err(KtmmError::ControlError(error)),
+        _ => Err(KtmmError::ControlError(
+            "the daemon sent an empty response".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_wire_format() {
+        let message = RequestMessage {
+            version: PROTOCOL_VERSION,
+            request: Request::SetInterval { secs: 30 },
+        };
+        assert_eq!(
+            serde_json::to_string(&message).unwrap(),
+            r#"{"version":1,"command":"set-interval","secs":30}"#
+        );
+        let parsed: RequestMessage =
+            serde_json::from_str(r#"{"version":1,"command":"pause"}"#).unwrap();
+        assert_eq!(parsed.request, Request::Pause);
+        assert!(
+            serde_json::from_str::<RequestMessage>(r#"{"version":1,"command":"dance"}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn test_socket_path() {
+        let env = HashMap::from([("XDG_RUNTIME_DIR".to_string(), "/run/user/1000".to_string())]);
+        assert_eq!(socket_path(&env), Path::new("/run/user/1000/ktmm.sock"));
+        let fallback = socket_path(&HashMap::new());
+        assert!(fallback.starts_with(std::env::temp_dir()));
+    }
+}
@@ -0,0 +1,170 @@
+//! Screen geometry used to keep nudges on screen
+//!
+//! A nudge of `(dx, dy)` from a pointer sitting at the right or bottom edge
+//! of a monitor would land off-screen, where it is clamped (so nothing moves)
+//! or ends up on a neighbouring monitor. [`plan_nudge`] picks a target on the
+//! same monitor instead, flipping the direction where necessary.
+
+/// A monitor's area in screen coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    /// Create a rectangle from its top-left corner and size
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Whether the pixel at `point` lies inside the rectangle
+    pub fn contains(&self, point: (i32, i32)) -> bool {
+        let (x, y) = point;
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+/// Pick where to nudge the pointer at `position` to
+///
+/// The target is `position + delta` if that stays on the pointer's monitor;
+/// otherwise the direction is flipped on one or both axes, and if even that
+/// does not fit (a tiny monitor or a huge delta) an adjacent pixel is used.
+/// Without any known monitor geometry the delta is applied unchanged.
+pub fn plan_nudge(position: (i32, i32), delta: (i32, i32), monitors: &[Rect]) -> (i32, i32) {
+    let (x, y) = position;
+    let (dx, dy) = delta;
+    let Some(monitor) = monitors.iter().find(|monitor| monitor.contains(position)) else {
+        return (x + dx, y + dy);
+    };
+
+    let flips = [(dx, dy), (-dx, dy), (dx, -dy), (-dx, -dy)];
+    let neighbours = [
+        (1, 0),
+        (-1, 0),
+        (0, 1),
+        (0, -1),
+        (1, 1),
+        (-1, -1),
+        (1, -1),
+        (-1, 1),
+    ];
+    flips
+        .into_iter()
+        .chain(neighbours)
+        .map(|(dx, dy)| (x + dx, y + dy))
+        .find(|&target| target != position && monitor.contains(target))
+        .unwrap_or((x + dx, y + dy))
+}
+
+/// Pick the screen positions for tracing `path`, a list of offsets from the
+/// pointer at `position`
+///
+/// The path is mirrored on one or both axes if that keeps all of it on the
+/// pointer's monitor; a path with a single point off the start is planned
+/// like [`plan_nudge`]. Otherwise, or without known monitor geometry, the
+/// offsets are applied unchanged.
+pub fn plan_path(position: (i32, i32), path: &[(i32, i32)], monitors: &[Rect]) -> Vec<(i32, i32)> {
+    let (x, y) = position;
+    let mut away = path.iter().filter(|&&offset| offset != (0, 0));
+    if let Some(&delta) = away.next() {
+        if away.all(|&offset| offset == delta) {
+            let target = plan_nudge(position, delta, monitors);
+            return path
+                .iter()
+                .map(|&offset| if offset == delta { target } else { position })
+                .collect();
+        }
+    }
+
+    let trace = |(sx, sy): (i32, i32)| -> Vec<(i32, i32)> {
+        path.iter()
+            .map(|&(dx, dy)| (x + sx * dx, y + sy * dy))
+            .collect()
+    };
+    if let Some(monitor) = monitors.iter().find(|monitor| monitor.contains(position)) {
+        for mirror in [(1, 1), (-1, 1), (1, -1), (-1, -1)] {
+            let points = trace(mirror);
+            if points.iter().all(|&point| monitor.contains(point)) {
+                return points;
+            }
+        }
+    }
+    trace((1, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEFT: Rect = Rect {
+        x: 0,
+        y: 0,
+        width: 1920,
+        height: 1080,
+    };
+    const RIGHT: Rect = Rect {
+        x: 1920,
+        y: 0,
+        width: 1280,
+        height: 1024,
+    };
+
+    #[test]
+    fn test_nudge_inside_monitor_is_unchanged() {
+        assert_eq!(plan_nudge((100, 100), (2, 3), &[LEFT]), (102, 103));
+        assert_eq!(plan_nudge((100, 100), (2, 3), &[]), (102, 103));
+    }
+
+    #[test]
+    fn test_nudge_flips_at_screen_edges() {
+        assert_eq!(
+            plan_nudge((3199, 1023), (1, 1), &[LEFT, RIGHT]),
+            (3198, 1022)
+        );
+        assert_eq!(plan_nudge((500, 1079), (1, 1), &[LEFT, RIGHT]), (501, 1078));
+        assert_eq!(plan_nudge((0, 0), (-1, -1), &[LEFT]), (1, 1));
+    }
+
+    #[test]
+    fn test_nudge_stays_on_the_same_monitor() {
+        // Moving right from the last column of the left monitor would land on the right one
+        assert_eq!(plan_nudge((1919, 500), (1, 0), &[LEFT, RIGHT]), (1918, 500));
+        // Below the right monitor's bottom edge is outside every monitor
+        assert_eq!(
+            plan_nudge((2000, 1023), (0, 1), &[LEFT, RIGHT]),
+            (2000, 1022)
+        );
+    }
+
+    #[test]
+    fn test_nudge_falls_back_to_a_neighbouring_pixel() {
+        let tiny = Rect::new(0, 0, 3, 3);
+        assert_eq!(plan_nudge((1, 1), (5, 5), &[tiny]), (2, 1));
+    }
+
+    #[test]
+    fn test_path_is_mirrored_to_stay_on_screen() {
+        let square = [(5, 0), (5, 5), (0, 5), (0, 0)];
+        assert_eq!(
+            plan_path((100, 100), &square, &[LEFT]),
+            [(105, 100), (105, 105), (100, 105), (100, 100)]
+        );
+        assert_eq!(
+            plan_path((1919, 1079), &square, &[LEFT, RIGHT]),
+            [(1914, 1079), (1914, 1074), (1919, 1074), (1919, 1079)]
+        );
+        // A single point off the start is planned like a nudge
+        assert_eq!(
+            plan_path((1919, 0), &[(1, 1), (0, 0)], &[LEFT, RIGHT]),
+            [(1918, 1), (1919, 0)]
+        );
+    }
+}
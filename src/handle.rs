@@ -0,0 +1,326 @@
+//! Controlling a running [`MouseMover`](crate::MouseMover) from other threads
+//!
+//! A [`Handle`] is shared between the mover's loop and whoever wants to stop,
+//! pause, nudge or reconfigure it. The loop sleeps on the handle's condition
+//! variable, so every change wakes it immediately instead of after the
+//! current interval. A heartbeat set with [`Handle::set_heartbeat`] is called
+//! from those waits, which tells a watchdog that the loop is still turning.
+
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::{KtmmError, MouseMoverConfig};
+
+/// The controllable state of a running mover
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HandleState {
+    /// The mover has been told to stop
+    pub stopped: bool,
+    /// The mover keeps running but does not move the mouse
+    pub paused: bool,
+    /// Overrides the configured interval when set
+    pub interval_secs: Option<u64>,
+    /// Counts the requests for an immediate nudge
+    pub nudge_requests: u64,
+    /// Counts the configurations passed to [`Handle::reload`]
+    pub config_generation: u64,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    state: Mutex<HandleState>,
+    changed: Condvar,
+    /// The configuration passed to the latest [`Handle::reload`], until the
+    /// mover takes it
+    pending_config: Mutex<Option<MouseMoverConfig>>,
+    heartbeat: Mutex<Option<Heartbeat>>,
+}
+
+/// A callback made at least every `every` while the loop waits
+struct Heartbeat {
+    every: Duration,
+    due: Instant,
+    beat: Box<dyn FnMut() + Send>,
+}
+
+impl fmt::Debug for Heartbeat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Heartbeat")
+            .field("every", &self.every)
+            .field("due", &self.due)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A cloneable remote control for a running mover
+#[derive(Debug, Clone, Default)]
+pub struct Handle {
+    shared: Arc<Shared>,
+}
+
+impl Handle {
+    /// Create a handle for a mover that has not been stopped or paused
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop the mover; it cannot be restarted with the same handle
+    pub fn stop(&self) {
+        self.update(|state| state.stopped = true);
+    }
+
+    /// Stop moving the mouse until [`resume`](Self::resume) is called
+    pub fn pause(&self) {
+        self.update(|state| state.paused = true);
+    }
+
+    /// Start moving the mouse again after [`pause`](Self::pause)
+    pub fn resume(&self) {
+        self.update(|state| state.paused = false);
+    }
+
+    /// Pause a running mover or resume a paused one, returning whether it is
+    /// now paused
+    pub fn toggle_pause(&self) -> bool {
+        let mut paused = false;
+        self.update(|state| {
+            state.paused = !state.paused;
+            paused = state.paused;
+        });
+        paused
+    }
+
+    /// Nudge right away, then wait a full interval for the next nudge
+    ///
+    /// This also nudges a paused mover, but not outside the schedule.
+    pub fn nudge_now(&self) {
+        self.update(|state| state.nudge_requests = state.nudge_requests.wrapping_add(1));
+    }
+
+    /// Replace the mover's configuration, such as after the config file changed
+    ///
+    /// The new configuration also replaces any interval set with
+    /// [`set_interval`](Self::set_interval). The input backend and the
+    /// keep-awake mode cannot change while running, so those settings are
+    /// ignored.
+    pub fn reload(&self, config: MouseMoverConfig) {
+        *self
+            .shared
+            .pending_config
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(config);
+        self.update(|state| {
+            state.interval_secs = None;
+            state.config_generation = state.config_generation.wrapping_add(1);
+        });
+    }
+
+    /// Take the configuration passed to [`reload`](Self::reload), if the
+    /// mover has not picked it up yet
+    pub fn take_config(&self) -> Option<MouseMoverConfig> {
+        self.shared
+            .pending_config
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+
+    /// Change the time between mouse movements, restarting the current wait
+    pub fn set_interval(&self, secs: u64) -> Result<(), KtmmError> {
+        if secs == 0 {
+            return Err(KtmmError::ConfigError(
+                "interval must be at least 1 second".to_string(),
+            ));
+        }
+        self.update(|state| state.interval_secs = Some(secs));
+        Ok(())
+    }
+
+    /// Call `beat` at least every `every` while the mover waits
+    ///
+    /// The beats come from the thread blocked in
+    /// [`wait_for_change`](Self::wait_for_change), the first one as soon as it
+    /// next waits, so they stop when the loop hangs. This suits watchdog pings.
+    pub fn set_heartbeat(&self, every: Duration, beat: impl FnMut() + Send + 'static) {
+        *self
+            .shared
+            .heartbeat
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(Heartbeat {
+            every,
+            due: Instant::now(),
+            beat: Box::new(beat),
+        });
+    }
+
+    /// Whether [`stop`](Self::stop) has been called
+    pub fn is_stopped(&self) -> bool {
+        self.state().stopped
+    }
+
+    /// Whether the mover is paused
+    pub fn is_paused(&self) -> bool {
+        self.state().paused
+    }
+
+    /// A snapshot of the current state
+    pub fn state(&self) -> HandleState {
+        *self.lock()
+    }
+
+    /// Block until the state differs from `current` or `until` has passed
+    ///
+    /// Waits indefinitely for a change when `until` is `None`. Returns the
+    /// state at the time of waking. The heartbeat, if any, is called while
+    /// waiting.
+    pub fn wait_for_change(&self, current: HandleState, until: Option<Instant>) -> HandleState {
+        loop {
+            let beat = self.beat_if_due();
+            let wake = [until, beat].into_iter().flatten().min();
+            let state = self.wait_until(current, wake);
+            if state != current || until.is_some_and(|until| Instant::now() >= until) {
+                return state;
+            }
+        }
+    }
+
+    fn wait_until(&self, current: HandleState, until: Option<Instant>) -> HandleState {
+        let mut state = self.lock();
+        while *state == current {
+            match until {
+                None => {
+                    state = self
+                        .shared
+                        .changed
+                        .wait(state)
+                        .unwrap_or_else(|e| e.into_inner());
+                }
+                Some(until) => {
+                    let now = Instant::now();
+                    if now >= until {
+                        break;
+                    }
+                    state = self
+                        .shared
+                        .changed
+                        .wait_timeout(state, until - now)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0;
+                }
+            }
+        }
+        *state
+    }
+
+    /// Call the heartbeat if it is due, returning when it is next due
+    fn beat_if_due(&self) -> Option<Instant> {
+        let mut heartbeat = self
+            .shared
+            .heartbeat
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let heartbeat = heartbeat.as_mut()?;
+        let now = Instant::now();
+        if now >= heartbeat.due {
+            (heartbeat.beat)();
+            heartbeat.due = now + heartbeat.every;
+        }
+        Some(heartbeat.due)
+    }
+
+    fn update(&self, change: impl FnOnce(&mut HandleState)) {
+        change(&mut self.lock());
+        self.shared.changed.notify_all();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HandleState> {
+        // The state is plain data, so a poisoned lock is still usable
+        self.shared.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_wait_times_out_without_changes() {
+        let handle = Handle::new();
+        let start = Instant::now();
+        let state = handle.wait_for_change(
+            HandleState::default(),
+            Some(start + Duration::from_millis(20)),
+        );
+        assert_eq!(state, HandleState::default());
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_wait_wakes_on_change_from_another_thread() {
+        let handle = Handle::new();
+        let remote = handle.clone();
+        let waker = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            remote.pause();
+        });
+
+        let start = Instant::now();
+        let state = handle.wait_for_change(HandleState::default(), None);
+        assert!(state.paused);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        waker.join().unwrap();
+    }
+
+    #[test]
+    fn test_heartbeat_beats_while_waiting() {
+        let handle = Handle::new();
+        let beats = Arc::new(Mutex::new(0));
+        let counter = beats.clone();
+        handle.set_heartbeat(Duration::from_millis(10), move || {
+            *counter.lock().unwrap() += 1
+        });
+        assert_eq!(*beats.lock().unwrap(), 0);
+
+        let start = Instant::now();
+        let state = handle.wait_for_change(
+            HandleState::default(),
+            Some(start + Duration::from_millis(55)),
+        );
+        assert_eq!(state, HandleState::default());
+        assert!(start.elapsed() >= Duration::from_millis(55));
+        assert!(*beats.lock().unwrap() >= 5);
+    }
+
+    #[test]
+    fn test_toggle_pause_and_reload() {
+        let handle = Handle::new();
+        assert!(handle.toggle_pause());
+        assert!(!handle.toggle_pause());
+
+        handle.set_interval(5).unwrap();
+        let config = MouseMoverConfig {
+            interval_secs: 30,
+            ..MouseMoverConfig::default()
+        };
+        handle.reload(config);
+        let state = handle.state();
+        assert_eq!(state.interval_secs, None);
+        assert_eq!(state.config_generation, 1);
+        assert_eq!(
+            handle.take_config().map(|config| config.interval_secs),
+            Some(30)
+        );
+        assert!(handle.take_config().is_none());
+    }
+
+    #[test]
+    fn test_set_interval_rejects_zero() {
+        let handle = Handle::new();
+        assert!(handle.set_interval(0).is_err());
+        handle.set_interval(5).unwrap();
+        assert_eq!(handle.state().interval_secs, Some(5));
+    }
+}
@@ -0,0 +1,154 @@
+//! Tracking how long the user has been idle
+//!
+//! KTMM should only nudge the mouse when nobody is using it. The best source
+//! of truth is the platform's own idle counter (the X11 MIT-SCREEN-SAVER
+//! extension), which counts the time since the last input event. Where that
+//! is not available, [`ActivityTracker`] falls back to sampling the pointer
+//! position and the keyboard: a moved pointer or a held key counts as
+//! activity.
+//!
+//! Our own nudges reset the platform idle counter too, so the tracker
+//! remembers when it last nudged and does not mistake that for the user.
+
+use std::time::{Duration, Instant};
+
+/// How far outside a nudge an input event is still attributed to it, to
+/// allow for the platform counter's rounding and delivery delays
+const NUDGE_SLACK: Duration = Duration::from_secs(1);
+
+/// Keeps track of when the user was last active
+#[derive(Debug, Clone)]
+pub struct ActivityTracker {
+    last_activity: Instant,
+    last_pointer: Option<(i32, i32)>,
+    /// When the last nudge started and finished
+    last_nudge: Option<(Instant, Instant)>,
+}
+
+impl ActivityTracker {
+    /// Start tracking, treating `now` as the last moment the user was active
+    pub fn new(now: Instant) -> Self {
+        Self {
+            last_activity: now,
+            last_pointer: None,
+            last_nudge: None,
+        }
+    }
+
+    /// Record a sample of the pointer position and keyboard state
+    ///
+    /// The first pointer sample only sets the baseline for later ones.
+    pub fn observe_input(&mut self, now: Instant, pointer: Option<(i32, i32)>, keys_held: bool) {
+        let pointer_moved = match (self.last_pointer, pointer) {
+            (Some(last), Some(current)) => last != current,
+            _ => false,
+        };
+        if keys_held || pointer_moved {
+            self.last_activity = now;
+        }
+        if pointer.is_some() {
+            self.last_pointer = pointer;
+        }
+    }
+
+    /// Record the platform idle counter, the time since the last input event
+    pub fn observe_idle_counter(&mut self, now: Instant, idle: Duration) {
+        let last_input = now.checked_sub(idle).unwrap_or(now);
+        let caused_by_nudge = self.last_nudge.is_some_and(|(started, finished)| {
+            let earliest = started.checked_sub(NUDGE_SLACK).unwrap_or(started);
+            last_input >= earliest && last_input <= finished + NUDGE_SLACK
+        });
+        if !caused_by_nudge {
+            self.last_activity = last_input;
+        }
+    }
+
+    /// Record user activity that KTMM noticed itself, e.g. a pointer moved
+    /// during a nudge
+    pub fn record_activity(&mut self, now: Instant, pointer: Option<(i32, i32)>) {
+        self.last_activity = now;
+        if pointer.is_some() {
+            self.last_pointer = pointer;
+        }
+    }
+
+    /// Record that KTMM nudged between `started` and `finished`, leaving the
+    /// pointer at `pointer`
+    pub fn record_nudge(
+        &mut self,
+        started: Instant,
+        finished: Instant,
+        pointer: Option<(i32, i32)>,
+    ) {
+        self.last_nudge = Some((started, finished));
+        if pointer.is_some() {
+            self.last_pointer = pointer;
+        }
+    }
+
+    /// How long the user has been idle at `now`
+    pub fn idle_for(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.last_activity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEC: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn test_pointer_and_keys_reset_idle_time() {
+        let start = Instant::now();
+        let mut tracker = ActivityTracker::new(start);
+
+        tracker.observe_input(start + 10 * SEC, Some((5, 5)), false);
+        assert_eq!(tracker.idle_for(start + 10 * SEC), 10 * SEC);
+
+        tracker.observe_input(start + 20 * SEC, Some((6, 5)), false);
+        assert_eq!(tracker.idle_for(start + 25 * SEC), 5 * SEC);
+
+        tracker.observe_input(start + 30 * SEC, Some((6, 5)), true);
+        assert_eq!(tracker.idle_for(start + 30 * SEC), Duration::ZERO);
+
+        tracker.observe_input(start + 40 * SEC, Some((6, 5)), false);
+        assert_eq!(tracker.idle_for(start + 40 * SEC), 10 * SEC);
+    }
+
+    #[test]
+    fn test_own_nudges_are_not_activity() {
+        let start = Instant::now();
+        let mut tracker = ActivityTracker::new(start);
+        tracker.observe_idle_counter(start, 100 * SEC);
+        assert_eq!(tracker.idle_for(start), 100 * SEC);
+
+        // The nudge resets the platform counter, which must not count
+        tracker.record_nudge(start, start, Some((1, 1)));
+        tracker.observe_idle_counter(start + 60 * SEC, 60 * SEC);
+        assert_eq!(tracker.idle_for(start + 60 * SEC), 160 * SEC);
+
+        // Real input after the nudge does
+        tracker.observe_idle_counter(start + 90 * SEC, 5 * SEC);
+        assert_eq!(tracker.idle_for(start + 90 * SEC), 5 * SEC);
+    }
+
+    #[test]
+    fn test_input_during_a_slow_nudge_is_not_activity() {
+        let start = Instant::now();
+        let mut tracker = ActivityTracker::new(start);
+        tracker.observe_idle_counter(start + 100 * SEC, 100 * SEC);
+
+        // A pattern traced over three seconds; the counter reports its last
+        // move, rounded down to slightly before the nudge was recorded
+        let (started, finished) = (start + 100 * SEC, start + 103 * SEC);
+        tracker.record_nudge(started, finished, Some((1, 1)));
+        let last_input = finished - Duration::from_millis(20);
+        tracker.observe_idle_counter(start + 110 * SEC, start + 110 * SEC - last_input);
+        assert_eq!(tracker.idle_for(start + 110 * SEC), 110 * SEC);
+
+        // Counter timestamps just before the nudge started still belong to it
+        tracker.observe_idle_counter(start + 110 * SEC, 10 * SEC + Duration::from_millis(300));
+        assert_eq!(tracker.idle_for(start + 110 * SEC), 110 * SEC);
+    }
+}
@@ -0,0 +1,142 @@
+//! Keeping the system awake with idle inhibitors instead of input (Linux)
+//!
+//! Freedesktop desktops let applications ask the screensaver
+//! (`org.freedesktop.ScreenSaver.Inhibit` on the session bus) and systemd-logind
+//! (`org.freedesktop.login1.Manager.Inhibit` on the system bus) not to blank
+//! the screen or suspend. The screensaver hands back a cookie that must be
+//! passed to `UnInhibit`, logind a file descriptor that keeps the lock for as
+//! long as it stays open. [`Inhibitor`] holds both for its lifetime.
+
+use zbus::blocking::Connection;
+use zbus::zvariant::OwnedFd;
+
+use crate::KtmmError;
+
+const SCREENSAVER_SERVICE: &str = "org.freedesktop.ScreenSaver";
+const SCREENSAVER_PATH: &str = "/org/freedesktop/ScreenSaver";
+const SCREENSAVER_INTERFACE: &str = "org.freedesktop.ScreenSaver";
+
+const LOGIND_SERVICE: &str = "org.freedesktop.login1";
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+const LOGIND_INTERFACE: &str = "org.freedesktop.login1.Manager";
+
+/// The application name reported to the screensaver and logind
+const APPLICATION: &str = "ktmm";
+
+/// An active idle inhibitor, released when dropped
+pub struct Inhibitor {
+    screensaver: Option<(Connection, u32)>,
+    logind: Option<OwnedFd>,
+}
+
+impl Inhibitor {
+    /// Inhibit idle on the user's session bus and sleep on the system bus
+    ///
+    /// Succeeds if at least one of the two inhibitors could be taken.
+    pub fn acquire(reason: &str) -> Result<Self, KtmmError> {
+        Self::acquire_on(Connection::session(), Connection::system(), reason)
+    }
+
+    /// Inhibit idle and sleep using the given session and system bus connections
+    pub fn acquire_on(
+        session: zbus::Result<Connection>,
+        system: zbus::Result<Connection>,
+        reason: &str,
+    ) -> Result<Self, KtmmError> {
+        let screensaver = session.and_then(|connection| {
+            let cookie = connection
+                .call_method(
+                    Some(SCREENSAVER_SERVICE),
+                    SCREENSAVER_PATH,
+                    Some(SCREENSAVER_INTERFACE),
+                    "Inhibit",
+                    &(APPLICATION, reason),
+                )?
+                .body()
+                .deserialize::<u32>()?;
+            Ok((connection, cookie))
+        });
+
+        let logind = system.and_then(|connection| {
+            connection
+                .call_method(
+                    Some(LOGIND_SERVICE),
+                    LOGIND_PATH,
+                    Some(LOGIND_INTERFACE),
+                    "Inhibit",
+                    &("idle:sleep", APPLICATION, reason, "block"),
+                )?
+                .body()
+                .deserialize::<OwnedFd>()
+        });
+
+        match (screensaver, logind) {
+            (Err(screensaver), Err(logind)) => Err(KtmmError::PlatformError(format!(
+                "could not inhibit idle: screensaver: {}; logind: {}",
+                screensaver, logind
+            ))),
+            (screensaver, logind) => Ok(Self {
+                screensaver: screensaver.ok(),
+                logind: logind.ok(),
+            }),
+        }
+    }
+
+    /// The cookie returned by the screensaver, if it accepted the inhibitor
+    pub fn screensaver_cookie(&self) -> Option<u32> {
+        self.screensaver.as_ref().map(|(_, cookie)| *cookie)
+    }
+
+    /// Whether logind accepted the inhibitor
+    pub fn holds_logind_lock(&self) -> bool {
+        self.logind.is_some()
+    }
+
+    /// Human-readable list of the inhibitors being held
+    pub fn describe(&self) -> String {
+        let mut held = Vec::new();
+        if let Some(cookie) = self.screensaver_cookie() {
+            held.push(format!("screensaver (cookie {})", cookie));
+        }
+        if self.holds_logind_lock() {
+            held.push("logind idle:sleep lock".to_string());
+        }
+        held.join(", ")
+    }
+
+    /// Release the inhibitors, reporting any failure
+    pub fn release(mut self) -> Result<(), KtmmError> {
+        self.release_inner()
+    }
+
+    fn release_inner(&mut self) -> Result<(), KtmmError> {
+        // Closing the file descriptor is all logind needs
+        self.logind = None;
+
+        if let Some((connection, cookie)) = self.screensaver.take() {
+            connection
+                .call_method(
+                    Some(SCREENSAVER_SERVICE),
+                    SCREENSAVER_PATH,
+                    Some(SCREENSAVER_INTERFACE),
+                    "UnInhibit",
+                    &(cookie,),
+                )
+                .map_err(|e| {
+                    KtmmError::PlatformError(format!(
+                        "could not release screensaver inhibitor: {}",
+                        e
+                    ))
+                })?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Inhibitor {
+    fn drop(&mut self) {
+        if let Err(e) = self.release_inner() {
+            eprintln!("{}", e);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Making sure only one KTMM instance moves the pointer (Unix)
+//!
+//! A running instance holds an exclusive `flock` on `ktmm.lock` in the
+//! runtime directory and writes its PID to `ktmm.pid` next to it. The lock is
+//! released by the kernel when the process exits, however it exits, so a
+//! stale PID file never keeps a new instance from starting.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::KtmmError;
+
+/// How long `--replace` waits for the old instance to exit
+pub const REPLACE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often to retry the lock while the old instance shuts down
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// `$XDG_RUNTIME_DIR/ktmm.<extension>`, or a per-user path in the temporary
+/// directory when there is no runtime directory
+pub fn runtime_path(env: &HashMap<String, String>, extension: &str) -> PathBuf {
+    match env.get("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => Path::new(dir).join(format!("ktmm.{}", extension)),
+        None => {
+            let uid = unsafe { libc::getuid() };
+            std::env::temp_dir().join(format!("ktmm-{}.{}", uid, extension))
+        }
+    }
+}
+
+/// The result of trying to become the running instance
+#[derive(Debug)]
+pub enum LockAttempt {
+    /// This process is now the running instance
+    Acquired(InstanceLock),
+    /// Another instance holds the lock; its PID if it has written one
+    Held { pid: Option<u32> },
+}
+
+/// The lock of the running instance, released when dropped
+#[derive(Debug)]
+pub struct InstanceLock {
+    file: File,
+    pid_path: PathBuf,
+}
+
+impl InstanceLock {
+    /// Try to take the lock at `lock_path` without waiting, writing this
+    /// process's PID to `pid_path` on success
+    pub fn try_acquire(lock_path: &Path, pid_path: &Path) -> Result<LockAttempt, KtmmError> {
+        let error = |e: io::Error| {
+            KtmmError::PlatformError(format!("cannot lock {}: {}", lock_path.display(), e))
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .mode(0o600)
+            .open(lock_path)
+            .map_err(error)?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::WouldBlock {
+                return Ok(LockAttempt::Held {
+                    pid: read_pid(pid_path),
+                });
+            }
+            return Err(error(e));
+        }
+
+        fs::write(pid_path, format!("{}\n", std::process::id())).map_err(|e| {
+            KtmmError::PlatformError(format!("cannot write {}: {}", pid_path.display(), e))
+        })?;
+        Ok(LockAttempt::Acquired(InstanceLock {
+            file,
+            pid_path: pid_path.to_path_buf(),
+        }))
+    }
+
+    /// Take the lock, asking the instance holding it to exit first
+    ///
+    /// The old instance is sent `SIGTERM` and given `timeout` to shut down
+    /// cleanly.
+    pub fn replace(
+        lock_path: &Path,
+        pid_path: &Path,
+        timeout: Duration,
+    ) -> Result<InstanceLock, KtmmError> {
+        let give_up = Instant::now() + timeout;
+        let mut signalled = None;
+        loop {
+            let pid = match Self::try_acquire(lock_path, pid_path)? {
+                LockAttempt::Acquired(lock) => return Ok(lock),
+                LockAttempt::Held { pid } => pid,
+            };
+            if let Some(pid) = pid.filter(|&pid| signalled != Some(pid)) {
+                if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+                    let e = io::Error::last_os_error();
+                    // It may have exited on its own in the meantime
+                    if e.raw_os_error() != Some(libc::ESRCH) {
+                        return Err(KtmmError::PlatformError(format!(
+                            "cannot stop the running instance (PID {}): {}",
+                            pid, e
+                        )));
+                    }
+                }
+                signalled = Some(pid);
+            }
+            if Instant::now() >= give_up {
+                return Err(KtmmError::PlatformError(match pid {
+                    Some(pid) => format!(
+                        "the running instance (PID {}) did not exit within {}s",
+                        pid,
+                        timeout.as_secs()
+                    ),
+                    None => format!(
+                        "the running instance did not release {} within {}s",
+                        lock_path.display(),
+                        timeout.as_secs()
+                    ),
+                }));
+            }
+            thread::sleep(RETRY_INTERVAL);
+        }
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // Remove the PID file while still holding the lock, so it never names
+        // a newer instance
+        let _ = fs::remove_file(&self.pid_path);
+        unsafe {
+            libc::flock(self.file.as_raw_fd(), libc::LOCK_UN);
+        }
+    }
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runtime_path() {
+        let env = HashMap::from([("XDG_RUNTIME_DIR".to_string(), "/run/user/1000".to_string())]);
+        assert_eq!(
+            runtime_path(&env, "lock"),
+            Path::new("/run/user/1000/ktmm.lock")
+        );
+        assert!(runtime_path(&HashMap::new(), "pid").starts_with(std::env::temp_dir()));
+    }
+}
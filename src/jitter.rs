@@ -0,0 +1,290 @@
+//! Randomised intervals and offsets
+//!
+//! Some remote-desktop gateways ignore input that repeats exactly, and a
+//! fixed tick is easy to spot. The interval between movements can instead be
+//! drawn uniformly from a range or from an exponential distribution, and the
+//! offset can point in a random direction with a random length. All draws
+//! come from one RNG, which can be seeded to make a run reproducible.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use std::fmt;
+use std::time::Duration;
+
+/// How many times the mean an exponential interval may reach unless capped
+const EXPONENTIAL_CAP_FACTOR: u64 = 3;
+
+/// How the time between mouse movements is chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub enum IntervalJitter {
+    /// Always the configured interval
+    #[default]
+    Fixed,
+    /// Uniformly between `min_secs` and `max_secs`, ignoring the interval
+    Uniform { min_secs: u64, max_secs: u64 },
+    /// Exponentially distributed around the configured interval as the mean,
+    /// but at least a second and at most `max_secs` (three times the mean
+    /// when `None`)
+    Exponential { max_secs: Option<u64> },
+}
+
+impl IntervalJitter {
+    /// Draw the time until the next movement for a configured interval of
+    /// `interval_secs`
+    pub fn sample(&self, interval_secs: u64, rng: &mut impl Rng) -> Duration {
+        match *self {
+            IntervalJitter::Fixed => Duration::from_secs(interval_secs),
+            IntervalJitter::Uniform { min_secs, max_secs } => {
+                Duration::from_millis(rng.random_range(min_secs * 1000..=max_secs * 1000))
+            }
+            IntervalJitter::Exponential { max_secs } => {
+                let max = max_secs.unwrap_or(interval_secs * EXPONENTIAL_CAP_FACTOR) as f64;
+                // Inverse transform sampling; 1 - u avoids ln(0)
+                let u: f64 = rng.random();
+                let secs = -(interval_secs as f64) * (1.0 - u).ln();
+                Duration::from_secs_f64(secs.clamp(1.0, max.max(1.0)))
+            }
+        }
+    }
+
+    /// The shortest interval that can be drawn for `interval_secs`
+    pub fn min_secs(&self, interval_secs: u64) -> u64 {
+        match *self {
+            IntervalJitter::Fixed => interval_secs,
+            IntervalJitter::Uniform { min_secs, .. } => min_secs,
+            IntervalJitter::Exponential { .. } => 1,
+        }
+    }
+}
+
+impl std::str::FromStr for IntervalJitter {
+    type Err = String;
+
+    /// Parse `fixed`, `MIN-MAX` seconds, `exponential` or `exponential:MAX`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        let invalid = || {
+            format!(
+                "invalid interval jitter `{}`: expected `fixed`, a range of seconds such as `30-90`, `exponential` or `exponential:MAX`",
+                s
+            )
+        };
+        if s == "fixed" {
+            return Ok(IntervalJitter::Fixed);
+        }
+        if let Some(rest) = s
+            .strip_prefix("exponential")
+            .or_else(|| s.strip_prefix("exp"))
+        {
+            let max_secs = match rest.strip_prefix(':') {
+                Some(max) => Some(max.parse().map_err(|_| invalid())?),
+                None if rest.is_empty() => None,
+                None => return Err(invalid()),
+            };
+            if max_secs == Some(0) {
+                return Err("exponential interval cap must be at least 1 second".to_string());
+            }
+            return Ok(IntervalJitter::Exponential { max_secs });
+        }
+        let (min_secs, max_secs) = parse_range(&s).ok_or_else(invalid)?;
+        if min_secs == 0 {
+            return Err("interval range must start at 1 second or more".to_string());
+        }
+        Ok(IntervalJitter::Uniform { min_secs, max_secs })
+    }
+}
+
+impl TryFrom<String> for IntervalJitter {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for IntervalJitter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntervalJitter::Fixed => write!(f, "fixed"),
+            IntervalJitter::Uniform { min_secs, max_secs } => {
+                write!(f, "{}-{}", min_secs, max_secs)
+            }
+            IntervalJitter::Exponential { max_secs: None } => write!(f, "exponential"),
+            IntervalJitter::Exponential {
+                max_secs: Some(max),
+            } => write!(f, "exponential:{}", max),
+        }
+    }
+}
+
+/// Bounds on the length of a randomly directed offset, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct OffsetRange {
+    pub min: u32,
+    pub max: u32,
+}
+
+impl OffsetRange {
+    /// Draw an offset of a length between `min` and `max` pixels in a
+    /// uniformly random direction
+    pub fn sample(&self, rng: &mut impl Rng) -> (i32, i32) {
+        let length = rng.random_range(self.min as f64..=self.max as f64);
+        let angle = rng.random_range(0.0..std::f64::consts::TAU);
+        let delta = (
+            (length * angle.cos()).round() as i32,
+            (length * angle.sin()).round() as i32,
+        );
+        // Rounding can only cancel both axes for lengths below one pixel
+        if delta == (0, 0) {
+            (1, 0)
+        } else {
+            delta
+        }
+    }
+}
+
+impl std::str::FromStr for OffsetRange {
+    type Err = String;
+
+    /// Parse `MIN-MAX` pixels, or a single length
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (min, max) = parse_range(s)
+            .or_else(|| s.parse().ok().map(|length| (length, length)))
+            .ok_or_else(|| {
+                format!(
+                    "invalid offset range `{}`: expected pixels such as `1-5`",
+                    s
+                )
+            })?;
+        if min == 0 {
+            return Err("offset range must start at 1 pixel or more".to_string());
+        }
+        let convert =
+            |value: u64| u32::try_from(value).map_err(|_| "offset is too large".to_string());
+        Ok(Self {
+            min: convert(min)?,
+            max: convert(max)?,
+        })
+    }
+}
+
+impl TryFrom<String> for OffsetRange {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for OffsetRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.min, self.max)
+    }
+}
+
+/// Parse an ascending `MIN-MAX` range
+fn parse_range(s: &str) -> Option<(u64, u64)> {
+    let (min, max) = s.split_once('-')?;
+    let (min, max) = (min.trim().parse().ok()?, max.trim().parse().ok()?);
+    (min <= max).then_some((min, max))
+}
+
+/// An RNG seeded with `seed`, or from the operating system when `None`
+pub fn rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_os_rng(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_interval_jitter() {
+        assert_eq!("fixed".parse(), Ok(IntervalJitter::Fixed));
+        assert_eq!(
+            "30-90".parse(),
+            Ok(IntervalJitter::Uniform {
+                min_secs: 30,
+                max_secs: 90
+            })
+        );
+        assert_eq!(
+            "exponential".parse(),
+            Ok(IntervalJitter::Exponential { max_secs: None })
+        );
+        assert_eq!(
+            "exp:240".parse::<IntervalJitter>().unwrap().to_string(),
+            "exponential:240"
+        );
+        for bad in ["90-30", "0-10", "often", "exponential:0", "exponentially"] {
+            assert!(bad.parse::<IntervalJitter>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_intervals_stay_within_bounds() {
+        let mut rng = rng(Some(7));
+        let uniform = IntervalJitter::Uniform {
+            min_secs: 30,
+            max_secs: 90,
+        };
+        let exponential = IntervalJitter::Exponential { max_secs: None };
+        let mut total = Duration::ZERO;
+        for _ in 0..1000 {
+            let interval = uniform.sample(60, &mut rng);
+            assert!(interval >= Duration::from_secs(30) && interval <= Duration::from_secs(90));
+
+            let interval = exponential.sample(60, &mut rng);
+            assert!(interval >= Duration::from_secs(1) && interval <= Duration::from_secs(180));
+            total += interval;
+        }
+        // The cap pulls the mean a little below the configured interval
+        let mean = total.as_secs_f64() / 1000.0;
+        assert!((45.0..65.0).contains(&mean), "{}", mean);
+        assert_eq!(
+            IntervalJitter::Fixed.sample(60, &mut rng),
+            Duration::from_secs(60)
+        );
+    }
+
+    #[test]
+    fn test_offsets_vary_within_bounds() {
+        let mut rng = rng(Some(7));
+        let range: OffsetRange = "2-5".parse().unwrap();
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..200 {
+            let (dx, dy) = range.sample(&mut rng);
+            let length = ((dx * dx + dy * dy) as f64).sqrt();
+            assert!((1.0..=5.8).contains(&length), "({}, {})", dx, dy);
+            seen.insert((dx, dy));
+        }
+        assert!(seen.len() > 10);
+        assert!(seen.iter().any(|&(dx, _)| dx < 0));
+        assert!(seen.iter().any(|&(_, dy)| dy < 0));
+        assert!("0-3".parse::<OffsetRange>().is_err());
+        assert_eq!("3".parse(), Ok(OffsetRange { min: 3, max: 3 }));
+    }
+
+    #[test]
+    fn test_seeded_rng_is_reproducible() {
+        let jitter = IntervalJitter::Uniform {
+            min_secs: 1,
+            max_secs: 100,
+        };
+        let draw = |seed| {
+            let mut rng = rng(Some(seed));
+            (0..5)
+                .map(|_| jitter.sample(60, &mut rng))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(draw(42), draw(42));
+        assert_ne!(draw(42), draw(43));
+    }
+}
@@ -0,0 +1,167 @@
+//! Keys that are safe to tap as a keyboard nudge
+//!
+//! Some applications, such as VDI clients and some chat clients, only reset
+//! their idle timers on keyboard input. Tapping a key is only harmless if it
+//! cannot type into or trigger anything in the focused window, so only keys
+//! from a short list are accepted: the function keys F13 to F24, which hardly
+//! any keyboard or application uses, the bare modifiers Shift and Control,
+//! and Scroll Lock on Linux.
+
+use serde::Deserialize;
+use std::fmt;
+
+use crate::backend::Key;
+
+/// A key that can be tapped without affecting the focused window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub enum NudgeKey {
+    F13,
+    F14,
+    #[default]
+    F15,
+    F16,
+    F17,
+    F18,
+    F19,
+    F20,
+    F21,
+    F22,
+    F23,
+    F24,
+    Shift,
+    Control,
+    ScrollLock,
+}
+
+const NAMES: [(&str, NudgeKey); 15] = [
+    ("f13", NudgeKey::F13),
+    ("f14", NudgeKey::F14),
+    ("f15", NudgeKey::F15),
+    ("f16", NudgeKey::F16),
+    ("f17", NudgeKey::F17),
+    ("f18", NudgeKey::F18),
+    ("f19", NudgeKey::F19),
+    ("f20", NudgeKey::F20),
+    ("f21", NudgeKey::F21),
+    ("f22", NudgeKey::F22),
+    ("f23", NudgeKey::F23),
+    ("f24", NudgeKey::F24),
+    ("shift", NudgeKey::Shift),
+    ("control", NudgeKey::Control),
+    ("scrolllock", NudgeKey::ScrollLock),
+];
+
+impl NudgeKey {
+    /// The key to press, or `None` if it cannot be generated on this platform
+    pub fn key(self) -> Option<Key> {
+        let key = match self {
+            NudgeKey::F13 => Key::F13,
+            NudgeKey::F14 => Key::F14,
+            NudgeKey::F15 => Key::F15,
+            NudgeKey::F16 => Key::F16,
+            NudgeKey::F17 => Key::F17,
+            NudgeKey::F18 => Key::F18,
+            NudgeKey::F19 => Key::F19,
+            NudgeKey::F20 => Key::F20,
+            #[cfg(any(target_os = "windows", target_os = "linux"))]
+            NudgeKey::F21 => Key::F21,
+            #[cfg(any(target_os = "windows", target_os = "linux"))]
+            NudgeKey::F22 => Key::F22,
+            #[cfg(any(target_os = "windows", target_os = "linux"))]
+            NudgeKey::F23 => Key::F23,
+            #[cfg(any(target_os = "windows", target_os = "linux"))]
+            NudgeKey::F24 => Key::F24,
+            NudgeKey::Shift => Key::Shift,
+            NudgeKey::Control => Key::Control,
+            #[cfg(target_os = "linux")]
+            NudgeKey::ScrollLock => Key::ScrollLock,
+            #[allow(unreachable_patterns)]
+            _ => return None,
+        };
+        Some(key)
+    }
+}
+
+impl std::str::FromStr for NudgeKey {
+    type Err = String;
+
+    /// Parse a key name such as `F15`, `shift`, `ctrl` or `scroll-lock`,
+    /// refusing keys that could type or trigger something
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name: String = s
+            .trim()
+            .to_ascii_lowercase()
+            .chars()
+            .filter(|c| !matches!(c, '-' | '_' | ' '))
+            .collect();
+        let name = match name.as_str() {
+            "ctrl" => "control",
+            "scroll" | "scrlk" => "scrolllock",
+            name => name,
+        };
+        let key = NAMES
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|&(_, key)| key)
+            .ok_or_else(|| {
+                format!(
+                    "refusing to tap `{}`: it could type into or trigger something in the focused window; \
+                     use one of F13-F24, shift, control or scroll-lock",
+                    s.trim()
+                )
+            })?;
+        match key.key() {
+            Some(_) => Ok(key),
+            None => Err(format!("`{}` cannot be pressed on this platform", key)),
+        }
+    }
+}
+
+impl TryFrom<String> for NudgeKey {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for NudgeKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NudgeKey::Shift => write!(f, "shift"),
+            NudgeKey::Control => write!(f, "control"),
+            NudgeKey::ScrollLock => write!(f, "scroll-lock"),
+            key => write!(f, "{:?}", key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_harmless_keys_are_accepted() {
+        assert_eq!("F15".parse(), Ok(NudgeKey::F15));
+        assert_eq!("f13".parse(), Ok(NudgeKey::F13));
+        assert_eq!("Shift".parse(), Ok(NudgeKey::Shift));
+        assert_eq!("ctrl".parse(), Ok(NudgeKey::Control));
+        for refused in [
+            "a", "space", "enter", "F5", "F12", "alt", "meta", "capslock", "tab", "",
+        ] {
+            let err = refused.parse::<NudgeKey>().unwrap_err();
+            assert!(err.starts_with("refusing"), "{}: {}", refused, err);
+        }
+    }
+
+    #[test]
+    fn test_names_round_trip() {
+        for &(_, key) in &NAMES {
+            if key.key().is_some() {
+                assert_eq!(key.to_string().parse(), Ok(key));
+            }
+        }
+        assert_eq!(NudgeKey::ScrollLock.to_string(), "scroll-lock");
+    }
+}
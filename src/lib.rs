@@ -1,9 +1,74 @@
-use device_query::{DeviceQuery, DeviceState};
-use enigo::{Enigo, MouseControllable};
-use std::{thread, time::Duration};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
+// When to keep the system awake
+pub mod activation;
+// Input backends
+pub mod backend;
+// iCalendar file events
+pub mod calendar;
+// Command-line interface
+pub mod cli;
+// Time source of the run loop
+pub mod clock;
+// Config file and environment loading
+pub mod config;
+// Cron expressions
+pub mod cron;
+// Control socket of a background daemon
+#[cfg(unix)]
+pub mod daemon;
+// Screen and monitor geometry
+pub mod geometry;
+// Remote control of a running mover
+pub mod handle;
+// User activity tracking
+pub mod idle;
+// Single-instance lock
+#[cfg(unix)]
+pub mod instance;
+// Randomised intervals and offsets
+pub mod jitter;
+// Keys that are safe to tap
+pub mod keys;
+// D-Bus idle inhibitors
+#[cfg(target_os = "linux")]
+pub mod inhibit;
+// Movement patterns
+pub mod pattern;
 // Platform-specific functionality
 pub mod platform;
+// Weekly keep-awake windows
+pub mod schedule;
+// systemd user service integration
+#[cfg(target_os = "linux")]
+pub mod systemd;
+// Durations and times of day
+pub mod timespec;
+// Virtual uinput pointer
+#[cfg(target_os = "linux")]
+pub mod uinput;
+// Waiting for other processes to exit
+#[cfg(unix)]
+pub mod watch;
+
+pub use backend::{EnigoBackend, InputBackend, RecordingBackend, ScrollAxis};
+pub use handle::{Handle, HandleState};
+
+use activation::Activation;
+use clock::{Clock, SystemClock};
+use cron::CronExpr;
+use idle::ActivityTracker;
+use jitter::{IntervalJitter, OffsetRange};
+use keys::NudgeKey;
+use pattern::Pattern;
+use rand::rngs::StdRng;
+use schedule::Schedule;
+use timespec::TimeOfDay;
 
 /// Error types for KTMM operations
 #[derive(Debug)]
@@ -15,6 +80,12 @@ pub enum KtmmError {
     /// Error when accessibility permissions are not granted (macOS)
     #[cfg(target_os = "macos")]
     AccessibilityPermissionError,
+    /// Error when the configuration is invalid
+    ConfigError(String),
+    /// Error when an input backend does not support an operation
+    Unsupported(String),
+    /// Error when talking to a daemon over its control socket fails
+    ControlError(String),
     /// Generic error
     Other(String),
 }
@@ -28,6 +99,9 @@ impl std::fmt::Display for KtmmError {
             KtmmError::AccessibilityPermissionError => {
                 write!(f, "macOS accessibility permission not granted")
             }
+            KtmmError::ConfigError(msg) => write!(f, "Configuration error: {}", msg),
+            KtmmError::Unsupported(msg) => write!(f, "Unsupported operation: {}", msg),
+            KtmmError::ControlError(msg) => write!(f, "Control error: {}", msg),
             KtmmError::Other(msg) => write!(f, "Error: {}", msg),
         }
     }
@@ -35,131 +109,940 @@ impl std::fmt::Display for KtmmError {
 
 impl std::error::Error for KtmmError {}
 
+/// How much progress output to print
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verbosity {
+    /// Only print errors
+    Quiet,
+    /// Print start and shutdown messages
+    #[default]
+    Normal,
+    /// Additionally report every mouse movement
+    Verbose,
+}
+
+impl std::str::FromStr for Verbosity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "quiet" => Ok(Verbosity::Quiet),
+            "normal" => Ok(Verbosity::Normal),
+            "verbose" => Ok(Verbosity::Verbose),
+            _ => Err("expected one of `quiet`, `normal` or `verbose`".to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for Verbosity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verbosity::Quiet => write!(f, "quiet"),
+            Verbosity::Normal => write!(f, "normal"),
+            Verbosity::Verbose => write!(f, "verbose"),
+        }
+    }
+}
+
+/// How KTMM keeps the system awake
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeepAwakeMode {
+    /// Periodically nudge the mouse pointer
+    #[default]
+    Mouse,
+    /// Hold idle inhibitors over D-Bus without touching the mouse (Linux)
+    Inhibit,
+}
+
+impl std::str::FromStr for KeepAwakeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mouse" => Ok(KeepAwakeMode::Mouse),
+            "inhibit" => Ok(KeepAwakeMode::Inhibit),
+            _ => Err("expected `mouse` or `inhibit`".to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for KeepAwakeMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeepAwakeMode::Mouse => write!(f, "mouse"),
+            KeepAwakeMode::Inhibit => write!(f, "inhibit"),
+        }
+    }
+}
+
+/// Which input backend moves the mouse
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// `uinput` on Wayland, `native` everywhere else
+    #[default]
+    Auto,
+    /// The platform's native input APIs (X11, macOS, Windows)
+    Native,
+    /// A virtual `/dev/uinput` pointer (Linux, works on Wayland)
+    Uinput,
+}
+
+impl BackendKind {
+    /// Resolve `Auto` to the backend that suits the current session
+    pub fn resolve(self) -> BackendKind {
+        match self {
+            BackendKind::Auto
+                if cfg!(target_os = "linux") && std::env::var_os("WAYLAND_DISPLAY").is_some() =>
+            {
+                BackendKind::Uinput
+            }
+            BackendKind::Auto => BackendKind::Native,
+            kind => kind,
+        }
+    }
+}
+
+impl std::str::FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(BackendKind::Auto),
+            "native" => Ok(BackendKind::Native),
+            "uinput" => Ok(BackendKind::Uinput),
+            _ => Err("expected one of `auto`, `native` or `uinput`".to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackendKind::Auto => write!(f, "auto"),
+            BackendKind::Native => write!(f, "native"),
+            BackendKind::Uinput => write!(f, "uinput"),
+        }
+    }
+}
+
+/// What kind of input each nudge generates in the mouse mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NudgeKind {
+    /// Move the pointer and back
+    #[default]
+    Pointer,
+    /// Tap a harmless key, for applications that only watch the keyboard
+    Key,
+    /// Scroll one notch and back, leaving the pointer where it is
+    Scroll,
+    /// Reset the idle timer without visible movement where the platform
+    /// allows it, and nudge the pointer elsewhere
+    Synthetic,
+}
+
+impl std::str::FromStr for NudgeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "pointer" => Ok(NudgeKind::Pointer),
+            "key" => Ok(NudgeKind::Key),
+            "scroll" => Ok(NudgeKind::Scroll),
+            "synthetic" => Ok(NudgeKind::Synthetic),
+            _ => Err("expected `pointer`, `key`, `scroll` or `synthetic`".to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for NudgeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NudgeKind::Pointer => write!(f, "pointer"),
+            NudgeKind::Key => write!(f, "key"),
+            NudgeKind::Scroll => write!(f, "scroll"),
+            NudgeKind::Synthetic => write!(f, "synthetic"),
+        }
+    }
+}
+
 /// Configuration for mouse movement
 #[derive(Debug, Clone)]
 pub struct MouseMoverConfig {
     /// Time to wait between mouse movements in seconds
     pub interval_secs: u64,
+    /// How the time between movements varies around `interval_secs`
+    pub interval_jitter: IntervalJitter,
     /// Distance to move the mouse in pixels (x, y)
     pub movement_pixels: (i32, i32),
-    /// Time to wait before moving back in milliseconds
+    /// Move a random distance within these bounds in a random direction
+    /// instead of by `movement_pixels`
+    pub random_offset: Option<OffsetRange>,
+    /// Seed for the random intervals and offsets, so a run can be repeated;
+    /// seeded by the operating system when `None`
+    pub seed: Option<u64>,
+    /// The path each nudge traces, sized by the offset
+    pub pattern: Pattern,
+    /// What kind of input each nudge generates
+    pub nudge: NudgeKind,
+    /// The key tapped by keyboard nudges
+    pub nudge_key: NudgeKey,
+    /// Which way scroll nudges turn the wheel
+    pub scroll_axis: ScrollAxis,
+    /// Time to wait before moving back in milliseconds; the time a whole
+    /// pattern takes for patterns other than a line
     pub return_delay_ms: u64,
+    /// Perform a single mouse movement immediately and stop
+    pub once: bool,
+    /// Stop after this many seconds; run until stopped when `None`
+    pub duration_secs: Option<u64>,
+    /// Stop when the local clock next shows this time
+    pub until: Option<TimeOfDay>,
+    /// Only move the mouse once the user has been idle this many seconds;
+    /// move on every interval when `None`
+    pub idle_threshold_secs: Option<u64>,
+    /// Only keep the system awake inside these windows; always when empty
+    pub schedule: Schedule,
+    /// Also keep the system awake during the minutes these expressions match
+    pub cron: Vec<CronExpr>,
+    /// Also keep the system awake during the events in this `.ics` file
+    pub calendar: Option<PathBuf>,
+    /// Only use the calendar events tagged with this category or word
+    pub calendar_tag: Option<String>,
+    /// How much progress output to print
+    pub verbosity: Verbosity,
+    /// How to keep the system awake
+    pub mode: KeepAwakeMode,
+    /// Which input backend moves the mouse
+    pub backend: BackendKind,
 }
 
 impl Default for MouseMoverConfig {
     fn default() -> Self {
         Self {
             interval_secs: 60,
+            interval_jitter: IntervalJitter::Fixed,
             movement_pixels: (1, 1),
+            random_offset: None,
+            seed: None,
+            pattern: Pattern::Line,
+            nudge: NudgeKind::Pointer,
+            nudge_key: NudgeKey::F15,
+            scroll_axis: ScrollAxis::Vertical,
             return_delay_ms: 6,
+            once: false,
+            duration_secs: None,
+            until: None,
+            idle_threshold_secs: None,
+            schedule: Schedule::default(),
+            cron: Vec::new(),
+            calendar: None,
+            calendar_tag: None,
+            verbosity: Verbosity::Normal,
+            mode: KeepAwakeMode::Mouse,
+            backend: BackendKind::Auto,
+        }
+    }
+}
+
+impl MouseMoverConfig {
+    /// When a run starting now should end, the earlier of `duration_secs`
+    /// and `until`; `None` if it should run until stopped
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline_from(&SystemClock)
+    }
+
+    /// When a run timed by `clock` and starting now should end
+    pub fn deadline_from(&self, clock: &dyn Clock) -> Option<Instant> {
+        let now = clock.now();
+        let by_duration = self
+            .duration_secs
+            .map(|secs| now + Duration::from_secs(secs));
+        let by_time = self
+            .until
+            .map(|time| now + time.duration_from(&clock.local_now()));
+        by_duration.into_iter().chain(by_time).min()
+    }
+
+    /// Check that the configuration values make sense together
+    pub fn validate(&self) -> Result<(), KtmmError> {
+        if self.interval_secs == 0 {
+            return Err(KtmmError::ConfigError(
+                "interval must be at least 1 second".to_string(),
+            ));
+        }
+        if self.movement_pixels == (0, 0) {
+            return Err(KtmmError::ConfigError(
+                "movement must be non-zero in at least one direction".to_string(),
+            ));
+        }
+        let min_interval = self.interval_jitter.min_secs(self.interval_secs);
+        if self.return_delay_ms >= min_interval.saturating_mul(1000) {
+            return Err(KtmmError::ConfigError(format!(
+                "return delay ({} ms) must be shorter than the interval ({} s)",
+                self.return_delay_ms, min_interval
+            )));
+        }
+        if self.nudge == NudgeKind::Key && self.backend == BackendKind::Uinput {
+            return Err(KtmmError::ConfigError(
+                "keyboard nudges need the native backend, not `uinput`".to_string(),
+            ));
+        }
+        if self.once && self.mode == KeepAwakeMode::Inhibit {
+            return Err(KtmmError::ConfigError(
+                "`once` only applies to the mouse mode".to_string(),
+            ));
+        }
+        if self.duration_secs == Some(0) {
+            return Err(KtmmError::ConfigError(
+                "duration must be at least 1 second".to_string(),
+            ));
+        }
+        if self.once && self.until.is_some() {
+            return Err(KtmmError::ConfigError(
+                "`once` cannot be combined with `until`".to_string(),
+            ));
         }
+        if self.idle_threshold_secs == Some(0) {
+            return Err(KtmmError::ConfigError(
+                "idle threshold must be at least 1 second".to_string(),
+            ));
+        }
+        Ok(())
     }
 }
 
+/// What happened on a scheduled tick of the mover
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tick {
+    /// The mouse was moved
+    Moved,
+    /// The user was active too recently; try again after `retry_in`
+    Deferred { idle: Duration, retry_in: Duration },
+}
+
+/// Something noteworthy that happened while moving the mouse
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoverEvent {
+    /// The mouse was moved by `delta` and back
+    Nudged { delta: (i32, i32) },
+    /// A keyboard nudge tapped `key`
+    KeyTapped { key: NudgeKey },
+    /// A scroll nudge turned the wheel one notch along `axis` and back
+    Scrolled { axis: ScrollAxis },
+    /// The idle timer was reset through `mechanism` without moving anything
+    IdleTimerReset { mechanism: &'static str },
+    /// The idle timer cannot be reset directly here, so pointer nudges are
+    /// used instead; reported once
+    SyntheticUnavailable,
+    /// The user was active `idle` ago, so the nudge was put off by `retry_in`
+    Deferred { idle: Duration, retry_in: Duration },
+    /// The pointer was left where the user moved it during the return delay
+    /// instead of being moved back
+    RestoreSkipped {
+        /// Where the nudge had put the pointer
+        expected: (i32, i32),
+        /// Where the pointer was found before moving it back
+        actual: (i32, i32),
+    },
+    /// The schedule turned on or off; `next_change` is when it flips back
+    ScheduleChanged {
+        active: bool,
+        next_change: Option<chrono::DateTime<chrono::Local>>,
+    },
+    /// A schedule source could not be read; it keeps its previous answer
+    ActivationError { message: String },
+    /// A reloaded configuration was rejected because its schedule could not
+    /// be read; the previous configuration stays in use
+    ReloadRejected { message: String },
+}
+
+impl MoverEvent {
+    /// Whether the event reports a nudge that reset the idle timer
+    pub fn is_nudge(&self) -> bool {
+        matches!(
+            self,
+            MoverEvent::Nudged { .. }
+                | MoverEvent::KeyTapped { .. }
+                | MoverEvent::Scrolled { .. }
+                | MoverEvent::IdleTimerReset { .. }
+        )
+    }
+
+    /// Whether the event reports an error the run carried on after
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            MoverEvent::ActivationError { .. } | MoverEvent::ReloadRejected { .. }
+        )
+    }
+}
+
+impl std::fmt::Display for MoverEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoverEvent::Nudged { delta } => {
+                write!(f, "Moved mouse by ({}, {}) and back.", delta.0, delta.1)
+            }
+            MoverEvent::KeyTapped { key } => write!(f, "Tapped {}.", key),
+            MoverEvent::Scrolled { axis } => {
+                write!(f, "Scrolled one notch {}ly and back.", axis)
+            }
+            MoverEvent::IdleTimerReset { mechanism } => {
+                write!(f, "Reset the idle timer with {}.", mechanism)
+            }
+            MoverEvent::SyntheticUnavailable => write!(
+                f,
+                "The idle timer cannot be reset directly here; moving the pointer instead."
+            ),
+            MoverEvent::Deferred { idle, retry_in } => write!(
+                f,
+                "User active {}s ago, checking again in {}s.",
+                idle.as_secs(),
+                retry_in.as_secs()
+            ),
+            MoverEvent::RestoreSkipped { actual, .. } => write!(
+                f,
+                "Pointer was moved to ({}, {}) during the nudge; leaving it there.",
+                actual.0, actual.1
+            ),
+            MoverEvent::ScheduleChanged {
+                active: true,
+                next_change,
+            } => match next_change {
+                Some(end) => write!(
+                    f,
+                    "Inside scheduled hours until {}.",
+                    end.format("%a %H:%M")
+                ),
+                None => write!(f, "Inside scheduled hours."),
+            },
+            MoverEvent::ScheduleChanged {
+                active: false,
+                next_change,
+            } => match next_change {
+                Some(start) => write!(
+                    f,
+                    "Outside scheduled hours, sleeping until {}.",
+                    start.format("%a %H:%M")
+                ),
+                None => write!(f, "Outside scheduled hours."),
+            },
+            MoverEvent::ActivationError { message } => write!(f, "{}", message),
+            MoverEvent::ReloadRejected { message } => {
+                write!(f, "Keeping the previous configuration: {}", message)
+            }
+        }
+    }
+}
+
+/// Receives the [`MoverEvent`]s of a [`MouseMover`]
+pub type EventHandler = Box<dyn FnMut(&MoverEvent) + Send>;
+
 /// The main mouse mover struct
-pub struct MouseMover {
+///
+/// The mover is generic over the [`InputBackend`] that performs the actual
+/// input; it uses the `enigo`-based [`EnigoBackend`] unless another backend
+/// is supplied with [`MouseMover::with_backend`].
+pub struct MouseMover<B: InputBackend = EnigoBackend> {
     pub config: MouseMoverConfig,
-    enigo: Enigo,
-    device_state: DeviceState,
-    running: bool,
+    backend: B,
+    handle: Handle,
+    activity: ActivityTracker,
+    event_handler: Option<EventHandler>,
+    rng: StdRng,
+    /// Whether synthetic nudges have had to fall back to pointer nudges
+    synthetic_fallback: bool,
+    clock: Arc<dyn Clock>,
 }
 
-impl MouseMover {
+impl MouseMover<EnigoBackend> {
     /// Create a new MouseMover with the given configuration
     pub fn new(config: MouseMoverConfig) -> Self {
+        Self::with_backend(config, EnigoBackend::new())
+    }
+}
+
+impl Default for MouseMover<EnigoBackend> {
+    /// Create a new MouseMover with default configuration
+    fn default() -> Self {
+        Self::new(MouseMoverConfig::default())
+    }
+}
+
+impl<B: InputBackend> MouseMover<B> {
+    /// Create a new MouseMover that drives the given input backend
+    pub fn with_backend(config: MouseMoverConfig, backend: B) -> Self {
+        let rng = jitter::rng(config.seed);
         Self {
             config,
-            enigo: Enigo::new(),
-            device_state: DeviceState::new(),
-            running: false,
+            backend,
+            handle: Handle::new(),
+            activity: ActivityTracker::new(Instant::now()),
+            event_handler: None,
+            rng,
+            synthetic_fallback: false,
+            clock: Arc::new(SystemClock),
         }
     }
 
-    /// Create a new MouseMover with default configuration
-    pub fn default() -> Self {
-        Self::new(MouseMoverConfig::default())
+    /// Read the time from `clock` instead of real time
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.activity = ActivityTracker::new(clock.now());
+        self.clock = clock;
+    }
+
+    /// Call `handler` for every [`MoverEvent`], replacing any previous handler
+    pub fn set_event_handler<F>(&mut self, handler: F)
+    where
+        F: FnMut(&MoverEvent) + Send + 'static,
+    {
+        self.event_handler = Some(Box::new(handler));
+    }
+
+    fn emit(&mut self, event: MoverEvent) {
+        if let Some(handler) = &mut self.event_handler {
+            handler(&event);
+        }
+    }
+
+    /// A handle that stops, pauses or reconfigures [`start`](Self::start)
+    /// from other threads
+    pub fn handle(&self) -> Handle {
+        self.handle.clone()
+    }
+
+    /// Get a reference to the input backend
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Get a mutable reference to the input backend
+    pub fn backend_mut(&mut self) -> &mut B {
+        &mut self.backend
     }
 
     /// Check if the application has the necessary permissions
     pub fn check_permissions(&self) -> Result<(), KtmmError> {
-        // Use platform-specific implementation
-        platform::check_accessibility_permissions()
+        // The backend knows what it needs, usually the platform's accessibility permissions
+        self.backend.check_permissions()
+    }
+
+    /// How long to wait before the next movement, drawn afresh each time
+    /// when the interval is randomised
+    pub fn next_interval(&mut self) -> Duration {
+        self.config
+            .interval_jitter
+            .sample(self.config.interval_secs, &mut self.rng)
+    }
+
+    /// How far to move the mouse on the next movement
+    pub fn next_offset(&mut self) -> (i32, i32) {
+        match self.config.random_offset {
+            Some(range) => range.sample(&mut self.rng),
+            None => self.config.movement_pixels,
+        }
+    }
+
+    /// Perform a single nudge of the configured kind
+    pub fn nudge_once(&mut self) -> Result<(), KtmmError> {
+        match self.config.nudge {
+            NudgeKind::Pointer => self.move_mouse_once(),
+            NudgeKind::Key => self.tap_key_once(),
+            NudgeKind::Scroll => self.scroll_once(),
+            NudgeKind::Synthetic => self.reset_idle_timer_once(),
+        }
+    }
+
+    /// Reset the idle timer without moving the pointer, or nudge the pointer
+    /// if the backend cannot do that
+    pub fn reset_idle_timer_once(&mut self) -> Result<(), KtmmError> {
+        let started = self.clock.now();
+        match self.backend.reset_idle_timer() {
+            Ok(mechanism) => {
+                self.activity.record_nudge(started, self.clock.now(), None);
+                self.emit(MoverEvent::IdleTimerReset { mechanism });
+                Ok(())
+            }
+            Err(KtmmError::Unsupported(_)) => {
+                if !self.synthetic_fallback {
+                    self.synthetic_fallback = true;
+                    self.emit(MoverEvent::SyntheticUnavailable);
+                }
+                self.move_mouse_once()
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Scroll one notch along the configured axis and back
+    pub fn scroll_once(&mut self) -> Result<(), KtmmError> {
+        let axis = self.config.scroll_axis;
+        let started = self.clock.now();
+        self.backend.scroll(axis, 1)?;
+        self.clock
+            .sleep_until(started + Duration::from_millis(self.config.return_delay_ms));
+        self.backend.scroll(axis, -1)?;
+        self.activity.record_nudge(started, self.clock.now(), None);
+        self.emit(MoverEvent::Scrolled { axis });
+        Ok(())
+    }
+
+    /// Tap the configured harmless key once
+    pub fn tap_key_once(&mut self) -> Result<(), KtmmError> {
+        let key = self.config.nudge_key;
+        let code = key.key().ok_or_else(|| {
+            KtmmError::Unsupported(format!("`{}` cannot be pressed on this platform", key))
+        })?;
+        let started = self.clock.now();
+        self.backend.key_click(code)?;
+        self.activity.record_nudge(started, self.clock.now(), None);
+        self.emit(MoverEvent::KeyTapped { key });
+        Ok(())
     }
 
     /// Perform a single mouse movement cycle
+    ///
+    /// Traces the configured [`Pattern`] and returns the pointer to where it
+    /// was, unless the user moves it in the meantime.
     pub fn move_mouse_once(&mut self) -> Result<(), KtmmError> {
+        let delta = self.next_offset();
+        let duration = Duration::from_millis(self.config.return_delay_ms);
+        let waypoints = self.config.pattern.build(delta, duration).waypoints();
+        let start = self.clock.now();
+
+        if !self.backend.supports_absolute_positioning() {
+            // Without a known position, trace the pattern relatively
+            let mut current = (0, 0);
+            for waypoint in &waypoints {
+                self.clock.sleep_until(start + waypoint.at);
+                let (x, y) = waypoint.offset;
+                self.backend
+                    .mouse_move_relative(x - current.0, y - current.1)?;
+                current = waypoint.offset;
+            }
+            let offsets: Vec<_> = waypoints.iter().map(|waypoint| waypoint.offset).collect();
+            self.activity.record_nudge(start, self.clock.now(), None);
+            self.emit(MoverEvent::Nudged {
+                delta: furthest((0, 0), &offsets),
+            });
+            return Ok(());
+        }
+
         // Get current mouse position
-        let mouse_pos = self.device_state.get_mouse();
-        let (x, y) = (mouse_pos.coords.0, mouse_pos.coords.1);
+        let (x, y) = self.backend.mouse_location()?;
 
-        // Move mouse by the configured amount
-        let (dx, dy) = self.config.movement_pixels;
-        self.enigo.mouse_move_to(x + dx, y + dy);
+        // Plan the pattern around it, staying on the same monitor
+        let monitors = self.backend.monitors();
+        let offsets: Vec<_> = waypoints.iter().map(|waypoint| waypoint.offset).collect();
+        let targets = geometry::plan_path((x, y), &offsets, &monitors);
 
-        // Sleep for the configured delay
-        thread::sleep(Duration::from_millis(self.config.return_delay_ms));
+        let mut expected = None;
+        for (waypoint, &target) in waypoints.iter().zip(&targets) {
+            self.clock.sleep_until(start + waypoint.at);
+
+            // If the user grabbed the mouse in the meantime, leave it where they put it
+            if let Some(expected) = expected {
+                let current = self.backend.mouse_location()?;
+                if current != expected {
+                    self.activity
+                        .record_activity(self.clock.now(), Some(current));
+                    self.emit(MoverEvent::RestoreSkipped {
+                        expected,
+                        actual: current,
+                    });
+                    return Ok(());
+                }
+            }
+            self.backend.mouse_move_to(target.0, target.1)?;
+            expected = Some(target);
+        }
 
-        // Move mouse back to original position
-        self.enigo.mouse_move_to(x, y);
+        self.activity
+            .record_nudge(start, self.clock.now(), Some((x, y)));
+        self.emit(MoverEvent::Nudged {
+            delta: furthest((x, y), &targets),
+        });
 
         Ok(())
     }
 
+    /// How long the user has been idle
+    ///
+    /// Uses the platform idle counter when the backend has one and otherwise
+    /// compares the pointer position and key state with the previous call.
+    pub fn idle_time(&mut self) -> Duration {
+        let now = self.clock.now();
+        match self.backend.idle_time() {
+            Some(idle) => self.activity.observe_idle_counter(now, idle),
+            None => {
+                let pointer = if self.backend.supports_absolute_positioning() {
+                    self.backend.mouse_location().ok()
+                } else {
+                    None
+                };
+                let keys_held = self.backend.keys_held();
+                self.activity.observe_input(now, pointer, keys_held);
+            }
+        }
+        self.activity.idle_for(now)
+    }
+
+    /// Move the mouse unless the user was active within the idle threshold
+    pub fn tick(&mut self) -> Result<Tick, KtmmError> {
+        if let Some(threshold) = self.config.idle_threshold_secs {
+            let threshold = Duration::from_secs(threshold);
+            let idle = self.idle_time();
+            if idle < threshold {
+                let retry_in = (threshold - idle).max(Duration::from_secs(1));
+                self.emit(MoverEvent::Deferred { idle, retry_in });
+                return Ok(Tick::Deferred { idle, retry_in });
+            }
+        }
+        self.nudge_once()?;
+        Ok(Tick::Moved)
+    }
+
     /// Start the mouse mover loop
+    ///
+    /// Runs until the duration elapses or the mover is stopped through
+    /// [`handle`](Self::handle) or [`stop`](Self::stop).
     pub fn start(&mut self) -> Result<(), KtmmError> {
+        let handle = self.handle.clone();
+        self.run_with_handle(&handle)
+    }
+
+    /// Run the mouse mover loop under the control of `handle`
+    ///
+    /// Stopping, pausing, resuming, nudging, reloading or changing the
+    /// interval through the handle takes effect immediately; all but stopping
+    /// restart the wait for the next movement.
+    pub fn run_with_handle(&mut self, handle: &Handle) -> Result<(), KtmmError> {
         // Check permissions first
         self.check_permissions()?;
 
-        self.running = true;
+        if self.config.once {
+            return self.nudge_once();
+        }
+
+        let deadline = self.config.deadline_from(&*self.clock);
+
+        let mut state = handle.state();
+        let reloaded = handle
+            .take_config()
+            .and_then(|config| self.try_reconfigure(config));
+        self.apply_handle_state(&state);
+        let mut next = self.clock.now() + self.next_interval();
+        let mut activation = match reloaded {
+            Some(activation) => activation,
+            None => Activation::from_config(&self.config)?,
+        };
+        let (mut scheduled, mut schedule_check) = self.check_activation(&mut activation, None);
 
-        while self.running {
-            // Sleep for the configured interval
-            thread::sleep(Duration::from_secs(self.config.interval_secs));
+        while !state.stopped {
+            // Sleep until the next tick, a change or the schedule flipping,
+            // but never past the deadline
+            let tick = (!state.paused && scheduled).then_some(next);
+            let wake = [tick, schedule_check, deadline].into_iter().flatten().min();
+            let new_state = self.clock.wait(handle, state, wake);
 
-            // Move the mouse
-            if let Err(e) = self.move_mouse_once() {
-                eprintln!("Error moving mouse: {}", e);
-                // Continue running despite errors
+            if new_state.stopped || deadline.is_some_and(|deadline| self.clock.now() >= deadline) {
+                break;
+            }
+            if new_state != state {
+                if let Some(config) = handle.take_config() {
+                    if let Some(reloaded) = self.try_reconfigure(config) {
+                        activation = reloaded;
+                        (scheduled, schedule_check) =
+                            self.check_activation(&mut activation, Some(scheduled));
+                    }
+                }
+                let nudge = new_state.nudge_requests != state.nudge_requests;
+                state = new_state;
+                self.apply_handle_state(&state);
+                if nudge && scheduled {
+                    if let Err(e) = self.nudge_once() {
+                        eprintln!("Error moving mouse: {}", e);
+                    }
+                }
+                next = self.clock.now() + self.next_interval();
+                continue;
             }
+            if schedule_check.is_some_and(|check| self.clock.now() >= check) {
+                let was_scheduled = scheduled;
+                (scheduled, schedule_check) =
+                    self.check_activation(&mut activation, Some(scheduled));
+                if scheduled && !was_scheduled {
+                    next = self.clock.now() + self.next_interval();
+                }
+            }
+            if state.paused || !scheduled || self.clock.now() < next {
+                continue;
+            }
+
+            // Move the mouse if the user is idle
+            let wait = match self.tick() {
+                Ok(Tick::Deferred { retry_in, .. }) => retry_in,
+                Ok(Tick::Moved) => self.next_interval(),
+                Err(e) => {
+                    eprintln!("Error moving mouse: {}", e);
+                    // Continue running despite errors
+                    self.next_interval()
+                }
+            };
+            next = self.clock.now() + wait;
         }
 
         Ok(())
     }
 
-    /// Stop the mouse mover loop
-    pub fn stop(&mut self) {
-        self.running = false;
+    /// Spawn the mouse mover loop on a new thread
+    ///
+    /// Returns a handle to control the loop and the thread's join handle.
+    pub fn spawn(mut self) -> (Handle, thread::JoinHandle<Result<(), KtmmError>>)
+    where
+        B: Send + 'static,
+    {
+        let handle = self.handle.clone();
+        let thread = thread::spawn(move || self.start());
+        (handle, thread)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::sync::{Arc, Mutex};
+    /// Keep the system awake while `task` runs on another thread
+    ///
+    /// The mover loop runs on the current thread, under its own handle, until
+    /// the task returns. The task's result is only returned once it finishes,
+    /// even if the configured duration elapses first.
+    pub fn run_while<T, F>(&mut self, task: F) -> Result<T, KtmmError>
+    where
+        F: FnOnce() -> T + Send,
+        T: Send,
+    {
+        self.check_permissions()?;
+
+        let handle = Handle::new();
+        thread::scope(|scope| {
+            let worker = scope.spawn(|| {
+                let result = task();
+                handle.stop();
+                result
+            });
+            let run = self.run_with_handle(&handle);
+            let result = worker
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            run.map(|()| result)
+        })
+    }
 
-    // This mock implementation is used in tests/mock_tests.rs
-    // We're keeping it here as a reference for how to mock the dependencies
-    #[allow(dead_code)]
-    struct MockEnigo {
-        positions: Arc<Mutex<Vec<(i32, i32)>>>,
+    /// Run `command` and keep the system awake until it exits
+    pub fn run_command(&mut self, command: &mut Command) -> Result<ExitStatus, KtmmError> {
+        // Fail before starting the command rather than leaving it running unwatched
+        self.check_permissions()?;
+
+        let program = command.get_program().to_string_lossy().into_owned();
+        let mut child = command
+            .spawn()
+            .map_err(|e| KtmmError::Other(format!("cannot run `{}`: {}", program, e)))?;
+        self.run_while(move || child.wait())?
+            .map_err(|e| KtmmError::Other(format!("cannot wait for `{}`: {}", program, e)))
+    }
+
+    /// Stop the mouse mover loop
+    pub fn stop(&self) {
+        self.handle.stop();
+    }
+
+    /// Whether the schedule is active now, and when to check it again
+    ///
+    /// Emits [`MoverEvent::ScheduleChanged`] if the state differs from
+    /// `previous` (or, initially, if the schedule starts out inactive), and
+    /// [`MoverEvent::ActivationError`] for each source that failed to read.
+    fn check_activation(
+        &mut self,
+        activation: &mut Activation,
+        previous: Option<bool>,
+    ) -> (bool, Option<Instant>) {
+        let now = self.clock.local_now();
+        let active = activation.is_active_at(&now);
+        if previous.unwrap_or(true) != active {
+            self.emit(MoverEvent::ScheduleChanged {
+                active,
+                next_change: activation.next_change(&now),
+            });
+        }
+        let recheck = activation.recheck_in(&now);
+        for e in activation.take_errors() {
+            self.emit(MoverEvent::ActivationError {
+                message: e.to_string(),
+            });
+        }
+        (active, recheck.map(|wait| self.clock.now() + wait))
     }
 
-    #[allow(dead_code)]
-    impl MockEnigo {
-        fn new() -> Self {
-            Self {
-                positions: Arc::new(Mutex::new(Vec::new())),
+    /// Switch to a configuration passed to [`Handle::reload`] if its schedule
+    /// can be read, returning the schedule
+    ///
+    /// Otherwise the whole configuration is rejected, which is reported as
+    /// [`MoverEvent::ReloadRejected`].
+    fn try_reconfigure(&mut self, config: MouseMoverConfig) -> Option<Activation> {
+        match Activation::from_config(&config) {
+            Ok(activation) => {
+                self.reconfigure(config);
+                Some(activation)
+            }
+            Err(e) => {
+                self.emit(MoverEvent::ReloadRejected {
+                    message: e.to_string(),
+                });
+                None
             }
         }
+    }
 
-        fn mouse_move_to(&mut self, x: i32, y: i32) {
-            self.positions.lock().unwrap().push((x, y));
+    /// Switch to a configuration passed to [`Handle::reload`]
+    ///
+    /// The mode, backend and end of the run stay as they were.
+    fn reconfigure(&mut self, mut config: MouseMoverConfig) {
+        config.mode = self.config.mode;
+        config.backend = self.config.backend;
+        config.once = self.config.once;
+        config.duration_secs = self.config.duration_secs;
+        config.until = self.config.until;
+        if config.seed != self.config.seed {
+            self.rng = jitter::rng(config.seed);
         }
+        self.config = config;
+    }
 
-        fn get_positions(&self) -> Vec<(i32, i32)> {
-            self.positions.lock().unwrap().clone()
+    fn apply_handle_state(&mut self, state: &HandleState) {
+        if let Some(interval) = state.interval_secs {
+            self.config.interval_secs = interval;
+            // A range ignores the interval, so an explicit one replaces it
+            if let IntervalJitter::Uniform { .. } = self.config.interval_jitter {
+                self.config.interval_jitter = IntervalJitter::Fixed;
+            }
         }
     }
+}
+
+/// The offset of the point in `points` furthest from `origin`
+fn furthest(origin: (i32, i32), points: &[(i32, i32)]) -> (i32, i32) {
+    points
+        .iter()
+        .map(|&(x, y)| (x - origin.0, y - origin.1))
+        .max_by_key(|&(dx, dy)| i64::from(dx).pow(2) + i64::from(dy).pow(2))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::InputEvent;
+    use std::sync::{Arc, Mutex};
 
     #[test]
     fn test_config_default() {
@@ -175,10 +1058,258 @@ mod tests {
             interval_secs: 30,
             movement_pixels: (2, 3),
             return_delay_ms: 10,
+            ..MouseMoverConfig::default()
         };
 
         assert_eq!(config.interval_secs, 30);
         assert_eq!(config.movement_pixels, (2, 3));
         assert_eq!(config.return_delay_ms, 10);
     }
+
+    #[test]
+    fn test_move_mouse_once_with_recording_backend() {
+        let backend = RecordingBackend::new((10, 20));
+        let config = MouseMoverConfig {
+            interval_secs: 1,
+            movement_pixels: (3, -4),
+            return_delay_ms: 0,
+            ..MouseMoverConfig::default()
+        };
+        let mut mover = MouseMover::with_backend(config, backend.clone());
+
+        mover.move_mouse_once().unwrap();
+
+        assert_eq!(
+            backend.events(),
+            vec![InputEvent::MoveTo(13, 16), InputEvent::MoveTo(10, 20)]
+        );
+        assert_eq!(backend.position(), (10, 20));
+    }
+
+    #[test]
+    fn test_seeded_movers_repeat_the_same_jitter() {
+        let config = MouseMoverConfig {
+            interval_jitter: IntervalJitter::Exponential { max_secs: None },
+            random_offset: Some(OffsetRange { min: 2, max: 6 }),
+            seed: Some(1234),
+            return_delay_ms: 0,
+            ..MouseMoverConfig::default()
+        };
+        let run = |config: &MouseMoverConfig| {
+            let backend = RecordingBackend::new((100, 100));
+            let mut mover = MouseMover::with_backend(config.clone(), backend.clone());
+            let intervals: Vec<Duration> = (0..5).map(|_| mover.next_interval()).collect();
+            for _ in 0..5 {
+                mover.move_mouse_once().unwrap();
+            }
+            assert_eq!(backend.position(), (100, 100));
+            (intervals, backend.events())
+        };
+
+        let (intervals, events) = run(&config);
+        assert_eq!((intervals.clone(), events.clone()), run(&config));
+        assert!(intervals.iter().any(|&interval| interval != intervals[0]));
+        assert_ne!(events[0], events[2]);
+
+        let reseeded = MouseMoverConfig {
+            seed: Some(4321),
+            ..config
+        };
+        assert_ne!(run(&reseeded).0, intervals);
+    }
+
+    #[test]
+    fn test_key_nudge_taps_the_key_without_moving() {
+        let backend = RecordingBackend::new((10, 20));
+        let config = MouseMoverConfig {
+            nudge: NudgeKind::Key,
+            nudge_key: NudgeKey::F15,
+            return_delay_ms: 0,
+            ..MouseMoverConfig::default()
+        };
+        let mut mover = MouseMover::with_backend(config, backend.clone());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        mover.set_event_handler(move |event| sink.lock().unwrap().push(event.clone()));
+
+        assert_eq!(mover.tick().unwrap(), Tick::Moved);
+
+        assert_eq!(
+            backend.events(),
+            vec![InputEvent::KeyClick(backend::Key::F15)]
+        );
+        assert_eq!(backend.position(), (10, 20));
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![MoverEvent::KeyTapped { key: NudgeKey::F15 }]
+        );
+    }
+
+    #[test]
+    fn test_scroll_nudge_leaves_the_pointer_alone() {
+        let backend = RecordingBackend::new((10, 20));
+        let config = MouseMoverConfig {
+            nudge: NudgeKind::Scroll,
+            scroll_axis: ScrollAxis::Horizontal,
+            return_delay_ms: 0,
+            ..MouseMoverConfig::default()
+        };
+        let mut mover = MouseMover::with_backend(config, backend.clone());
+
+        mover.nudge_once().unwrap();
+
+        assert_eq!(
+            backend.events(),
+            vec![
+                InputEvent::Scroll(ScrollAxis::Horizontal, 1),
+                InputEvent::Scroll(ScrollAxis::Horizontal, -1),
+            ]
+        );
+        assert!(backend.positions().is_empty());
+        assert_eq!(backend.position(), (10, 20));
+    }
+
+    #[test]
+    fn test_synthetic_nudge_falls_back_to_the_pointer() {
+        let backend = RecordingBackend::new((10, 20));
+        let config = MouseMoverConfig {
+            nudge: NudgeKind::Synthetic,
+            return_delay_ms: 0,
+            ..MouseMoverConfig::default()
+        };
+        let mut mover = MouseMover::with_backend(config, backend.clone());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        mover.set_event_handler(move |event| sink.lock().unwrap().push(event.clone()));
+
+        mover.nudge_once().unwrap();
+        assert_eq!(backend.events(), vec![InputEvent::ResetIdleTimer]);
+
+        backend.clear();
+        backend.set_idle_reset_supported(false);
+        mover.nudge_once().unwrap();
+        mover.nudge_once().unwrap();
+        assert_eq!(
+            backend.events(),
+            vec![
+                InputEvent::MoveTo(11, 21),
+                InputEvent::MoveTo(10, 20),
+                InputEvent::MoveTo(11, 21),
+                InputEvent::MoveTo(10, 20),
+            ]
+        );
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                MoverEvent::IdleTimerReset {
+                    mechanism: "recording"
+                },
+                MoverEvent::SyntheticUnavailable,
+                MoverEvent::Nudged { delta: (1, 1) },
+                MoverEvent::Nudged { delta: (1, 1) },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_restore_skipped_when_user_moves_during_delay() {
+        let backend = RecordingBackend::new((10, 20));
+        let config = MouseMoverConfig {
+            return_delay_ms: 0,
+            ..MouseMoverConfig::default()
+        };
+        let mut mover = MouseMover::with_backend(config, backend.clone());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        mover.set_event_handler(move |event| sink.lock().unwrap().push(event.clone()));
+
+        backend.interrupt_next_move(200, 100);
+        mover.move_mouse_once().unwrap();
+
+        assert_eq!(backend.events(), vec![InputEvent::MoveTo(11, 21)]);
+        assert_eq!(backend.position(), (200, 100));
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![MoverEvent::RestoreSkipped {
+                expected: (11, 21),
+                actual: (200, 100),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_patterns_are_traced_and_return_to_the_start() {
+        let backend = RecordingBackend::new((1915, 500));
+        backend.set_monitors(vec![geometry::Rect::new(0, 0, 1920, 1080)]);
+        let config = MouseMoverConfig {
+            movement_pixels: (6, 6),
+            pattern: Pattern::Square,
+            return_delay_ms: 0,
+            ..MouseMoverConfig::default()
+        };
+        let mut mover = MouseMover::with_backend(config, backend.clone());
+
+        mover.move_mouse_once().unwrap();
+
+        // Mirrored to the left to stay on the monitor
+        assert_eq!(
+            backend.positions(),
+            vec![(1909, 500), (1909, 506), (1915, 506), (1915, 500)]
+        );
+
+        backend.clear();
+        mover.config.pattern = Pattern::Circle;
+        backend.interrupt_next_move(42, 42);
+        mover.move_mouse_once().unwrap();
+        assert_eq!(backend.events().len(), 1);
+        assert_eq!(backend.position(), (42, 42));
+    }
+
+    #[test]
+    fn test_tick_defers_while_user_is_active() {
+        let backend = RecordingBackend::new((10, 20));
+        let config = MouseMoverConfig {
+            return_delay_ms: 0,
+            idle_threshold_secs: Some(30),
+            ..MouseMoverConfig::default()
+        };
+        let mut mover = MouseMover::with_backend(config, backend.clone());
+
+        backend.set_idle_time(Some(Duration::from_secs(10)));
+        assert_eq!(
+            mover.tick().unwrap(),
+            Tick::Deferred {
+                idle: Duration::from_secs(10),
+                retry_in: Duration::from_secs(20),
+            }
+        );
+        assert!(backend.events().is_empty());
+
+        backend.set_idle_time(Some(Duration::from_secs(45)));
+        assert_eq!(mover.tick().unwrap(), Tick::Moved);
+        assert_eq!(backend.events().len(), 2);
+    }
+
+    #[test]
+    fn test_tick_samples_pointer_without_idle_counter() {
+        let backend = RecordingBackend::new((10, 20));
+        let config = MouseMoverConfig {
+            return_delay_ms: 0,
+            idle_threshold_secs: Some(30),
+            ..MouseMoverConfig::default()
+        };
+        let mut mover = MouseMover::with_backend(config, backend.clone());
+        // Pretend the mover was created a minute ago
+        mover.activity = ActivityTracker::new(Instant::now() - Duration::from_secs(60));
+
+        backend.set_keys_held(true);
+        assert!(matches!(mover.tick().unwrap(), Tick::Deferred { .. }));
+        backend.set_keys_held(false);
+        backend.set_position(11, 20);
+        assert!(matches!(mover.tick().unwrap(), Tick::Deferred { .. }));
+        assert!(backend.events().is_empty());
+
+        mover.activity = ActivityTracker::new(Instant::now() - Duration::from_secs(60));
+        assert_eq!(mover.tick().unwrap(), Tick::Moved);
+    }
 }
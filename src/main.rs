@@ -1,28 +1,487 @@
-use std::env;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::HashMap;
+use std::process::{self, ExitStatus};
+#[cfg(unix)]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(unix)]
 use std::sync::Arc;
-use std::{thread, time::Duration};
+use std::thread;
+use std::time::Instant;
 
-use ktmm::{KtmmError, MouseMover, MouseMoverConfig};
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use ktmm::activation::Activation;
+use ktmm::cli::{Cli, Command};
+#[cfg(unix)]
+use ktmm::daemon::{self, Request, Server};
+#[cfg(target_os = "linux")]
+use ktmm::inhibit::Inhibitor;
+#[cfg(unix)]
+use ktmm::instance::{self, InstanceLock, LockAttempt};
+use ktmm::jitter::IntervalJitter;
+#[cfg(target_os = "linux")]
+use ktmm::systemd::{self, Notifier};
+use ktmm::timespec::format_duration;
+#[cfg(target_os = "linux")]
+use ktmm::uinput::UinputBackend;
+#[cfg(unix)]
+use ktmm::watch;
+use ktmm::{
+    config, BackendKind, EventHandler, Handle, InputBackend, KeepAwakeMode, KtmmError, MouseMover,
+    MouseMoverConfig, MoverEvent, NudgeKind, Verbosity,
+};
+
+/// Exit code when the run ends because `--duration`/`--for` or `--until` was reached
+const EXIT_DEADLINE_REACHED: i32 = 124;
+
+/// How a keep-awake run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunEnd {
+    /// Stopped by a signal or the watched processes, or done after `--once`
+    Stopped,
+    /// The configured duration or end time was reached
+    DeadlineReached,
+}
+
+type RunResult = Result<RunEnd, Box<dyn std::error::Error>>;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Parse command line arguments
-    let args: Vec<String> = env::args().collect();
+    let cli = Cli::parse();
+    if let Some(command) = &cli.command {
+        return match command {
+            Command::Profiles => list_profiles(&cli),
+            Command::Schedule { next } => show_schedule(&cli, *next),
+            #[cfg(unix)]
+            Command::Daemon => run_daemon(&cli),
+            #[cfg(unix)]
+            Command::Status => control(&cli, Request::Status),
+            #[cfg(unix)]
+            Command::Pause => control(&cli, Request::Pause),
+            #[cfg(unix)]
+            Command::Resume => control(&cli, Request::Resume),
+            #[cfg(unix)]
+            Command::Stop => control(&cli, Request::Stop),
+            #[cfg(unix)]
+            Command::SetInterval { secs } => control(&cli, Request::SetInterval { secs: *secs }),
+            #[cfg(target_os = "linux")]
+            Command::InstallService { force } => install_service(&cli, *force),
+            #[cfg(not(target_os = "linux"))]
+            Command::InstallService { .. } => {
+                let e = KtmmError::Unsupported(
+                    "systemd services are only available on Linux".to_string(),
+                );
+                eprintln!("{}", e);
+                Err(e.into())
+            }
+            #[cfg(not(unix))]
+            _ => {
+                let e = KtmmError::Unsupported("the daemon is only available on Unix".to_string());
+                eprintln!("{}", e);
+                Err(e.into())
+            }
+        };
+    }
 
-    // Check for version flag
-    if args.len() > 1 && (args[1] == "--version" || args[1] == "-v") {
-        println!("ktmm {}", env!("CARGO_PKG_VERSION"));
-        return Ok(());
+    let config = resolve_config(&cli);
+    // A single nudge cannot fight over the pointer and the inhibitors leave
+    // it alone, so only the runs that keep nudging are kept to one instance
+    let instance =
+        (!config.once && config.mode != KeepAwakeMode::Inhibit).then(|| lock_instance(&cli));
+    if !cli.exec.is_empty() {
+        let code = run_wrapped(&cli.exec, config);
+        drop(instance);
+        process::exit(code);
+    }
+
+    // Set up signal handling for graceful shutdown, pausing and reloading
+    let handle = Handle::new();
+    setup_signal_handlers(handle.clone(), &cli, &config, |_| {});
+    let watcher = if cli.wait_pid.is_empty() {
+        None
+    } else {
+        Some(watch_pids(&cli.wait_pid, &handle)?)
+    };
+
+    let verbosity = config.verbosity;
+    let end = keep_awake(config, Until::Stopped(&handle), Hooks::default())?;
+    if watcher.is_some_and(|watcher| watcher.is_finished()) && verbosity != Verbosity::Quiet {
+        println!("All watched processes have exited.");
+    }
+    drop(instance);
+    if end == RunEnd::DeadlineReached {
+        process::exit(EXIT_DEADLINE_REACHED);
+    }
+    Ok(())
+}
+
+/// Become the only running instance, or exit naming the one already running
+///
+/// With `--replace`, the running instance is asked to exit instead.
+#[cfg(unix)]
+fn lock_instance(cli: &Cli) -> InstanceLock {
+    let env: HashMap<String, String> = std::env::vars().collect();
+    let lock_path = instance::runtime_path(&env, "lock");
+    let pid_path = instance::runtime_path(&env, "pid");
+    let result = if cli.replace {
+        InstanceLock::replace(&lock_path, &pid_path, instance::REPLACE_TIMEOUT)
+    } else {
+        match InstanceLock::try_acquire(&lock_path, &pid_path) {
+            Ok(LockAttempt::Acquired(lock)) => Ok(lock),
+            Ok(LockAttempt::Held { pid }) => {
+                match pid {
+                    Some(pid) => eprintln!("ktmm is already running (PID {}).", pid),
+                    None => eprintln!("ktmm is already running."),
+                }
+                eprintln!("Stop it first, or pass --replace to take over from it.");
+                process::exit(1);
+            }
+            Err(e) => Err(e),
+        }
+    };
+    result.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    })
+}
+
+#[cfg(not(unix))]
+fn lock_instance(_cli: &Cli) {}
+
+/// Keep the system awake like a plain run, taking commands on the control socket
+#[cfg(unix)]
+fn run_daemon(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let config = resolve_config(cli);
+    let instance = lock_instance(cli);
+    let handle = Handle::new();
+    // The interval reported without an override follows config reloads
+    let interval_secs = Arc::new(AtomicU64::new(config.interval_secs));
+    let reloaded = interval_secs.clone();
+    setup_signal_handlers(handle.clone(), cli, &config, move |config| {
+        reloaded.store(config.interval_secs, Ordering::Relaxed)
+    });
+
+    let path = socket_path(cli);
+    let server = Server::bind(&path).inspect_err(|e| eprintln!("{}", e))?;
+    let (mode, started) = (config.mode, Instant::now());
+    server.serve(handle.clone(), move |state| daemon::Status {
+        pid: process::id(),
+        paused: state.paused,
+        interval_secs: state
+            .interval_secs
+            .unwrap_or_else(|| interval_secs.load(Ordering::Relaxed)),
+        mode: mode.to_string(),
+        uptime_secs: started.elapsed().as_secs(),
+    })?;
+    if config.verbosity != Verbosity::Quiet {
+        println!("Listening for commands on {}", server.path().display());
+    }
+
+    #[cfg(target_os = "linux")]
+    let notifier = notify_systemd(&handle);
+    #[cfg(target_os = "linux")]
+    let hooks = match notifier.clone() {
+        Some(notifier) => {
+            let status = match config.mode {
+                KeepAwakeMode::Mouse => format!(
+                    "Waiting to nudge every {}",
+                    format_duration(config.interval_secs)
+                ),
+                KeepAwakeMode::Inhibit => "Holding idle inhibitors".to_string(),
+            };
+            let ready = notifier.clone();
+            Hooks {
+                on_ready: Some(Box::new(move || {
+                    if let Err(e) = ready.ready(&status) {
+                        eprintln!("{}", e);
+                    }
+                })),
+                on_event: Some(Box::new(move |event: &MoverEvent| {
+                    if event.is_nudge() {
+                        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+                        let _ = notifier.status(&format!("Last nudge at {}", now));
+                    }
+                })),
+            }
+        }
+        None => Hooks::default(),
+    };
+    #[cfg(not(target_os = "linux"))]
+    let hooks = Hooks::default();
+
+    let end = keep_awake(config, Until::Stopped(&handle), hooks);
+    #[cfg(target_os = "linux")]
+    if let Some(notifier) = notifier {
+        let _ = notifier.stopping();
+    }
+    let end = end?;
+    // Remove the socket and release the lock before exiting
+    drop(server);
+    drop(instance);
+    if end == RunEnd::DeadlineReached {
+        process::exit(EXIT_DEADLINE_REACHED);
+    }
+    Ok(())
+}
+
+/// Connect to systemd when the daemon runs as a notify-type service, and
+/// keep its watchdog fed
+///
+/// The watchdog is pinged from the run loop's waits through the handle's
+/// heartbeat, so the pings stop, and systemd restarts the daemon, if the loop
+/// hangs. Readiness is only reported once the run has started.
+#[cfg(target_os = "linux")]
+fn notify_systemd(handle: &Handle) -> Option<Arc<Notifier>> {
+    let env: HashMap<String, String> = std::env::vars().collect();
+    let notifier = match Notifier::from_env(&env) {
+        Ok(notifier) => Arc::new(notifier?),
+        Err(e) => {
+            eprintln!("{}", e);
+            return None;
+        }
+    };
+    if let Some(every) = systemd::watchdog_interval(&env) {
+        let notifier = notifier.clone();
+        handle.set_heartbeat(every, move || {
+            let _ = notifier.watchdog();
+        });
+    }
+    Some(notifier)
+}
+
+/// Write a systemd user unit that runs `ktmm daemon` with the same config
+/// file and profile as this invocation
+#[cfg(target_os = "linux")]
+fn install_service(cli: &Cli, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let env: HashMap<String, String> = std::env::vars().collect();
+    let Some(path) = systemd::unit_path(&env) else {
+        let e = KtmmError::ConfigError(
+            "cannot find the systemd user unit directory: neither XDG_CONFIG_HOME nor HOME is set"
+                .to_string(),
+        );
+        eprintln!("{}", e);
+        return Err(e.into());
+    };
+
+    let mut exec_start = vec![std::env::current_exe()?.display().to_string()];
+    if let Some(config) = &cli.config {
+        exec_start.push("--config".to_string());
+        exec_start.push(std::path::absolute(config)?.display().to_string());
+    }
+    if let Some(profile) = &cli.profile {
+        exec_start.push("--profile".to_string());
+        exec_start.push(profile.clone());
+    }
+    exec_start.push("daemon".to_string());
+
+    systemd::install_unit(&path, &exec_start, force).inspect_err(|e| eprintln!("{}", e))?;
+    println!("Wrote {}", path.display());
+    println!("Start it now and at every login with:");
+    println!("  systemctl --user daemon-reload");
+    println!("  systemctl --user enable --now ktmm.service");
+    Ok(())
+}
+
+/// Send `request` to a running daemon and report its state
+#[cfg(unix)]
+fn control(cli: &Cli, request: Request) -> Result<(), Box<dyn std::error::Error>> {
+    let status = match daemon::send(&socket_path(cli), request) {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    let every = format_duration(status.interval_secs);
+    match request {
+        Request::Stop => println!("Stopped the daemon (PID {}).", status.pid),
+        _ if status.paused => println!(
+            "Paused (PID {}, {} mode, every {} when resumed, up {}).",
+            status.pid,
+            status.mode,
+            every,
+            format_duration(status.uptime_secs)
+        ),
+        _ => println!(
+            "Running (PID {}, {} mode, every {}, up {}).",
+            status.pid,
+            status.mode,
+            every,
+            format_duration(status.uptime_secs)
+        ),
+    }
+    Ok(())
+}
+
+/// The control socket given with `--socket`, or the default location
+#[cfg(unix)]
+fn socket_path(cli: &Cli) -> std::path::PathBuf {
+    match &cli.socket {
+        Some(path) => path.clone(),
+        None => daemon::socket_path(&std::env::vars().collect()),
+    }
+}
+
+/// Stop `handle` once all the given processes have exited
+#[cfg(unix)]
+fn watch_pids(
+    pids: &[u32],
+    handle: &Handle,
+) -> Result<thread::JoinHandle<()>, Box<dyn std::error::Error>> {
+    if let Some(pid) = pids.iter().find(|pid| !watch::process_exists(**pid)) {
+        Cli::command()
+            .error(
+                ErrorKind::ValueValidation,
+                format!("no process with PID {} is running", pid),
+            )
+            .exit();
+    }
+
+    Ok(watch::stop_when_exited(pids.to_vec(), handle.clone()))
+}
+
+#[cfg(not(unix))]
+fn watch_pids(
+    _pids: &[u32],
+    _handle: &Handle,
+) -> Result<thread::JoinHandle<()>, Box<dyn std::error::Error>> {
+    let e = KtmmError::Unsupported("--wait-pid is only available on Unix".to_string());
+    eprintln!("{}", e);
+    Err(e.into())
+}
+
+/// What ends a run
+enum Until<'a> {
+    /// The handle being stopped, by a signal, the control socket or
+    /// `--wait-pid`
+    Stopped(&'a Handle),
+    /// The wrapped command exiting
+    Exited(&'a mut process::Child),
+}
+
+/// Callbacks into a run, for a supervisor such as systemd
+#[derive(Default)]
+struct Hooks {
+    /// Called once the backend or the inhibitors are in place
+    on_ready: Option<Box<dyn FnOnce()>>,
+    /// Also receives the mouse mover's events
+    on_event: Option<EventHandler>,
+}
+
+impl Hooks {
+    fn ready(&mut self) {
+        if let Some(on_ready) = self.on_ready.take() {
+            on_ready();
+        }
+    }
+}
+
+/// Keep the system awake in the configured mode until the run ends
+fn keep_awake(config: MouseMoverConfig, until: Until, hooks: Hooks) -> RunResult {
+    match config.mode {
+        // The virtual uinput device is a pointer, so keys always go through the native backend
+        KeepAwakeMode::Mouse => match (config.backend.resolve(), config.nudge) {
+            (BackendKind::Uinput, nudge) if nudge != NudgeKind::Key => {
+                run_uinput(config, until, hooks)
+            }
+            _ => run_mouse_mover(MouseMover::new(config), until, hooks),
+        },
+        KeepAwakeMode::Inhibit => match until {
+            Until::Stopped(handle) => run_inhibitor(config, handle, hooks),
+            // The inhibitor has no mover to wait for the command with
+            Until::Exited(child) => {
+                let handle = Handle::new();
+                thread::scope(|scope| {
+                    scope.spawn(|| {
+                        let _ = child.wait();
+                        handle.stop();
+                    });
+                    run_inhibitor(config, &handle, hooks)
+                })
+            }
+        },
+    }
+}
+
+/// Run `argv` and keep the system awake until it exits, returning the exit
+/// code to pass on
+fn run_wrapped(argv: &[String], mut config: MouseMoverConfig) -> i32 {
+    // Leave the command's output alone unless asked for progress reports
+    if config.verbosity == Verbosity::Normal {
+        config.verbosity = Verbosity::Quiet;
+    }
+
+    let mut child = match process::Command::new(&argv[0]).args(&argv[1..]).spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("ktmm: cannot run `{}`: {}", argv[0], e);
+            return if e.kind() == std::io::ErrorKind::NotFound {
+                127
+            } else {
+                126
+            };
+        }
+    };
+
+    #[cfg(unix)]
+    if let Err(e) = forward_signals(child.id()) {
+        eprintln!("ktmm: cannot forward signals to `{}`: {}", argv[0], e);
+    }
+
+    // Failing to keep the system awake is reported but must not abandon the
+    // command, whose status is kept once it has been waited for
+    let _ = keep_awake(config, Until::Exited(&mut child), Hooks::default());
+    match child.wait() {
+        Ok(status) => exit_code(status),
+        Err(e) => {
+            eprintln!("ktmm: cannot wait for `{}`: {}", argv[0], e);
+            1
+        }
     }
+}
 
-    let config = parse_args();
+/// The exit code a shell would report for `status`
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
 
-    // Create a new MouseMover with the parsed configuration
-    let mut mouse_mover = MouseMover::new(config);
+/// Pass the signals a wrapper is expected to forward on to the child process
+///
+/// Signals generated by the terminal (Ctrl+C, Ctrl+\) already reach the
+/// child through its process group, so only signals sent by other processes
+/// are forwarded. The command owns these signals, so a wrapping KTMM does
+/// not also pause, nudge or reload on them.
+#[cfg(unix)]
+fn forward_signals(pid: u32) -> std::io::Result<()> {
+    use signal_hook::consts::signal::{SIGHUP, SIGINT, SIGQUIT, SIGTERM, SIGUSR1, SIGUSR2};
+    use signal_hook::iterator::exfiltrator::WithOrigin;
+    use signal_hook::iterator::SignalsInfo;
+    use signal_hook::low_level::siginfo::Cause;
+
+    let mut signals =
+        SignalsInfo::<WithOrigin>::new([SIGHUP, SIGINT, SIGQUIT, SIGTERM, SIGUSR1, SIGUSR2])?;
+    thread::spawn(move || {
+        for origin in signals.forever() {
+            if origin.cause != Cause::Kernel {
+                unsafe {
+                    libc::kill(pid as libc::pid_t, origin.signal);
+                }
+            }
+        }
+    });
+    Ok(())
+}
 
-    // Set up signal handling for graceful shutdown
-    let running = Arc::new(AtomicBool::new(true));
-    setup_signal_handlers(running.clone());
+fn run_mouse_mover<B: InputBackend>(
+    mut mouse_mover: MouseMover<B>,
+    until: Until,
+    mut hooks: Hooks,
+) -> RunResult {
+    let verbosity = mouse_mover.config.verbosity;
 
     // Check for necessary permissions
     if let Err(e) = mouse_mover.check_permissions() {
@@ -45,58 +504,470 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
-    println!("KTMM is running. Press Ctrl+C to exit.");
-    
-    // Main loop - runs in the current thread
-    while running.load(Ordering::SeqCst) {
-        // Instead of one long sleep, use shorter sleeps and check the running flag frequently
-        let total_sleep_secs = mouse_mover.config.interval_secs;
-        let check_interval_ms = 100; // Check every 100ms for interruption
-        let iterations = (total_sleep_secs * 1000) / check_interval_ms;
-        
-        for _ in 0..iterations {
-            if !running.load(Ordering::SeqCst) {
-                break; // Exit the sleep loop if we've been signaled to stop
+    let deadline = mouse_mover.config.deadline();
+    let mut on_event = hooks.on_event.take();
+    mouse_mover.set_event_handler(move |event| {
+        if event.is_error() {
+            eprintln!("{}", event);
+        } else if verbosity == Verbosity::Verbose {
+            match deadline {
+                Some(deadline) => println!("{} {} remaining.", event, remaining(deadline)),
+                None => println!("{}", event),
             }
-            thread::sleep(Duration::from_millis(check_interval_ms));
         }
-        
-        // Only move the mouse if we're still running
-        if running.load(Ordering::SeqCst) {
-            if let Err(e) = mouse_mover.move_mouse_once() {
-                eprintln!("Error moving mouse: {}", e);
-                // Continue running despite errors
+        if let Some(on_event) = &mut on_event {
+            on_event(event);
+        }
+    });
+    hooks.ready();
+
+    if mouse_mover.config.once {
+        mouse_mover.nudge_once()?;
+        return Ok(RunEnd::Stopped);
+    }
+
+    if verbosity != Verbosity::Quiet {
+        println!("KTMM is running. Press Ctrl+C to exit.");
+    }
+    print_deadline(&mouse_mover.config, deadline);
+
+    // Runs in the current thread until stopped or the deadline is reached,
+    // or, for a command, until it exits
+    match until {
+        Until::Stopped(handle) => {
+            mouse_mover.run_with_handle(handle)?;
+            Ok(finish(verbosity, handle.is_stopped()))
+        }
+        Until::Exited(child) => {
+            // The caller waits again for the status, reporting any error then
+            let _ = mouse_mover.run_while(|| child.wait())?;
+            Ok(finish(verbosity, true))
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn run_uinput(config: MouseMoverConfig, until: Until, hooks: Hooks) -> RunResult {
+    match UinputBackend::open() {
+        Ok(backend) => run_mouse_mover(MouseMover::with_backend(config, backend), until, hooks),
+        Err(e) => {
+            eprintln!("{}", e);
+            Err(e.into())
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn run_uinput(_config: MouseMoverConfig, _until: Until, _hooks: Hooks) -> RunResult {
+    let e = KtmmError::Unsupported("the uinput backend is only available on Linux".to_string());
+    eprintln!("{}", e);
+    Err(e.into())
+}
+
+#[cfg(target_os = "linux")]
+fn run_inhibitor(config: MouseMoverConfig, handle: &Handle, mut hooks: Hooks) -> RunResult {
+    let acquire = || {
+        Inhibitor::acquire("ktmm keep-awake requested").inspect_err(|e| {
+            eprintln!("{}", e);
+            eprintln!("Is a freedesktop screensaver or systemd-logind available? Try --mode mouse instead.");
+        })
+    };
+
+    let mut activation = Activation::from_config(&config).inspect_err(|e| eprintln!("{}", e))?;
+    let now = chrono::Local::now();
+    let mut inhibitor = if activation.is_active_at(&now) {
+        let inhibitor = acquire()?;
+        if config.verbosity != Verbosity::Quiet {
+            println!(
+                "KTMM is holding {}. Press Ctrl+C to exit.",
+                inhibitor.describe()
+            );
+        }
+        Some(inhibitor)
+    } else {
+        if config.verbosity != Verbosity::Quiet {
+            println!("KTMM is running. Press Ctrl+C to exit.");
+        }
+        print_schedule_change(&config, &mut activation, false, &now);
+        None
+    };
+
+    hooks.ready();
+    let deadline = config.deadline();
+    print_deadline(&config, deadline);
+
+    // Hold the inhibitors inside the scheduled hours, unless paused, until
+    // we are stopped
+    let mut state = handle.state();
+    let mut scheduled = inhibitor.is_some();
+    let mut retrying = false;
+    while !state.stopped && deadline.is_none_or(|deadline| Instant::now() < deadline) {
+        let recheck = activation
+            .recheck_in(&chrono::Local::now())
+            .map(|wait| Instant::now() + wait);
+        // Try again to take inhibitors that could not be taken last time
+        let retry = retrying.then(|| Instant::now() + ktmm::activation::RECHECK_INTERVAL);
+        let wake = [deadline, recheck, retry].into_iter().flatten().min();
+        let paused = state.paused;
+        state = handle.wait_for_change(state, wake);
+        if let Some(config) = handle.take_config() {
+            match Activation::from_config(&config) {
+                Ok(reloaded) => activation = reloaded,
+                Err(e) => {
+                    let event = MoverEvent::ReloadRejected {
+                        message: e.to_string(),
+                    };
+                    eprintln!("{}", event);
+                }
             }
         }
+
+        let now = chrono::Local::now();
+        let was_scheduled = scheduled;
+        scheduled = activation.is_active_at(&now);
+        for e in activation.take_errors() {
+            let event = MoverEvent::ActivationError {
+                message: e.to_string(),
+            };
+            eprintln!("{}", event);
+        }
+        if scheduled != was_scheduled {
+            print_schedule_change(&config, &mut activation, scheduled, &now);
+        }
+
+        match (scheduled && !state.paused, inhibitor.take()) {
+            (true, None) => match Inhibitor::acquire("ktmm keep-awake requested") {
+                Ok(acquired) => {
+                    if config.verbosity == Verbosity::Verbose {
+                        match paused {
+                            true => println!("Resumed, holding {}.", acquired.describe()),
+                            false => println!("Holding {}.", acquired.describe()),
+                        }
+                    }
+                    inhibitor = Some(acquired);
+                    retrying = false;
+                }
+                Err(e) => {
+                    // A restarting D-Bus service should not end the run
+                    if !retrying {
+                        eprintln!(
+                            "{}; trying again in {}.",
+                            e,
+                            format_duration(ktmm::activation::RECHECK_INTERVAL.as_secs())
+                        );
+                    }
+                    retrying = true;
+                }
+            },
+            (false, Some(held)) => {
+                if state.paused && !paused && config.verbosity == Verbosity::Verbose {
+                    println!("Paused, released {}.", held.describe());
+                }
+                held.release()?;
+            }
+            (false, None) => {
+                if state.paused != paused && config.verbosity == Verbosity::Verbose {
+                    match state.paused {
+                        true => println!("Paused."),
+                        false => println!("Resumed, outside scheduled hours."),
+                    }
+                }
+                retrying = false;
+            }
+            (true, held) => inhibitor = held,
+        }
+    }
+
+    if let Some(inhibitor) = inhibitor {
+        inhibitor.release()?;
+    }
+    Ok(finish(config.verbosity, state.stopped))
+}
+
+/// Tell verbose users that the schedule turned on or off at `now`
+#[cfg(target_os = "linux")]
+fn print_schedule_change(
+    config: &MouseMoverConfig,
+    activation: &mut Activation,
+    active: bool,
+    now: &chrono::DateTime<chrono::Local>,
+) {
+    if config.verbosity == Verbosity::Verbose {
+        let event = MoverEvent::ScheduleChanged {
+            active,
+            next_change: activation.next_change(now),
+        };
+        println!("{}", event);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn run_inhibitor(_config: MouseMoverConfig, _handle: &Handle, _hooks: Hooks) -> RunResult {
+    let e = KtmmError::Unsupported("the inhibit mode is only available on Linux".to_string());
+    eprintln!("{}", e);
+    Err(e.into())
+}
+
+/// Report how the run ended
+fn finish(verbosity: Verbosity, stopped: bool) -> RunEnd {
+    let end = if stopped {
+        RunEnd::Stopped
+    } else {
+        RunEnd::DeadlineReached
+    };
+    if verbosity != Verbosity::Quiet {
+        if end == RunEnd::DeadlineReached {
+            println!("Duration elapsed.");
+        }
+        println!("KTMM has been cleanly shut down.");
+    }
+    end
+}
+
+/// Tell verbose users when the run will end
+fn print_deadline(config: &MouseMoverConfig, deadline: Option<Instant>) {
+    if config.verbosity != Verbosity::Verbose {
+        return;
+    }
+    match (deadline, config.until) {
+        (Some(deadline), Some(until)) => {
+            println!("Stopping at {} ({} from now).", until, remaining(deadline))
+        }
+        (Some(deadline), None) => println!("Stopping in {}.", remaining(deadline)),
+        (None, _) => {}
+    }
+}
+
+/// Time left until `deadline`, e.g. `2h29m`
+fn remaining(deadline: Instant) -> String {
+    format_duration(deadline.saturating_duration_since(Instant::now()).as_secs())
+}
+
+fn resolve_config(cli: &Cli) -> MouseMoverConfig {
+    let config = match cli.to_config() {
+        Ok(config) => config,
+        Err(e) => Cli::command().error(ErrorKind::ValueValidation, e).exit(),
+    };
+    // Report an unreadable calendar now rather than once the run has started
+    if let Err(e) = Activation::from_config(&config) {
+        Cli::command().error(ErrorKind::ValueValidation, e).exit();
+    }
+    config
+}
+
+fn list_profiles(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let env: HashMap<String, String> = std::env::vars().collect();
+    let file = match config::find_config_file(cli.config.as_deref(), &env) {
+        Ok(file) => file,
+        Err(e) => Cli::command().error(ErrorKind::ValueValidation, e).exit(),
+    };
+    let Some(file) = file else {
+        match config::default_config_path(&env) {
+            Some(path) => println!("No config file found at {}", path.display()),
+            None => println!("No config file found"),
+        }
+        return Ok(());
+    };
+
+    if file.profiles.is_empty() {
+        println!("No profiles defined in {}", file.path.display());
+        return Ok(());
     }
 
-    println!("KTMM has been cleanly shut down.");
+    println!("Profiles in {}:", file.path.display());
+    for (name, profile) in &file.profiles {
+        println!();
+        match &profile.inherits {
+            Some(parent) => println!("[{}] (inherits {})", name, parent),
+            None => println!("[{}]", name),
+        }
+        match config::effective_config(Some(&file), Some(name), &env, cli) {
+            Ok(config) => print_config(&config),
+            Err(e) => println!("  {}", e),
+        }
+    }
     Ok(())
 }
 
-fn parse_args() -> MouseMoverConfig {
-    // For now, we'll just use the default configuration
-    // In a real implementation, we would use clap to parse command-line arguments
-    MouseMoverConfig::default()
+fn show_schedule(cli: &Cli, next: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let config = resolve_config(cli);
+    let mut activation = Activation::from_config(&config)?;
+    if activation.is_empty() {
+        println!("No schedule configured; KTMM keeps the system awake at all times.");
+        return Ok(());
+    }
+
+    println!("Schedule:");
+    for source in activation.describe() {
+        println!("  {}", source);
+    }
+    if let Some(calendar) = &config.calendar {
+        // Reading the calendar again is cheap and reports what was left out
+        if let Ok(text) = std::fs::read_to_string(calendar) {
+            for reason in ktmm::calendar::Calendar::parse(&text)
+                .map(|calendar| calendar.skipped().to_vec())
+                .unwrap_or_default()
+            {
+                println!("  skipped event {}", reason);
+            }
+        }
+    }
+    let now = chrono::Local::now();
+    if activation.is_active_at(&now) {
+        println!("Active now.");
+    } else {
+        println!("Inactive now.");
+    }
 
-    // When clap is properly set up, we would do:
-    /*
-    let args = Args::parse();
+    if next {
+        let format = |time: Option<chrono::DateTime<chrono::Local>>| match time {
+            Some(time) => time.format("%a %Y-%m-%d %H:%M %Z").to_string(),
+            None => "never".to_string(),
+        };
+        println!(
+            "Next activation:   {}",
+            format(activation.next_activation(&now))
+        );
+        println!(
+            "Next deactivation: {}",
+            format(activation.next_deactivation(&now))
+        );
+    }
+    Ok(())
+}
 
-    MouseMoverConfig {
-        interval_secs: args.interval,
-        movement_pixels: (args.x_movement, args.y_movement),
-        return_delay_ms: args.delay,
+fn print_config(config: &MouseMoverConfig) {
+    println!("  interval = {}", config.interval_secs);
+    if config.interval_jitter != IntervalJitter::Fixed {
+        println!("  interval_jitter = \"{}\"", config.interval_jitter);
+    }
+    println!("  dx = {}", config.movement_pixels.0);
+    println!("  dy = {}", config.movement_pixels.1);
+    if let Some(offset) = config.random_offset {
+        println!("  random_offset = \"{}\"", offset);
+    }
+    if let Some(seed) = config.seed {
+        println!("  seed = {}", seed);
+    }
+    println!("  pattern = \"{}\"", config.pattern);
+    println!("  nudge = \"{}\"", config.nudge);
+    match config.nudge {
+        NudgeKind::Key => println!("  nudge_key = \"{}\"", config.nudge_key),
+        NudgeKind::Scroll => println!("  scroll_axis = \"{}\"", config.scroll_axis),
+        NudgeKind::Pointer | NudgeKind::Synthetic => {}
+    }
+    println!("  delay = {}", config.return_delay_ms);
+    if let Some(duration) = config.duration_secs {
+        println!("  duration = {}", duration);
+    }
+    if let Some(until) = config.until {
+        println!("  until = \"{}\"", until);
+    }
+    if let Some(threshold) = config.idle_threshold_secs {
+        println!("  idle_threshold = {}", threshold);
     }
-    */
+    if !config.schedule.is_empty() {
+        let windows: Vec<String> = config
+            .schedule
+            .windows
+            .iter()
+            .map(|window| format!("\"{}\"", window))
+            .collect();
+        println!("  schedule = [{}]", windows.join(", "));
+    }
+    if !config.cron.is_empty() {
+        let exprs: Vec<String> = config
+            .cron
+            .iter()
+            .map(|expr| format!("\"{}\"", expr))
+            .collect();
+        println!("  cron = [{}]", exprs.join(", "));
+    }
+    if let Some(calendar) = &config.calendar {
+        println!("  calendar = \"{}\"", calendar.display());
+    }
+    if let Some(tag) = &config.calendar_tag {
+        println!("  calendar_tag = \"{}\"", tag);
+    }
+    println!("  verbosity = \"{}\"", config.verbosity);
+    println!("  mode = \"{}\"", config.mode);
+    println!("  backend = \"{}\"", config.backend);
 }
 
-fn setup_signal_handlers(running: Arc<AtomicBool>) {
-    // Use ctrlc crate for all platforms for simplicity
-    let r = running.clone();
+/// Control the run with signals
+///
+/// SIGINT and SIGTERM shut down cleanly, SIGUSR1 pauses or resumes, SIGUSR2
+/// nudges right away and SIGHUP reloads the configuration, passing it to
+/// `on_reload` as well. Signals are read on their own thread and reach the
+/// run loop through `handle`, which wakes it at once.
+#[cfg(unix)]
+fn setup_signal_handlers(
+    handle: Handle,
+    cli: &Cli,
+    config: &MouseMoverConfig,
+    on_reload: impl Fn(&MouseMoverConfig) + Send + 'static,
+) {
+    use signal_hook::consts::signal::{SIGHUP, SIGINT, SIGTERM, SIGUSR1, SIGUSR2};
+    use signal_hook::iterator::Signals;
+
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGUSR1, SIGUSR2, SIGHUP])
+        .expect("Error setting signal handlers");
+    let (cli, verbosity) = (cli.clone(), config.verbosity);
+    let (mode, backend) = (config.mode, config.backend);
+    let say = move |message: &str| {
+        if verbosity != Verbosity::Quiet {
+            println!("{}", message);
+        }
+    };
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            match signal {
+                SIGINT => {
+                    say("\nReceived Ctrl+C, shutting down...");
+                    handle.stop();
+                }
+                SIGTERM => {
+                    say("Received SIGTERM, shutting down...");
+                    handle.stop();
+                }
+                SIGUSR1 => match handle.toggle_pause() {
+                    true => say("Paused."),
+                    false => say("Resumed."),
+                },
+                SIGUSR2 => {
+                    say("Nudging now.");
+                    handle.nudge_now();
+                }
+                SIGHUP => match cli.to_config() {
+                    Ok(config) => {
+                        if config.mode != mode || config.backend != backend {
+                            eprintln!("The mode and backend only change after a restart.");
+                        }
+                        on_reload(&config);
+                        handle.reload(config);
+                        say("Reloaded the configuration.");
+                    }
+                    Err(e) => eprintln!("Not reloading the configuration: {}", e),
+                },
+                _ => {}
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+fn setup_signal_handlers(
+    handle: Handle,
+    _cli: &Cli,
+    config: &MouseMoverConfig,
+    _on_reload: impl Fn(&MouseMoverConfig) + Send + 'static,
+) {
+    let verbosity = config.verbosity;
     ctrlc::set_handler(move || {
-        println!("\nReceived Ctrl+C, shutting down...");
-        r.store(false, Ordering::SeqCst);
+        if verbosity != Verbosity::Quiet {
+            println!("\nReceived Ctrl+C, shutting down...");
+        }
+        handle.stop();
     })
     .expect("Error setting Ctrl+C handler");
 }
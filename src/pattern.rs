@@ -0,0 +1,399 @@
+//! Movement patterns traced by a nudge
+//!
+//! A [`MovementPattern`] turns a nudge into a sequence of timed
+//! [`Waypoint`]s relative to where the pointer started. Every pattern ends
+//! exactly where it began, so the pointer is left where the user put it. The
+//! built-in [`Pattern`]s are sized by the configured offset and spread over
+//! the return delay:
+//!
+//! - `line`: out by the offset and straight back, the classic nudge
+//! - `circle`: a small circle with the offset as its diameter
+//! - `square`: round the square spanned by the offset
+//! - `bezier`: out along a smooth arc and back along its mirror image, slow at
+//!   both ends of each arc like a hand would move
+//! - `zigzag`: a zig-zag towards the offset and back
+
+use serde::Deserialize;
+use std::f64::consts::TAU;
+use std::fmt;
+use std::time::Duration;
+
+/// Points on the circle
+const CIRCLE_STEPS: usize = 12;
+/// Points on each of the two arcs of a Bezier loop
+const BEZIER_STEPS: usize = 8;
+/// Turns on the way out of a zig-zag
+const ZIGZAG_TEETH: usize = 4;
+
+/// Where the pointer should be, relative to its starting position, at a
+/// given time after the movement began
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Waypoint {
+    pub offset: (i32, i32),
+    pub at: Duration,
+}
+
+/// A path for the pointer to follow on each nudge
+pub trait MovementPattern {
+    /// The points to move through in order
+    ///
+    /// The times never decrease and the last waypoint is always at offset
+    /// `(0, 0)`, back where the pointer started.
+    fn waypoints(&self) -> Vec<Waypoint>;
+}
+
+/// There and back in a straight line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Line {
+    pub delta: (i32, i32),
+    pub duration: Duration,
+}
+
+impl MovementPattern for Line {
+    fn waypoints(&self) -> Vec<Waypoint> {
+        let (dx, dy) = self.delta;
+        trace(&[(dx as f64, dy as f64)], self.duration)
+    }
+}
+
+/// A circle through the starting point with `diameter` across it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Circle {
+    pub diameter: (i32, i32),
+    pub duration: Duration,
+}
+
+impl MovementPattern for Circle {
+    fn waypoints(&self) -> Vec<Waypoint> {
+        let (dx, dy) = scale_to_at_least(self.diameter, 2.0);
+        let centre = (dx / 2.0, dy / 2.0);
+        let radius = centre.0.hypot(centre.1);
+        let start = (-centre.1).atan2(-centre.0);
+        let points: Vec<(f64, f64)> = (1..CIRCLE_STEPS)
+            .map(|step| {
+                let angle = start + TAU * step as f64 / CIRCLE_STEPS as f64;
+                (
+                    centre.0 + radius * angle.cos(),
+                    centre.1 + radius * angle.sin(),
+                )
+            })
+            .collect();
+        trace(&points, self.duration)
+    }
+}
+
+/// Round the square with opposite corners at the start and `corner`
+///
+/// A corner on one of the axes is turned into a square with that side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Square {
+    pub corner: (i32, i32),
+    pub duration: Duration,
+}
+
+impl MovementPattern for Square {
+    fn waypoints(&self) -> Vec<Waypoint> {
+        let (width, height) = match self.corner {
+            (0, dy) => (dy, dy),
+            (dx, 0) => (dx, dx),
+            corner => corner,
+        };
+        let (width, height) = (width as f64, height as f64);
+        trace(
+            &[(width, 0.0), (width, height), (0.0, height)],
+            self.duration,
+        )
+    }
+}
+
+/// Out to `delta` along a cubic Bezier arc and back along its mirror image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bezier {
+    pub delta: (i32, i32),
+    pub duration: Duration,
+}
+
+impl MovementPattern for Bezier {
+    fn waypoints(&self) -> Vec<Waypoint> {
+        let (dx, dy) = scale_to_at_least(self.delta, 2.0);
+        // Bulge to one side by half the length on the way out, the other on the way back
+        let bulge = (-dy / 2.0, dx / 2.0);
+        let arc = |from: (f64, f64), to: (f64, f64), side: f64| {
+            let control = |fraction: f64| {
+                (
+                    from.0 + (to.0 - from.0) * fraction + bulge.0 * side,
+                    from.1 + (to.1 - from.1) * fraction + bulge.1 * side,
+                )
+            };
+            let (p1, p2) = (control(1.0 / 3.0), control(2.0 / 3.0));
+            (1..=BEZIER_STEPS).map(move |step| {
+                // Equal steps in time, eased along the curve
+                let t = ease_in_out(step as f64 / BEZIER_STEPS as f64);
+                let u = 1.0 - t;
+                let mix = |a: f64, b: f64, c: f64, d: f64| {
+                    u * u * u * a + 3.0 * u * u * t * b + 3.0 * u * t * t * c + t * t * t * d
+                };
+                (mix(from.0, p1.0, p2.0, to.0), mix(from.1, p1.1, p2.1, to.1))
+            })
+        };
+        let mut points: Vec<(f64, f64)> = arc((0.0, 0.0), (dx, dy), 1.0).collect();
+        points.extend(arc((dx, dy), (0.0, 0.0), -1.0));
+        points.pop();
+        trace(&points, self.duration)
+    }
+}
+
+/// A zig-zag out to `delta` and back the same way
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Zigzag {
+    pub delta: (i32, i32),
+    pub duration: Duration,
+}
+
+impl MovementPattern for Zigzag {
+    fn waypoints(&self) -> Vec<Waypoint> {
+        let (dx, dy) = scale_to_at_least(self.delta, ZIGZAG_TEETH as f64);
+        // Swing a quarter of the length to either side, but at least a pixel
+        let length = dx.hypot(dy);
+        let swing = (length / 4.0).max(1.0) / length;
+        let side = (-dy * swing, dx * swing);
+        let out: Vec<(f64, f64)> = (1..=ZIGZAG_TEETH)
+            .map(|tooth| {
+                let along = tooth as f64 / ZIGZAG_TEETH as f64;
+                let sign = match tooth {
+                    _ if tooth == ZIGZAG_TEETH => 0.0,
+                    _ if tooth % 2 == 1 => 1.0,
+                    _ => -1.0,
+                };
+                (dx * along + side.0 * sign, dy * along + side.1 * sign)
+            })
+            .collect();
+        let mut points = out.clone();
+        points.extend(out.iter().rev().skip(1));
+        trace(&points, self.duration)
+    }
+}
+
+/// Which built-in pattern a nudge traces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pattern {
+    #[default]
+    Line,
+    Circle,
+    Square,
+    Bezier,
+    Zigzag,
+}
+
+impl Pattern {
+    /// The pattern sized by `delta` and taking `duration` from start to end
+    pub fn build(self, delta: (i32, i32), duration: Duration) -> Box<dyn MovementPattern> {
+        match self {
+            Pattern::Line => Box::new(Line { delta, duration }),
+            Pattern::Circle => Box::new(Circle {
+                diameter: delta,
+                duration,
+            }),
+            Pattern::Square => Box::new(Square {
+                corner: delta,
+                duration,
+            }),
+            Pattern::Bezier => Box::new(Bezier { delta, duration }),
+            Pattern::Zigzag => Box::new(Zigzag { delta, duration }),
+        }
+    }
+}
+
+impl std::str::FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "line" => Ok(Pattern::Line),
+            "circle" => Ok(Pattern::Circle),
+            "square" => Ok(Pattern::Square),
+            "bezier" => Ok(Pattern::Bezier),
+            "zigzag" => Ok(Pattern::Zigzag),
+            _ => Err("expected `line`, `circle`, `square`, `bezier` or `zigzag`".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Line => write!(f, "line"),
+            Pattern::Circle => write!(f, "circle"),
+            Pattern::Square => write!(f, "square"),
+            Pattern::Bezier => write!(f, "bezier"),
+            Pattern::Zigzag => write!(f, "zigzag"),
+        }
+    }
+}
+
+/// Turn the points visited after the start into waypoints ending back at
+/// the start
+///
+/// The first point is reached immediately and the return to the start at
+/// `duration`, with the points in between evenly spread out. Points that
+/// round to the same pixel as the next one are dropped.
+fn trace(points: &[(f64, f64)], duration: Duration) -> Vec<Waypoint> {
+    let steps = points.len();
+    let mut waypoints: Vec<Waypoint> = points
+        .iter()
+        .map(|&(x, y)| (x.round() as i32, y.round() as i32))
+        .chain([(0, 0)])
+        .enumerate()
+        .map(|(step, offset)| Waypoint {
+            offset,
+            at: duration.mul_f64(step as f64 / steps.max(1) as f64),
+        })
+        .collect();
+    waypoints.dedup_by(|later, earlier| {
+        if later.offset == earlier.offset {
+            earlier.at = later.at;
+            true
+        } else {
+            false
+        }
+    });
+    waypoints
+}
+
+/// Slow at both ends and fastest in the middle
+fn ease_in_out(t: f64) -> f64 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// `delta` as floats, stretched if needed to be at least `min` pixels long
+/// so that small offsets still trace a recognisable shape
+fn scale_to_at_least(delta: (i32, i32), min: f64) -> (f64, f64) {
+    let (dx, dy) = (delta.0 as f64, delta.1 as f64);
+    let length = dx.hypot(dy);
+    if length == 0.0 || length >= min {
+        (dx, dy)
+    } else {
+        (dx * min / length, dy * min / length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Pattern; 5] = [
+        Pattern::Line,
+        Pattern::Circle,
+        Pattern::Square,
+        Pattern::Bezier,
+        Pattern::Zigzag,
+    ];
+
+    #[test]
+    fn test_every_pattern_returns_to_the_start() {
+        let duration = Duration::from_millis(120);
+        for pattern in ALL {
+            for delta in [(1, 1), (3, -4), (0, 20), (-12, 5)] {
+                let waypoints = pattern.build(delta, duration).waypoints();
+                let last = waypoints.last().unwrap();
+                assert_eq!(last.offset, (0, 0), "{} {:?}", pattern, delta);
+                assert_eq!(last.at, duration, "{} {:?}", pattern, delta);
+                assert!(waypoints.len() >= 2, "{} {:?}", pattern, delta);
+                assert!(waypoints.windows(2).all(|pair| pair[0].at <= pair[1].at));
+                assert!(waypoints
+                    .windows(2)
+                    .all(|pair| pair[0].offset != pair[1].offset));
+            }
+        }
+    }
+
+    #[test]
+    fn test_line_is_there_and_back() {
+        let line = Line {
+            delta: (3, -4),
+            duration: Duration::from_millis(6),
+        };
+        assert_eq!(
+            line.waypoints(),
+            [
+                Waypoint {
+                    offset: (3, -4),
+                    at: Duration::ZERO
+                },
+                Waypoint {
+                    offset: (0, 0),
+                    at: Duration::from_millis(6)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shapes_stay_within_their_size() {
+        let duration = Duration::from_millis(100);
+        let circle = Circle {
+            diameter: (20, 0),
+            duration,
+        };
+        for waypoint in circle.waypoints() {
+            let (x, y) = waypoint.offset;
+            let distance = ((x - 10) as f64).hypot(y as f64);
+            assert!((9.0..=11.0).contains(&distance), "{:?}", waypoint);
+        }
+
+        let square = Square {
+            corner: (5, 0),
+            duration,
+        };
+        let corners: Vec<_> = square.waypoints().iter().map(|w| w.offset).collect();
+        assert_eq!(corners, [(5, 0), (5, 5), (0, 5), (0, 0)]);
+
+        let zigzag = Zigzag {
+            delta: (8, 0),
+            duration,
+        };
+        let offsets: Vec<_> = zigzag.waypoints().iter().map(|w| w.offset).collect();
+        assert_eq!(
+            offsets,
+            [
+                (2, 2),
+                (4, -2),
+                (6, 2),
+                (8, 0),
+                (6, 2),
+                (4, -2),
+                (2, 2),
+                (0, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bezier_timing_is_eased() {
+        let bezier = Bezier {
+            delta: (40, 0),
+            duration: Duration::from_millis(160),
+        };
+        let waypoints = bezier.waypoints();
+        // Reaches the far end half way through, and bulges sideways on both arcs
+        let turn = waypoints.iter().find(|w| w.offset == (40, 0)).unwrap();
+        assert!(turn.at > Duration::from_millis(60) && turn.at < Duration::from_millis(90));
+        assert!(waypoints.iter().any(|w| w.offset.1 > 5));
+        assert!(waypoints.iter().any(|w| w.offset.1 < -5));
+        // Steps near the ends of an arc are shorter than in its middle
+        let step = |i: usize| {
+            let (a, b) = (waypoints[i].offset, waypoints[i + 1].offset);
+            ((b.0 - a.0) as f64).hypot((b.1 - a.1) as f64)
+        };
+        assert!(step(0) < step(3));
+    }
+
+    #[test]
+    fn test_parse_pattern() {
+        for pattern in ALL {
+            assert_eq!(pattern.to_string().parse(), Ok(pattern));
+        }
+        assert!("spiral".parse::<Pattern>().is_err());
+    }
+}
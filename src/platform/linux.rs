@@ -1,5 +1,11 @@
 //! Linux-specific functionality for KTMM
 
+use std::ptr;
+use std::time::Duration;
+
+use x11::{xinerama, xlib, xss};
+
+use crate::geometry::Rect;
 use crate::KtmmError;
 
 /// Check if the application has the necessary permissions on Linux
@@ -12,9 +18,11 @@ pub fn check_accessibility_permissions() -> Result<(), KtmmError> {
     {
         // Check if we're running under Wayland
         if std::env::var("WAYLAND_DISPLAY").is_ok() {
-            // Wayland might restrict mouse control
+            // Wayland does not let clients move the pointer
             return Err(KtmmError::PlatformError(
-                "Running under Wayland, which may restrict mouse control".to_string(),
+                "Running under Wayland, which does not allow native mouse control; \
+                use --backend uinput instead"
+                    .to_string(),
             ));
         }
     }
@@ -32,11 +40,117 @@ pub fn get_accessibility_guidance() -> String {
         - If using a security-enhanced setup, check X11 access controls\n\
         \n\
         For Wayland:\n\
-        - Mouse control may be restricted by design\n\
-        - Consider using X11 instead for this application\n\
-        - Some compositors may provide accessibility APIs\n\
+        - Compositors do not allow moving the pointer directly\n\
+        - Use the uinput backend (--backend uinput), which needs write access to /dev/uinput\n\
+        - Or keep the session awake with D-Bus inhibitors (--mode inhibit)\n\
         \n\
         If running in a virtual machine or remote desktop:\n\
         - Ensure the host system allows mouse control",
     )
 }
+
+/// A connection to the X server, closed again when dropped
+///
+/// The connection is opened once and reused for every query, instead of
+/// connecting to the server each time the mover looks at the idle counter or
+/// the monitors.
+pub struct XDisplay {
+    display: *mut xlib::Display,
+}
+
+// Xlib connections may move between threads as long as only one thread uses
+// them at a time, which `&mut self` on the owner guarantees
+unsafe impl Send for XDisplay {}
+
+impl XDisplay {
+    /// Connect to the X display
+    ///
+    /// Returns `None` without an X display and under Wayland, where X11 only
+    /// sees the XWayland clients.
+    pub fn open() -> Option<Self> {
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() || std::env::var_os("DISPLAY").is_none() {
+            return None;
+        }
+
+        let display = unsafe { xlib::XOpenDisplay(ptr::null()) };
+        (!display.is_null()).then_some(Self { display })
+    }
+
+    /// Query the MIT-SCREEN-SAVER extension for the time since the last input
+    pub fn idle_time(&self) -> Option<Duration> {
+        let display = self.display;
+        unsafe {
+            let mut event_base = 0;
+            let mut error_base = 0;
+            if xss::XScreenSaverQueryExtension(display, &mut event_base, &mut error_base) == 0 {
+                return None;
+            }
+            let info = xss::XScreenSaverAllocInfo();
+            if info.is_null() {
+                return None;
+            }
+            let root = xlib::XDefaultRootWindow(display);
+            let idle = if xss::XScreenSaverQueryInfo(display, root, info) != 0 {
+                Some(Duration::from_millis((*info).idle as u64))
+            } else {
+                None
+            };
+            xlib::XFree(info.cast());
+            idle
+        }
+    }
+
+    /// Reset the server's idle timer without generating any visible input
+    ///
+    /// Returns the name of the mechanism used.
+    pub fn reset_idle_timer(&self) -> &'static str {
+        unsafe {
+            // Resets both the screen saver and the idle counter it is driven by
+            xlib::XResetScreenSaver(self.display);
+            xlib::XFlush(self.display);
+        }
+        "XResetScreenSaver"
+    }
+
+    /// List the monitors through Xinerama, or the whole screen without it
+    pub fn monitors(&self) -> Vec<Rect> {
+        let display = self.display;
+        unsafe {
+            if xinerama::XineramaIsActive(display) != 0 {
+                let mut count = 0;
+                let screens = xinerama::XineramaQueryScreens(display, &mut count);
+                if !screens.is_null() {
+                    let monitors = std::slice::from_raw_parts(screens, count.max(0) as usize)
+                        .iter()
+                        .map(|screen| {
+                            Rect::new(
+                                screen.x_org.into(),
+                                screen.y_org.into(),
+                                screen.width.into(),
+                                screen.height.into(),
+                            )
+                        })
+                        .collect();
+                    xlib::XFree(screens.cast());
+                    return monitors;
+                }
+            }
+
+            let screen = xlib::XDefaultScreen(display);
+            vec![Rect::new(
+                0,
+                0,
+                xlib::XDisplayWidth(display, screen),
+                xlib::XDisplayHeight(display, screen),
+            )]
+        }
+    }
+}
+
+impl Drop for XDisplay {
+    fn drop(&mut self) {
+        unsafe {
+            xlib::XCloseDisplay(self.display);
+        }
+    }
+}
@@ -12,6 +12,9 @@ pub mod linux;
 #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
 pub mod unsupported;
 
+use std::time::Duration;
+
+use crate::geometry::Rect;
 use crate::KtmmError;
 
 /// Check if the application has the necessary permissions to control the mouse
@@ -43,3 +46,54 @@ pub fn get_accessibility_guidance() -> String {
     #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
     return unsupported::get_accessibility_guidance();
 }
+
+/// A connection to the display server, opened on first use and kept open
+/// for later queries
+#[derive(Default)]
+pub struct Display {
+    #[cfg(target_os = "linux")]
+    x: Option<linux::XDisplay>,
+}
+
+impl Display {
+    /// A display that connects when it is first queried
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[cfg(target_os = "linux")]
+    fn x(&mut self) -> Option<&linux::XDisplay> {
+        if self.x.is_none() {
+            self.x = linux::XDisplay::open();
+        }
+        self.x.as_ref()
+    }
+
+    /// Time since the last user input, if the platform exposes an idle counter
+    pub fn idle_time(&mut self) -> Option<Duration> {
+        #[cfg(target_os = "linux")]
+        return self.x()?.idle_time();
+
+        #[cfg(not(target_os = "linux"))]
+        return None;
+    }
+
+    /// Reset the system idle timer without moving the pointer, if the
+    /// platform allows it; returns the name of the mechanism used
+    pub fn reset_idle_timer(&mut self) -> Option<&'static str> {
+        #[cfg(target_os = "linux")]
+        return self.x().map(|x| x.reset_idle_timer());
+
+        #[cfg(not(target_os = "linux"))]
+        return None;
+    }
+
+    /// The monitors' areas in screen coordinates, if the platform can list them
+    pub fn monitors(&mut self) -> Option<Vec<Rect>> {
+        #[cfg(target_os = "linux")]
+        return self.x().map(|x| x.monitors());
+
+        #[cfg(not(target_os = "linux"))]
+        return None;
+    }
+}
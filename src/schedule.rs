@@ -0,0 +1,382 @@
+//! Weekly windows in which KTMM keeps the system awake
+//!
+//! A schedule is a list of windows such as `Mon-Fri 09:00-18:00`, each giving
+//! the days it starts on and a local start and end time. A window whose end
+//! is not after its start runs past midnight into the next day, so
+//! `Fri 22:00-02:00` ends early on Saturday. An empty schedule is always
+//! active.
+//!
+//! Times are local wall-clock times: across a DST change a window still
+//! starts and ends when the clock shows the given times, and times skipped
+//! or repeated by the change are resolved as described for
+//! [`resolve_local`].
+
+use chrono::{DateTime, Datelike, Days, NaiveDate, TimeZone, Weekday};
+use serde::Deserialize;
+use std::fmt;
+
+use crate::activation::next_transition;
+use crate::timespec::{resolve_local, TimeOfDay};
+
+/// How many days around the current one are searched for window boundaries;
+/// long enough to leave one weekly window and reach the next
+const SEARCH_DAYS: u64 = 15;
+
+/// One weekly window, e.g. `Mon-Fri 09:00-18:00`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Window {
+    /// The days the window starts on, indexed from Monday
+    days: [bool; 7],
+    /// When the window starts
+    pub start: TimeOfDay,
+    /// When the window ends, on the following day if not after `start`
+    pub end: TimeOfDay,
+}
+
+impl Window {
+    /// A window starting on each of `days` at `start` and ending at `end`
+    pub fn new(days: &[Weekday], start: TimeOfDay, end: TimeOfDay) -> Self {
+        let mut set = [false; 7];
+        for day in days {
+            set[day.num_days_from_monday() as usize] = true;
+        }
+        Self {
+            days: set,
+            start,
+            end,
+        }
+    }
+
+    /// Whether the window starts on `day`
+    pub fn starts_on(&self, day: Weekday) -> bool {
+        self.days[day.num_days_from_monday() as usize]
+    }
+
+    /// The instants at which the window starting on `date` opens and closes
+    fn span_on<Tz: TimeZone>(
+        &self,
+        tz: &Tz,
+        date: NaiveDate,
+    ) -> Option<(DateTime<Tz>, DateTime<Tz>)> {
+        if !self.starts_on(date.weekday()) {
+            return None;
+        }
+        let end_date = if self.end > self.start {
+            date
+        } else {
+            date.checked_add_days(Days::new(1))?
+        };
+        let start = resolve_local(tz, date.and_time(self.start.0))?;
+        let end = resolve_local(tz, end_date.and_time(self.end.0))?;
+        Some((start, end))
+    }
+}
+
+impl std::str::FromStr for Window {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (days, times) = match s.rsplit_once(char::is_whitespace) {
+            Some((days, times)) => (parse_days(days.trim())?, times),
+            None => ([true; 7], s),
+        };
+        let (start, end) = times.split_once('-').ok_or_else(|| {
+            format!(
+                "invalid window `{}`: expected days and times, e.g. `Mon-Fri 09:00-18:00`",
+                s
+            )
+        })?;
+        Ok(Self {
+            days,
+            start: start.parse()?,
+            end: end.parse()?,
+        })
+    }
+}
+
+impl TryFrom<String> for Window {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}-{}", format_days(&self.days), self.start, self.end)
+    }
+}
+
+/// Parse a day list such as `Mon-Fri`, `Sat,Sun`, `Fri-Mon` or `daily`
+fn parse_days(text: &str) -> Result<[bool; 7], String> {
+    if text.eq_ignore_ascii_case("daily") || text == "*" {
+        return Ok([true; 7]);
+    }
+    let day = |name: &str| {
+        name.trim().parse::<Weekday>().map_err(|_| {
+            format!(
+                "invalid day `{}`: expected e.g. Mon, Tue or Monday",
+                name.trim()
+            )
+        })
+    };
+    let mut days = [false; 7];
+    for part in text.split(',') {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (day(first)?, day(last)?),
+            None => (day(part)?, day(part)?),
+        };
+        // Ranges may wrap around the end of the week
+        let mut current = first;
+        loop {
+            days[current.num_days_from_monday() as usize] = true;
+            if current == last {
+                break;
+            }
+            current = current.succ();
+        }
+    }
+    Ok(days)
+}
+
+/// Format a day set compactly, e.g. `Mon-Fri` or `Mon,Wed-Thu`
+fn format_days(days: &[bool; 7]) -> String {
+    if days.iter().all(|&day| day) {
+        return "daily".to_string();
+    }
+    let mut runs = Vec::new();
+    let mut i = 0;
+    while i < 7 {
+        if !days[i] {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i + 1 < 7 && days[i + 1] {
+            i += 1;
+        }
+        let name = |index: usize| {
+            Weekday::try_from(index as u8)
+                .expect("day index")
+                .to_string()
+        };
+        runs.push(if start == i {
+            name(start)
+        } else {
+            format!("{}-{}", name(start), name(i))
+        });
+        i += 1;
+    }
+    runs.join(",")
+}
+
+/// A set of weekly windows; active whenever any window is
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schedule {
+    pub windows: Vec<Window>,
+}
+
+impl Schedule {
+    /// A schedule made of the given windows
+    pub fn new(windows: Vec<Window>) -> Self {
+        Self { windows }
+    }
+
+    /// Whether the schedule has no windows, and so is always active
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// Whether KTMM should keep the system awake at `now`
+    pub fn is_active_at<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> bool {
+        self.is_empty()
+            || self
+                .spans(now)
+                .iter()
+                .any(|(start, end)| start <= now && now < end)
+    }
+
+    /// The next time after `now` at which the schedule becomes active
+    ///
+    /// `None` for a schedule that never changes.
+    pub fn next_activation<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        self.next_transition(now, true)
+    }
+
+    /// The next time after `now` at which the schedule becomes inactive
+    ///
+    /// `None` for a schedule that never changes.
+    pub fn next_deactivation<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        self.next_transition(now, false)
+    }
+
+    /// The next time after `now` at which the schedule turns on or off
+    pub fn next_change<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let active = self.is_active_at(now);
+        self.next_transition(now, !active)
+    }
+
+    fn next_transition<Tz: TimeZone>(
+        &self,
+        now: &DateTime<Tz>,
+        to_active: bool,
+    ) -> Option<DateTime<Tz>> {
+        if self.is_empty() {
+            return None;
+        }
+        next_transition(&self.spans(now), now, to_active)
+    }
+
+    /// Every window span that could contain or follow `now` within the search range
+    fn spans<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> Vec<(DateTime<Tz>, DateTime<Tz>)> {
+        let tz = now.timezone();
+        // Start a day early for windows running past midnight into today
+        let Some(first) = now.date_naive().checked_sub_days(Days::new(1)) else {
+            return Vec::new();
+        };
+        let mut spans = Vec::new();
+        for date in first.iter_days().take(SEARCH_DAYS as usize + 1) {
+            spans.extend(
+                self.windows
+                    .iter()
+                    .filter_map(|window| window.span_on(&tz, date)),
+            );
+        }
+        spans
+    }
+}
+
+impl std::str::FromStr for Schedule {
+    type Err = String;
+
+    /// Parse windows separated by `;`, e.g. `Mon-Fri 09:00-18:00; Sat 10:00-12:00`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(';')
+            .filter(|window| !window.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map(Self::new)
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let windows: Vec<String> = self.windows.iter().map(Window::to_string).collect();
+        write!(f, "{}", windows.join("; "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+    use chrono_tz::Europe::Berlin;
+
+    fn schedule(text: &str) -> Schedule {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_format_windows() {
+        let window: Window = "Mon-Fri 09:00-18:00".parse().unwrap();
+        assert!(window.starts_on(Weekday::Mon) && window.starts_on(Weekday::Fri));
+        assert!(!window.starts_on(Weekday::Sat));
+        assert_eq!(window.to_string(), "Mon-Fri 09:00-18:00");
+
+        let wrapping: Window = "fri-mon 22:00-02:00".parse().unwrap();
+        assert_eq!(wrapping.to_string(), "Mon,Fri-Sun 22:00-02:00");
+        assert_eq!(
+            "08:00-12:00".parse::<Window>().unwrap().to_string(),
+            "daily 08:00-12:00"
+        );
+        assert!("Mon-Fri".parse::<Window>().is_err());
+        assert!("Funday 09:00-10:00".parse::<Window>().is_err());
+        assert!("Mon 09:00-25:00".parse::<Window>().is_err());
+    }
+
+    #[test]
+    fn test_weekday_window() {
+        let tz = FixedOffset::east_opt(0).unwrap();
+        let hours = schedule("Mon-Fri 09:00-18:00");
+        // 2024-05-10 is a Friday
+        let friday_noon = tz.with_ymd_and_hms(2024, 5, 10, 12, 0, 0).unwrap();
+        let friday_evening = tz.with_ymd_and_hms(2024, 5, 10, 18, 0, 0).unwrap();
+        let saturday = tz.with_ymd_and_hms(2024, 5, 11, 12, 0, 0).unwrap();
+
+        assert!(hours.is_active_at(&friday_noon));
+        assert!(!hours.is_active_at(&friday_evening));
+        assert!(!hours.is_active_at(&saturday));
+        assert_eq!(hours.next_deactivation(&friday_noon), Some(friday_evening));
+        assert_eq!(
+            hours.next_activation(&friday_noon),
+            tz.with_ymd_and_hms(2024, 5, 13, 9, 0, 0).single()
+        );
+        assert_eq!(
+            hours.next_change(&saturday),
+            hours.next_activation(&saturday)
+        );
+    }
+
+    #[test]
+    fn test_window_crossing_midnight() {
+        let tz = FixedOffset::east_opt(0).unwrap();
+        let nights = schedule("Fri 22:00-02:00");
+        let saturday_early = tz.with_ymd_and_hms(2024, 5, 11, 1, 0, 0).unwrap();
+
+        assert!(nights.is_active_at(&saturday_early));
+        assert!(!nights.is_active_at(&tz.with_ymd_and_hms(2024, 5, 11, 3, 0, 0).unwrap()));
+        assert_eq!(
+            nights.next_deactivation(&saturday_early),
+            tz.with_ymd_and_hms(2024, 5, 11, 2, 0, 0).single()
+        );
+        assert_eq!(
+            nights.next_activation(&saturday_early),
+            tz.with_ymd_and_hms(2024, 5, 17, 22, 0, 0).single()
+        );
+    }
+
+    #[test]
+    fn test_overlapping_and_adjacent_windows_merge() {
+        let tz = FixedOffset::east_opt(0).unwrap();
+        let hours = schedule("Mon 09:00-12:00; Mon 11:00-13:00; Mon 13:00-14:00");
+        // 2024-05-13 is a Monday
+        let morning = tz.with_ymd_and_hms(2024, 5, 13, 10, 0, 0).unwrap();
+        assert_eq!(
+            hours.next_deactivation(&morning),
+            tz.with_ymd_and_hms(2024, 5, 13, 14, 0, 0).single()
+        );
+        assert_eq!(Schedule::default().next_change(&morning), None);
+        assert!(Schedule::default().is_active_at(&morning));
+    }
+
+    #[test]
+    fn test_windows_follow_the_clock_across_dst() {
+        let hours = schedule("daily 09:00-18:00");
+        // Clocks go forward in Berlin on 2024-03-31 at 02:00
+        let before = Berlin.with_ymd_and_hms(2024, 3, 30, 20, 0, 0).unwrap();
+        let start = hours.next_activation(&before).unwrap();
+        assert_eq!(
+            start,
+            Berlin.with_ymd_and_hms(2024, 3, 31, 9, 0, 0).unwrap()
+        );
+        assert_eq!((start - before).num_hours(), 12);
+
+        // A window starting in the skipped hour opens when the clock reaches 03:30
+        let night = schedule("Sun 02:30-04:00");
+        let start = night.next_activation(&before).unwrap();
+        assert_eq!(
+            start,
+            Berlin.with_ymd_and_hms(2024, 3, 31, 3, 30, 0).unwrap()
+        );
+
+        // Clocks go back on 2024-10-27 at 03:00; the repeated hour opens at its first occurrence
+        let night = schedule("Sun 02:30-03:30");
+        let before = Berlin.with_ymd_and_hms(2024, 10, 26, 20, 0, 0).unwrap();
+        let start = night.next_activation(&before).unwrap();
+        let end = night.next_deactivation(&start).unwrap();
+        assert_eq!((end - start).num_minutes(), 120);
+    }
+}
@@ -0,0 +1,217 @@
+//! Running as a systemd user service (Linux)
+//!
+//! `ktmm install-service` writes a `Type=notify` user unit that runs
+//! `ktmm daemon`. The daemon then reports to systemd over the socket in
+//! `$NOTIFY_SOCKET` with the `sd_notify` protocol: one datagram of
+//! newline-separated `KEY=VALUE` assignments per notification. It sends
+//! `READY=1` once it is listening, `STATUS=` lines saying when it last nudged,
+//! `STOPPING=1` when it shuts down and, if the unit sets `WatchdogSec=`,
+//! regular `WATCHDOG=1` pings so systemd restarts it if it hangs.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::KtmmError;
+
+/// The watchdog timeout written to installed units
+pub const WATCHDOG_SECS: u64 = 60;
+
+/// Sends `sd_notify` messages to the service manager
+#[derive(Debug)]
+pub struct Notifier {
+    socket: UnixDatagram,
+    address: SocketAddr,
+}
+
+impl Notifier {
+    /// Connect to the socket named by `$NOTIFY_SOCKET`, if the process was
+    /// started by a service manager that set it
+    pub fn from_env(env: &HashMap<String, String>) -> Result<Option<Self>, KtmmError> {
+        match env.get("NOTIFY_SOCKET").filter(|path| !path.is_empty()) {
+            Some(path) => Self::new(path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Notify the socket at `path`; a leading `@` names an abstract socket
+    pub fn new(path: &str) -> Result<Self, KtmmError> {
+        let error = |e: io::Error| {
+            KtmmError::PlatformError(format!("cannot use NOTIFY_SOCKET {}: {}", path, e))
+        };
+        let address = match path.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract_name(name),
+            None => SocketAddr::from_pathname(path),
+        }
+        .map_err(error)?;
+        let socket = UnixDatagram::unbound().map_err(error)?;
+        Ok(Self { socket, address })
+    }
+
+    /// Send one notification made of `KEY=VALUE` lines
+    pub fn notify(&self, state: &str) -> Result<(), KtmmError> {
+        self.socket
+            .send_to_addr(state.as_bytes(), &self.address)
+            .map(|_| ())
+            .map_err(|e| KtmmError::PlatformError(format!("cannot notify systemd: {}", e)))
+    }
+
+    /// Report that start-up has finished, with a first status line
+    pub fn ready(&self, status: &str) -> Result<(), KtmmError> {
+        self.notify(&format!("READY=1\nSTATUS={}", single_line(status)))
+    }
+
+    /// Replace the status line shown by `systemctl status`
+    pub fn status(&self, status: &str) -> Result<(), KtmmError> {
+        self.notify(&format!("STATUS={}", single_line(status)))
+    }
+
+    /// Report that the service is shutting down
+    pub fn stopping(&self) -> Result<(), KtmmError> {
+        self.notify("STOPPING=1")
+    }
+
+    /// Tell the watchdog that the service is still alive
+    pub fn watchdog(&self) -> Result<(), KtmmError> {
+        self.notify("WATCHDOG=1")
+    }
+}
+
+/// Newlines would start a new assignment
+fn single_line(text: &str) -> String {
+    text.replace('\n', " ")
+}
+
+/// How often to ping the watchdog, if systemd expects it of this process
+///
+/// This is half of `$WATCHDOG_USEC`, as systemd recommends, and only applies
+/// when `$WATCHDOG_PID` is unset or names this process.
+pub fn watchdog_interval(env: &HashMap<String, String>) -> Option<Duration> {
+    if let Some(pid) = env.get("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+    let usec: u64 = env.get("WATCHDOG_USEC")?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
+
+/// Where `ktmm install-service` writes the unit: the user unit directory
+/// under `$XDG_CONFIG_HOME`, or `~/.config`
+pub fn unit_path(env: &HashMap<String, String>) -> Option<PathBuf> {
+    let non_empty = |name: &str| {
+        env.get(name)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    };
+    let base = non_empty("XDG_CONFIG_HOME")
+        .or_else(|| non_empty("HOME").map(|home| home.join(".config")))?;
+    Some(base.join("systemd").join("user").join("ktmm.service"))
+}
+
+/// The unit file that starts `exec_start` as a notify-type user service
+pub fn unit_file(exec_start: &[String]) -> String {
+    let command: Vec<String> = exec_start.iter().map(|arg| quote(arg)).collect();
+    format!(
+        "[Unit]\n\
+         Description=Keep That Mouse Moving\n\
+         Documentation=https://github.com/ao/ktmm\n\
+         PartOf=graphical-session.target\n\
+         After=graphical-session.target\n\
+         \n\
+         [Service]\n\
+         Type=notify\n\
+         NotifyAccess=main\n\
+         ExecStart={}\n\
+         Restart=on-failure\n\
+         WatchdogSec={}\n\
+         \n\
+         [Install]\n\
+         WantedBy=graphical-session.target\n",
+        command.join(" "),
+        WATCHDOG_SECS
+    )
+}
+
+/// Write the unit for `exec_start` to `path`, refusing to replace an
+/// existing file unless `force` is set
+pub fn install_unit(path: &Path, exec_start: &[String], force: bool) -> Result<(), KtmmError> {
+    if path.exists() && !force {
+        return Err(KtmmError::ConfigError(format!(
+            "{} already exists; pass --force to overwrite it",
+            path.display()
+        )));
+    }
+    let error =
+        |e: io::Error| KtmmError::ConfigError(format!("cannot write {}: {}", path.display(), e));
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(error)?;
+    }
+    fs::write(path, unit_file(exec_start)).map_err(error)
+}
+
+/// Quote a command-line word for `ExecStart=`
+fn quote(arg: &str) -> String {
+    // `%` starts a unit specifier and `$` an environment variable
+    let escaped = arg.replace('%', "%%").replace('$', "$$");
+    if !escaped.is_empty()
+        && !escaped
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';'))
+    {
+        return escaped;
+    }
+    format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_file_quotes_exec_start() {
+        let unit = unit_file(&[
+            "/opt/my tools/ktmm".to_string(),
+            "--profile".to_string(),
+            "100%".to_string(),
+            "daemon".to_string(),
+        ]);
+        assert!(unit.contains("ExecStart=\"/opt/my tools/ktmm\" --profile 100%% daemon\n"));
+        assert!(unit.contains("Type=notify\n"));
+        assert!(unit.contains("WatchdogSec=60\n"));
+    }
+
+    #[test]
+    fn test_watchdog_interval() {
+        let env = |pairs: &[(&str, String)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect()
+        };
+        let pid = std::process::id().to_string();
+        assert_eq!(
+            watchdog_interval(&env(&[("WATCHDOG_USEC", "60000000".to_string())])),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            watchdog_interval(&env(&[
+                ("WATCHDOG_USEC", "60000000".to_string()),
+                ("WATCHDOG_PID", pid)
+            ])),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            watchdog_interval(&env(&[
+                ("WATCHDOG_USEC", "60000000".to_string()),
+                ("WATCHDOG_PID", "1".to_string())
+            ])),
+            None
+        );
+        assert_eq!(watchdog_interval(&HashMap::new()), None);
+    }
+}
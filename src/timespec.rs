@@ -0,0 +1,223 @@
+//! Human-friendly durations and times of day
+//!
+//! Durations are written as a sequence of numbers with units, e.g. `2h30m`,
+//! `90s` or `1d`; a bare number is a count of seconds. Times of day are local
+//! wall-clock times written `HH:MM` or `HH:MM:SS`.
+
+use chrono::{DateTime, Days, LocalResult, NaiveDateTime, NaiveTime, Offset, TimeZone};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Parse a duration such as `2h30m`, `45m`, `90s` or `3600` into seconds
+pub fn parse_duration(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("empty duration".to_string());
+    }
+    if let Ok(secs) = text.parse::<u64>() {
+        return Ok(secs);
+    }
+
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'd' => 86_400,
+            'h' => 3_600,
+            'm' => 60,
+            's' => 1,
+            _ => {
+                return Err(format!(
+                    "invalid duration `{}`: expected a number of seconds or units d, h, m, s (e.g. 2h30m)",
+                    text
+                ))
+            }
+        };
+        let value: u64 = number
+            .parse()
+            .map_err(|_| format!("invalid duration `{}`: missing number before `{}`", text, c))?;
+        total = value
+            .checked_mul(unit)
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(|| format!("duration `{}` is too long", text))?;
+        number.clear();
+    }
+    if !number.is_empty() {
+        return Err(format!(
+            "invalid duration `{}`: missing unit after `{}`",
+            text, number
+        ));
+    }
+    Ok(total)
+}
+
+/// Format seconds the way [`parse_duration`] reads them, e.g. `2h30m`
+pub fn format_duration(secs: u64) -> String {
+    if secs == 0 {
+        return "0s".to_string();
+    }
+    let units = [
+        (secs / 86_400, 'd'),
+        (secs / 3_600 % 24, 'h'),
+        (secs / 60 % 60, 'm'),
+        (secs % 60, 's'),
+    ];
+    let mut text = String::new();
+    for (value, unit) in units {
+        if value > 0 {
+            text.push_str(&format!("{}{}", value, unit));
+        }
+    }
+    text
+}
+
+/// A local wall-clock time of day
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TimeOfDay(pub NaiveTime);
+
+impl TimeOfDay {
+    /// The time of day at the given hour and minute
+    pub fn hm(hour: u32, minute: u32) -> Option<Self> {
+        NaiveTime::from_hms_opt(hour, minute, 0).map(Self)
+    }
+
+    /// The first moment strictly after `now` at which the clock shows this time
+    ///
+    /// Rolls over to the next day if the time has already passed today. Times
+    /// affected by DST changes are resolved as described for [`resolve_local`].
+    pub fn next_after<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> DateTime<Tz> {
+        let mut date = now.date_naive();
+        loop {
+            if let Some(candidate) = resolve_local(&now.timezone(), date.and_time(self.0)) {
+                if candidate > *now {
+                    return candidate;
+                }
+            }
+            date = date
+                .checked_add_days(Days::new(1))
+                .expect("date out of range");
+        }
+    }
+
+    /// Time from `now` until the clock next shows this time
+    pub fn duration_from<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> Duration {
+        (self.next_after(now) - now.clone())
+            .to_std()
+            .unwrap_or(Duration::ZERO)
+    }
+}
+
+/// Map a local date and time onto a real instant in `tz`
+///
+/// Ambiguous times (when clocks go back) resolve to the earlier instant;
+/// times that do not exist (when clocks go forward) are read with the offset
+/// from before the change, which moves them forward by the size of the gap.
+pub fn resolve_local<Tz: TimeZone>(tz: &Tz, local: NaiveDateTime) -> Option<DateTime<Tz>> {
+    match tz.from_local_datetime(&local) {
+        LocalResult::Single(time) => Some(time),
+        LocalResult::Ambiguous(earliest, _) => Some(earliest),
+        LocalResult::None => {
+            // Read the time with the offset from before the gap, so 02:30
+            // becomes 03:30 when the clocks go forward at 02:00
+            let before = (1..=24 * 60).find_map(|minutes| {
+                tz.from_local_datetime(&(local - chrono::Duration::minutes(minutes)))
+                    .earliest()
+            })?;
+            let offset = before.offset().fix().local_minus_utc();
+            Some(tz.from_utc_datetime(&(local - chrono::Duration::seconds(offset.into()))))
+        }
+    }
+}
+
+impl std::str::FromStr for TimeOfDay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        NaiveTime::parse_from_str(s, "%H:%M")
+            .or_else(|_| NaiveTime::parse_from_str(s, "%H:%M:%S"))
+            .map(TimeOfDay)
+            .map_err(|_| format!("invalid time `{}`: expected HH:MM, e.g. 17:30", s))
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl std::fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use chrono::Timelike;
+        if self.0.second() == 0 {
+            write!(f, "{}", self.0.format("%H:%M"))
+        } else {
+            write!(f, "{}", self.0.format("%H:%M:%S"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(90));
+        assert_eq!(parse_duration("2h30m"), Ok(9_000));
+        assert_eq!(parse_duration("1d2h"), Ok(93_600));
+        assert_eq!(parse_duration("45s"), Ok(45));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("2x").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("2h30").is_err());
+    }
+
+    #[test]
+    fn test_format_duration_round_trips() {
+        for secs in [0, 45, 60, 9_000, 9_030, 93_600, 200_000] {
+            assert_eq!(parse_duration(&format_duration(secs)), Ok(secs));
+        }
+        assert_eq!(format_duration(9_000), "2h30m");
+    }
+
+    #[test]
+    fn test_time_of_day_rolls_over_to_tomorrow() {
+        let tz = FixedOffset::east_opt(3_600).unwrap();
+        let now = tz.with_ymd_and_hms(2024, 5, 10, 16, 0, 0).unwrap();
+        let evening: TimeOfDay = "17:30".parse().unwrap();
+        let morning: TimeOfDay = "09:00".parse().unwrap();
+
+        assert_eq!(
+            evening.next_after(&now),
+            tz.with_ymd_and_hms(2024, 5, 10, 17, 30, 0).unwrap()
+        );
+        assert_eq!(
+            morning.next_after(&now),
+            tz.with_ymd_and_hms(2024, 5, 11, 9, 0, 0).unwrap()
+        );
+        assert_eq!(evening.duration_from(&now), Duration::from_secs(90 * 60));
+        // Exactly now counts as already passed
+        assert_eq!(
+            TimeOfDay::hm(16, 0).unwrap().next_after(&now),
+            tz.with_ymd_and_hms(2024, 5, 11, 16, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_time_of_day() {
+        assert_eq!("7:05".parse(), Ok(TimeOfDay::hm(7, 5).unwrap()));
+        assert!("25:00".parse::<TimeOfDay>().is_err());
+        assert!("noon".parse::<TimeOfDay>().is_err());
+        assert_eq!(TimeOfDay::hm(17, 30).unwrap().to_string(), "17:30");
+    }
+}
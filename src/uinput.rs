@@ -0,0 +1,353 @@
+//! Virtual pointer backend using the Linux uinput device
+//!
+//! Wayland compositors do not let clients move the pointer, but every
+//! compositor accepts input from kernel input devices. [`UinputBackend`]
+//! creates a virtual relative pointer through `/dev/uinput` and nudges it
+//! with tiny `REL_X`/`REL_Y` events (or turns its wheel with `REL_WHEEL` and
+//! `REL_HWHEEL`), so it works the same everywhere as long as the user may
+//! write to `/dev/uinput`.
+//!
+//! The event encoding is kept separate in [`EventWriter`], which can write to
+//! any [`Write`] implementation, e.g. a plain file in tests.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::mem;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::backend::{InputBackend, ScrollAxis};
+use crate::KtmmError;
+
+/// The uinput device node
+pub const UINPUT_PATH: &str = "/dev/uinput";
+
+// Event types and codes from linux/input-event-codes.h
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+pub const EV_REL: u16 = 0x02;
+pub const SYN_REPORT: u16 = 0x00;
+pub const REL_X: u16 = 0x00;
+pub const REL_Y: u16 = 0x01;
+pub const REL_HWHEEL: u16 = 0x06;
+pub const REL_WHEEL: u16 = 0x08;
+pub const BTN_LEFT: u16 = 0x110;
+
+const BUS_VIRTUAL: u16 = 0x06;
+
+/// How long a new device takes to be picked up by the compositor and
+/// libinput; events written before then are lost
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// Size of one `struct input_event` on this platform
+pub const EVENT_SIZE: usize = mem::size_of::<libc::input_event>();
+
+// ioctl requests from linux/uinput.h, encoded for the target architecture
+const UINPUT_IOCTL_BASE: u32 = b'U' as u32;
+
+const UI_DEV_CREATE: libc::Ioctl = libc::_IO(UINPUT_IOCTL_BASE, 1);
+const UI_DEV_DESTROY: libc::Ioctl = libc::_IO(UINPUT_IOCTL_BASE, 2);
+const UI_DEV_SETUP: libc::Ioctl = libc::_IOW::<libc::uinput_setup>(UINPUT_IOCTL_BASE, 3);
+const UI_SET_EVBIT: libc::Ioctl = libc::_IOW::<libc::c_int>(UINPUT_IOCTL_BASE, 100);
+const UI_SET_KEYBIT: libc::Ioctl = libc::_IOW::<libc::c_int>(UINPUT_IOCTL_BASE, 101);
+const UI_SET_RELBIT: libc::Ioctl = libc::_IOW::<libc::c_int>(UINPUT_IOCTL_BASE, 102);
+
+/// Encodes `struct input_event` records
+pub struct EventWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> EventWriter<W> {
+    /// Write events to `out`
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    /// Write a single event; the kernel fills in the timestamp
+    pub fn write_event(&mut self, kind: u16, code: u16, value: i32) -> io::Result<()> {
+        let mut event = [0u8; EVENT_SIZE];
+        let time = mem::size_of::<libc::timeval>();
+        event[time..time + 2].copy_from_slice(&kind.to_ne_bytes());
+        event[time + 2..time + 4].copy_from_slice(&code.to_ne_bytes());
+        event[time + 4..time + 8].copy_from_slice(&value.to_ne_bytes());
+        self.out.write_all(&event)
+    }
+
+    /// Write a relative pointer motion followed by a sync report
+    pub fn move_relative(&mut self, dx: i32, dy: i32) -> io::Result<()> {
+        if dx != 0 {
+            self.write_event(EV_REL, REL_X, dx)?;
+        }
+        if dy != 0 {
+            self.write_event(EV_REL, REL_Y, dy)?;
+        }
+        self.write_event(EV_SYN, SYN_REPORT, 0)?;
+        self.out.flush()
+    }
+
+    /// Write a scroll wheel turn followed by a sync report
+    ///
+    /// Positive `notches` scroll down or right, as with the other backends,
+    /// while the kernel counts `REL_WHEEL` upwards.
+    pub fn scroll(&mut self, axis: ScrollAxis, notches: i32) -> io::Result<()> {
+        match axis {
+            ScrollAxis::Vertical => self.write_event(EV_REL, REL_WHEEL, -notches)?,
+            ScrollAxis::Horizontal => self.write_event(EV_REL, REL_HWHEEL, notches)?,
+        }
+        self.write_event(EV_SYN, SYN_REPORT, 0)?;
+        self.out.flush()
+    }
+
+    /// Get the underlying writer back
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Decode a buffer of `struct input_event` records into `(type, code, value)`
+pub fn decode_events(bytes: &[u8]) -> Vec<(u16, u16, i32)> {
+    let time = mem::size_of::<libc::timeval>();
+    bytes
+        .chunks_exact(EVENT_SIZE)
+        .map(|event| {
+            let field = &event[time..];
+            (
+                u16::from_ne_bytes([field[0], field[1]]),
+                u16::from_ne_bytes([field[2], field[3]]),
+                i32::from_ne_bytes([field[4], field[5], field[6], field[7]]),
+            )
+        })
+        .collect()
+}
+
+/// A virtual pointer device created through uinput, destroyed when dropped
+pub struct UinputDevice {
+    file: File,
+}
+
+impl UinputDevice {
+    /// Create a virtual relative pointer through the uinput node at `path`
+    ///
+    /// Returns once the new device has had time to be noticed, so that even
+    /// the first events written to it take effect.
+    pub fn create(path: &Path) -> Result<Self, KtmmError> {
+        let file = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)
+            .map_err(|e| open_error(path, e))?;
+        let fd = file.as_raw_fd();
+
+        let mut setup: libc::uinput_setup = unsafe { mem::zeroed() };
+        setup.id = libc::input_id {
+            bustype: BUS_VIRTUAL,
+            vendor: 0,
+            product: 0,
+            version: 1,
+        };
+        for (dst, src) in setup.name.iter_mut().zip(b"ktmm virtual pointer") {
+            *dst = *src as libc::c_char;
+        }
+
+        // A pointer needs a button for libinput to treat it as a mouse
+        let result = unsafe {
+            check(libc::ioctl(fd, UI_SET_EVBIT, EV_KEY as libc::c_int))
+                .and_then(|_| check(libc::ioctl(fd, UI_SET_KEYBIT, BTN_LEFT as libc::c_int)))
+                .and_then(|_| check(libc::ioctl(fd, UI_SET_EVBIT, EV_REL as libc::c_int)))
+                .and_then(|_| check(libc::ioctl(fd, UI_SET_RELBIT, REL_X as libc::c_int)))
+                .and_then(|_| check(libc::ioctl(fd, UI_SET_RELBIT, REL_Y as libc::c_int)))
+                .and_then(|_| check(libc::ioctl(fd, UI_SET_RELBIT, REL_WHEEL as libc::c_int)))
+                .and_then(|_| check(libc::ioctl(fd, UI_SET_RELBIT, REL_HWHEEL as libc::c_int)))
+                .and_then(|_| check(libc::ioctl(fd, UI_DEV_SETUP, &setup)))
+                .and_then(|_| check(libc::ioctl(fd, UI_DEV_CREATE)))
+        };
+        result.map_err(|e| {
+            KtmmError::PlatformError(format!(
+                "could not create a virtual pointer on {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+
+        thread::sleep(SETTLE_TIME);
+        Ok(Self { file })
+    }
+}
+
+impl Write for UinputDevice {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for UinputDevice {
+    fn drop(&mut self) {
+        unsafe {
+            libc::ioctl(self.file.as_raw_fd(), UI_DEV_DESTROY);
+        }
+    }
+}
+
+fn check(result: libc::c_int) -> io::Result<()> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+fn open_error(path: &Path, e: io::Error) -> KtmmError {
+    let hint = match e.kind() {
+        io::ErrorKind::PermissionDenied => format!(
+            "{} is not writable by this user. Allow access with a udev rule such as\n\
+            \n    KERNEL==\"uinput\", GROUP=\"input\", MODE=\"0660\", OPTIONS+=\"static_node=uinput\"\n\
+            \nin /etc/udev/rules.d/99-ktmm-uinput.rules, add yourself to the `input` group\n\
+            and log in again.",
+            path.display()
+        ),
+        io::ErrorKind::NotFound => format!(
+            "{} does not exist. Load the uinput kernel module with `sudo modprobe uinput`.",
+            path.display()
+        ),
+        _ => format!("could not open {}: {}", path.display(), e),
+    };
+    KtmmError::PlatformError(hint)
+}
+
+/// An input backend that moves a virtual uinput pointer
+///
+/// Only relative motion is possible: the pointer position cannot be read
+/// back, so absolute moves are unsupported.
+pub struct UinputBackend<W: Write = UinputDevice> {
+    events: EventWriter<W>,
+}
+
+impl UinputBackend<UinputDevice> {
+    /// Create a virtual pointer through `/dev/uinput`
+    pub fn open() -> Result<Self, KtmmError> {
+        Self::open_path(Path::new(UINPUT_PATH))
+    }
+
+    /// Create a virtual pointer through the uinput node at `path`
+    pub fn open_path(path: &Path) -> Result<Self, KtmmError> {
+        Ok(Self::from_writer(UinputDevice::create(path)?))
+    }
+}
+
+impl<W: Write> UinputBackend<W> {
+    /// Write the pointer events to `out` instead of a uinput device
+    pub fn from_writer(out: W) -> Self {
+        Self {
+            events: EventWriter::new(out),
+        }
+    }
+
+    /// Get the underlying writer back
+    pub fn into_inner(self) -> W {
+        self.events.into_inner()
+    }
+}
+
+impl<W: Write> InputBackend for UinputBackend<W> {
+    fn mouse_location(&mut self) -> Result<(i32, i32), KtmmError> {
+        Err(KtmmError::Unsupported(
+            "uinput cannot read the pointer position".to_string(),
+        ))
+    }
+
+    fn mouse_move_to(&mut self, _x: i32, _y: i32) -> Result<(), KtmmError> {
+        Err(KtmmError::Unsupported(
+            "uinput can only move the pointer relatively".to_string(),
+        ))
+    }
+
+    fn mouse_move_relative(&mut self, dx: i32, dy: i32) -> Result<(), KtmmError> {
+        self.events
+            .move_relative(dx, dy)
+            .map_err(|e| KtmmError::MouseControlError(format!("uinput write failed: {}", e)))
+    }
+
+    fn scroll(&mut self, axis: ScrollAxis, notches: i32) -> Result<(), KtmmError> {
+        self.events
+            .scroll(axis, notches)
+            .map_err(|e| KtmmError::MouseControlError(format!("uinput write failed: {}", e)))
+    }
+
+    fn supports_absolute_positioning(&self) -> bool {
+        false
+    }
+
+    fn check_permissions(&self) -> Result<(), KtmmError> {
+        // Opening the device already proved we may use it
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The numbers below use the generic layout; mips, powerpc and sparc
+    // encode the direction and size differently
+    #[cfg(not(any(
+        target_arch = "mips",
+        target_arch = "mips64",
+        target_arch = "powerpc",
+        target_arch = "powerpc64",
+        target_arch = "sparc",
+        target_arch = "sparc64"
+    )))]
+    #[test]
+    fn test_ioctl_numbers_match_linux_headers() {
+        assert_eq!(UI_DEV_CREATE as u32, 0x5501);
+        assert_eq!(UI_DEV_DESTROY as u32, 0x5502);
+        assert_eq!(UI_DEV_SETUP as u32, 0x405c_5503);
+        assert_eq!(UI_SET_EVBIT as u32, 0x4004_5564);
+        assert_eq!(UI_SET_KEYBIT as u32, 0x4004_5565);
+        assert_eq!(UI_SET_RELBIT as u32, 0x4004_5566);
+    }
+
+    #[test]
+    fn test_move_relative_encoding() {
+        let mut writer = EventWriter::new(Vec::new());
+        writer.move_relative(1, -1).unwrap();
+        writer.move_relative(0, 2).unwrap();
+        let bytes = writer.into_inner();
+
+        assert_eq!(bytes.len(), 5 * EVENT_SIZE);
+        assert_eq!(
+            decode_events(&bytes),
+            vec![
+                (EV_REL, REL_X, 1),
+                (EV_REL, REL_Y, -1),
+                (EV_SYN, SYN_REPORT, 0),
+                (EV_REL, REL_Y, 2),
+                (EV_SYN, SYN_REPORT, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scroll_encoding() {
+        let mut writer = EventWriter::new(Vec::new());
+        writer.scroll(ScrollAxis::Vertical, 1).unwrap();
+        writer.scroll(ScrollAxis::Horizontal, -1).unwrap();
+
+        assert_eq!(
+            decode_events(&writer.into_inner()),
+            vec![
+                (EV_REL, REL_WHEEL, -1),
+                (EV_SYN, SYN_REPORT, 0),
+                (EV_REL, REL_HWHEEL, -1),
+                (EV_SYN, SYN_REPORT, 0),
+            ]
+        );
+    }
+}
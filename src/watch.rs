@@ -0,0 +1,124 @@
+//! Waiting for other processes to exit (Unix)
+//!
+//! `ktmm --wait-pid PID` keeps the system awake for as long as an existing
+//! process runs, like `caffeinate -w`. On Linux the wait uses a pidfd, which
+//! becomes readable as soon as the process exits; elsewhere, or on kernels
+//! without `pidfd_open`, the process is polled.
+
+use std::thread;
+use std::time::Duration;
+
+use crate::Handle;
+
+/// How often to check on a process that cannot be waited for directly
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Whether a process with this PID exists
+pub fn process_exists(pid: u32) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        std::path::Path::new(&format!("/proc/{}", pid)).exists()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        // Signal 0 only checks whether the process could be signalled
+        let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+        result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+}
+
+/// Block until the process with this PID has exited
+pub fn wait_for_exit(pid: u32) {
+    #[cfg(target_os = "linux")]
+    if wait_pidfd(pid) {
+        return;
+    }
+
+    while process_exists(pid) {
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Wait through a pidfd; returns `false` if that is not possible
+#[cfg(target_os = "linux")]
+fn wait_pidfd(pid: u32) -> bool {
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    if fd < 0 {
+        // Already gone, or no pidfd support
+        return io::Error::last_os_error().raw_os_error() == Some(libc::ESRCH);
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) };
+
+    loop {
+        let mut pollfd = libc::pollfd {
+            fd: fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        match unsafe { libc::poll(&mut pollfd, 1, -1) } {
+            n if n > 0 => return true,
+            _ if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+            _ => return false,
+        }
+    }
+}
+
+/// Block until all of `pids` have exited
+pub fn wait_for_all(pids: &[u32]) {
+    for &pid in pids {
+        wait_for_exit(pid);
+    }
+}
+
+/// Stop `handle` once all of `pids` have exited
+///
+/// The processes are watched on a background thread, which is returned.
+pub fn stop_when_exited(pids: Vec<u32>, handle: Handle) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        wait_for_all(&pids);
+        handle.stop();
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use std::time::Instant;
+
+    #[test]
+    fn test_process_exists() {
+        assert!(process_exists(std::process::id()));
+
+        let mut child = Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        assert!(!process_exists(pid));
+    }
+
+    #[test]
+    fn test_handle_stops_after_all_processes_exit() {
+        let short = Command::new("sleep").arg("0.2").spawn().unwrap();
+        let long = Command::new("sleep").arg("0.6").spawn().unwrap();
+        let pids = vec![long.id(), short.id()];
+        // Reap the children as their real parent would
+        let reapers: Vec<_> = [short, long]
+            .into_iter()
+            .map(|mut child| thread::spawn(move || child.wait()))
+            .collect();
+
+        let handle = Handle::new();
+        let start = Instant::now();
+        stop_when_exited(pids, handle.clone()).join().unwrap();
+
+        assert!(handle.is_stopped());
+        assert!(start.elapsed() >= Duration::from_millis(400));
+        for reaper in reapers {
+            reaper.join().unwrap().unwrap();
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use chrono::{Local, TimeZone};
+use common::config;
+use ktmm::clock::VirtualClock;
+use ktmm::{MouseMover, MouseMoverConfig, MoverEvent, RecordingBackend};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+mod common;
+
+// These tests run the mover loop on a virtual clock, so a simulated minute
+// passes in an instant and every wakeup of the loop is counted
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+#[test]
+fn test_loop_only_wakes_up_to_nudge() {
+    let backend = RecordingBackend::new((10, 10));
+    let clock = Arc::new(VirtualClock::new(MINUTE));
+    let mut mover = MouseMover::with_backend(config(10), backend.clone());
+    mover.set_clock(clock.clone());
+
+    let started = Instant::now();
+    mover.start().unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(clock.elapsed(), MINUTE);
+    // Six nudges, each moving out and back
+    assert_eq!(backend.positions().len(), 12);
+    // One wakeup per nudge, plus the one that ends the minute
+    assert_eq!(clock.wakeups(), 7);
+}
+
+#[test]
+fn test_paused_loop_sleeps_through_the_minute() {
+    let backend = RecordingBackend::new((10, 10));
+    let clock = Arc::new(VirtualClock::new(MINUTE));
+    let mut mover = MouseMover::with_backend(config(1), backend.clone());
+    mover.set_clock(clock.clone());
+    mover.handle().pause();
+
+    mover.start().unwrap();
+
+    assert!(backend.events().is_empty());
+    assert_eq!(clock.wakeups(), 1);
+}
+
+#[test]
+fn test_schedule_follows_simulated_time() {
+    let backend = RecordingBackend::new((10, 10));
+    // 2024-05-13 is a Monday; the window opens a simulated minute in
+    let start = Local.with_ymd_and_hms(2024, 5, 13, 8, 59, 0).unwrap();
+    let clock = Arc::new(VirtualClock::starting_at(start, 2 * MINUTE));
+    let config = MouseMoverConfig {
+        schedule: "Mon-Fri 09:00-17:00".parse().unwrap(),
+        ..config(10)
+    };
+    let mut mover = MouseMover::with_backend(config, backend.clone());
+    mover.set_clock(clock.clone());
+
+    let started = Instant::now();
+    mover.start().unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    // Six nudges, starting ten seconds after the window opens
+    assert_eq!(backend.positions().len(), 12);
+}
+
+#[test]
+fn test_idle_threshold_follows_simulated_time() {
+    let backend = RecordingBackend::new((10, 10));
+    let clock = Arc::new(VirtualClock::new(MINUTE));
+    let config = MouseMoverConfig {
+        idle_threshold_secs: Some(30),
+        ..config(10)
+    };
+    let mut mover = MouseMover::with_backend(config, backend.clone());
+    mover.set_clock(clock.clone());
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    mover.set_event_handler(move |event| sink.lock().unwrap().push(event.clone()));
+
+    mover.start().unwrap();
+
+    let events = events.lock().unwrap();
+    assert_eq!(
+        events[0],
+        MoverEvent::Deferred {
+            idle: Duration::from_secs(10),
+            retry_in: Duration::from_secs(20)
+        }
+    );
+    // Idle for long enough from 30s on, so nudging at 30s, 40s, 50s and 60s
+    assert_eq!(
+        events[1..].iter().filter(|event| event.is_nudge()).count(),
+        4
+    );
+    assert_eq!(backend.positions().len(), 8);
+}
+
+#[test]
+fn test_unreadable_calendar_is_reported_once() {
+    let path = common::temp_path("clock-calendar.ics");
+    std::fs::write(
+        &path,
+        "BEGIN:VCALENDAR\nBEGIN:VEVENT\nSUMMARY:Demo\nDTSTART:20240513T000000Z\n\
+         DTEND:20240514T000000Z\nEND:VEVENT\nEND:VCALENDAR\n",
+    )
+    .unwrap();
+    let backend = RecordingBackend::new((10, 10));
+    let start = Local.with_ymd_and_hms(2024, 5, 13, 12, 0, 0).unwrap();
+    let clock = Arc::new(VirtualClock::starting_at(start, 3 * MINUTE));
+    let removed = path.clone();
+    clock.schedule(MINUTE / 2, move |_| std::fs::remove_file(removed).unwrap());
+    let config = MouseMoverConfig {
+        calendar: Some(path),
+        ..config(10)
+    };
+    let mut mover = MouseMover::with_backend(config, backend.clone());
+    mover.set_clock(clock.clone());
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    mover.set_event_handler(move |event| sink.lock().unwrap().push(event.clone()));
+
+    mover.start().unwrap();
+
+    let events = events.lock().unwrap();
+    let errors: Vec<_> = events.iter().filter(|event| event.is_error()).collect();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(matches!(errors[0], MoverEvent::ActivationError { .. }));
+    // The events read before keep the mover going
+    assert_eq!(backend.positions().len(), 36);
+}
@@ -0,0 +1,23 @@
+//! Helpers shared by the integration tests
+
+// Each test crate only uses some of them
+#![allow(dead_code)]
+
+use ktmm::MouseMoverConfig;
+use std::path::PathBuf;
+
+/// A configuration that nudges every `interval_secs` and moves the pointer
+/// straight back
+pub fn config(interval_secs: u64) -> MouseMoverConfig {
+    MouseMoverConfig {
+        interval_secs,
+        return_delay_ms: 0,
+        ..MouseMoverConfig::default()
+    }
+}
+
+/// A path in the temporary directory that is unique to `name` and this test
+/// run, for sockets, lock files and private directories
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("ktmm-test-{}-{}", name, std::process::id()))
+}
@@ -0,0 +1,206 @@
+use clap::Parser;
+use ktmm::cli::Cli;
+use ktmm::config::load_from;
+use ktmm::{KtmmError, Verbosity};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+mod common;
+
+// Write a config file into a fresh temporary directory
+fn write_config(name: &str, contents: &str) -> PathBuf {
+    let dir = common::temp_path(name);
+    fs::create_dir_all(dir.join("ktmm")).unwrap();
+    let path = dir.join("ktmm").join("config.toml");
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+fn env(vars: &[(&str, &str)]) -> HashMap<String, String> {
+    vars.iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_precedence_defaults_file_env_cli() {
+    let path = write_config(
+        "precedence",
+        "interval = 10\ndx = 5\ndy = 6\ndelay = 20\nverbosity = \"quiet\"\n",
+    );
+    let env = env(&[("KTMM_DX", "7"), ("KTMM_DELAY", "30")]);
+    let cli =
+        Cli::try_parse_from(["ktmm", "--config", path.to_str().unwrap(), "--delay", "40"]).unwrap();
+
+    let config = load_from(&cli, &env).unwrap();
+
+    assert_eq!(config.interval_secs, 10); // file
+    assert_eq!(config.movement_pixels, (7, 6)); // env beats file for dx
+    assert_eq!(config.return_delay_ms, 40); // cli beats env and file
+    assert_eq!(config.verbosity, Verbosity::Quiet); // file
+    assert_eq!(config.duration_secs, None); // default
+}
+
+#[test]
+fn test_default_location_under_xdg_config_home() {
+    let path = write_config("xdg", "interval = 42\n");
+    let xdg = path.parent().unwrap().parent().unwrap();
+    let env = env(&[("XDG_CONFIG_HOME", xdg.to_str().unwrap())]);
+    let cli = Cli::try_parse_from(["ktmm"]).unwrap();
+
+    let config = load_from(&cli, &env).unwrap();
+
+    assert_eq!(config.interval_secs, 42);
+}
+
+#[test]
+fn test_missing_default_file_is_ignored() {
+    let env = env(&[("XDG_CONFIG_HOME", "/nonexistent/ktmm-test")]);
+    let cli = Cli::try_parse_from(["ktmm"]).unwrap();
+
+    let config = load_from(&cli, &env).unwrap();
+
+    assert_eq!(config.interval_secs, 60);
+}
+
+#[test]
+fn test_missing_explicit_file_is_an_error() {
+    let cli = Cli::try_parse_from(["ktmm", "--config", "/nonexistent/ktmm.toml"]).unwrap();
+    assert!(matches!(
+        load_from(&cli, &HashMap::new()),
+        Err(KtmmError::ConfigError(_))
+    ));
+}
+
+#[test]
+fn test_invalid_file_reports_path_and_line() {
+    let path = write_config("invalid", "interval = 30\ndelay = -5\n");
+    let cli = Cli::try_parse_from(["ktmm", "--config", path.to_str().unwrap()]).unwrap();
+
+    match load_from(&cli, &HashMap::new()) {
+        Err(KtmmError::ConfigError(msg)) => {
+            assert!(msg.contains(&format!("{}:2:", path.display())), "{}", msg);
+            assert!(msg.contains("`delay`"), "{}", msg);
+        }
+        other => panic!("expected a config error, got {:?}", other),
+    }
+}
+
+const PROFILES: &str = r#"
+interval = 60
+dx = 2
+
+[profile.presentation]
+interval = 20
+
+[profile.download]
+interval = 240
+delay = 3
+
+[profile.overnight]
+inherits = "download"
+verbosity = "quiet"
+"#;
+
+#[test]
+fn test_profile_inherits_top_level_settings() {
+    let path = write_config("profile", PROFILES);
+    let cli = Cli::try_parse_from([
+        "ktmm",
+        "--config",
+        path.to_str().unwrap(),
+        "--profile",
+        "presentation",
+    ])
+    .unwrap();
+
+    let config = load_from(&cli, &HashMap::new()).unwrap();
+
+    assert_eq!(config.interval_secs, 20);
+    assert_eq!(config.movement_pixels, (2, 1));
+    assert_eq!(config.return_delay_ms, 6);
+}
+
+#[test]
+fn test_profile_inherits_from_other_profile() {
+    let path = write_config("profile-chain", PROFILES);
+    let cli = Cli::try_parse_from(["ktmm", "--config", path.to_str().unwrap()]).unwrap();
+    let env = env(&[("KTMM_PROFILE", "overnight")]);
+
+    let config = load_from(&cli, &env).unwrap();
+
+    assert_eq!(config.interval_secs, 240);
+    assert_eq!(config.return_delay_ms, 3);
+    assert_eq!(config.movement_pixels, (2, 1));
+    assert_eq!(config.verbosity, Verbosity::Quiet);
+}
+
+#[test]
+fn test_cli_overrides_profile() {
+    let path = write_config("profile-cli", PROFILES);
+    let cli = Cli::try_parse_from([
+        "ktmm",
+        "--config",
+        path.to_str().unwrap(),
+        "--profile",
+        "download",
+        "--interval",
+        "90",
+    ])
+    .unwrap();
+
+    let config = load_from(&cli, &HashMap::new()).unwrap();
+
+    assert_eq!(config.interval_secs, 90);
+    assert_eq!(config.return_delay_ms, 3);
+}
+
+#[test]
+fn test_unknown_profile_lists_available_profiles() {
+    let path = write_config("profile-unknown", PROFILES);
+    let cli = Cli::try_parse_from([
+        "ktmm",
+        "--config",
+        path.to_str().unwrap(),
+        "--profile",
+        "lunch",
+    ])
+    .unwrap();
+
+    match load_from(&cli, &HashMap::new()) {
+        Err(KtmmError::ConfigError(msg)) => {
+            assert!(msg.contains("`lunch`"), "{}", msg);
+            assert!(msg.contains("download, overnight, presentation"), "{}", msg);
+        }
+        other => panic!("expected a config error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_schedule_from_file_and_env() {
+    let path = write_config(
+        "schedule",
+        "schedule = [\"Mon-Fri 09:00-18:00\", \"Sat 10:00-12:00\"]\n",
+    );
+    let cli = Cli::try_parse_from(["ktmm", "--config", path.to_str().unwrap()]).unwrap();
+
+    let config = load_from(&cli, &HashMap::new()).unwrap();
+    assert_eq!(
+        config.schedule.to_string(),
+        "Mon-Fri 09:00-18:00; Sat 10:00-12:00"
+    );
+
+    let env = env(&[("KTMM_SCHEDULE", "Sun 22:00-06:00")]);
+    let config = load_from(&cli, &env).unwrap();
+    assert_eq!(config.schedule.to_string(), "Sun 22:00-06:00");
+
+    let path = write_config("schedule-invalid", "\nschedule = [\"Mon-Fri 9-18\"]\n");
+    let cli = Cli::try_parse_from(["ktmm", "--config", path.to_str().unwrap()]).unwrap();
+    match load_from(&cli, &HashMap::new()) {
+        Err(KtmmError::ConfigError(msg)) => {
+            assert!(msg.contains(&format!("{}:2:", path.display())), "{}", msg);
+        }
+        other => panic!("expected a config error, got {:?}", other),
+    }
+}
@@ -0,0 +1,119 @@
+#![cfg(unix)]
+
+use ktmm::daemon::{self, Request, Server, Status};
+use ktmm::{KtmmError, MouseMover, MouseMoverConfig, RecordingBackend};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+mod common;
+
+fn socket(name: &str) -> PathBuf {
+    common::temp_path(name).with_extension("sock")
+}
+
+fn describe(state: &ktmm::HandleState) -> Status {
+    Status {
+        pid: std::process::id(),
+        paused: state.paused,
+        interval_secs: state.interval_secs.unwrap_or(60),
+        mode: "mouse".to_string(),
+        uptime_secs: 0,
+    }
+}
+
+#[test]
+fn test_requests_control_a_running_mover() {
+    let path = socket("control");
+    let config = MouseMoverConfig {
+        return_delay_ms: 0,
+        ..MouseMoverConfig::default()
+    };
+    let mover = MouseMover::with_backend(config, RecordingBackend::new((10, 10)));
+    let server = Server::bind(&path).unwrap();
+    server.serve(mover.handle(), describe).unwrap();
+    let (handle, thread) = mover.spawn();
+
+    let status = daemon::send(&path, Request::Status).unwrap();
+    assert!(!status.paused);
+    assert_eq!(status.interval_secs, 60);
+
+    assert!(daemon::send(&path, Request::Pause).unwrap().paused);
+    assert!(handle.is_paused());
+    assert!(!daemon::send(&path, Request::Resume).unwrap().paused);
+
+    let status = daemon::send(&path, Request::SetInterval { secs: 30 }).unwrap();
+    assert_eq!(status.interval_secs, 30);
+    assert!(matches!(
+        daemon::send(&path, Request::SetInterval { secs: 0 }),
+        Err(KtmmError::ControlError(_))
+    ));
+
+    daemon::send(&path, Request::Stop).unwrap();
+    thread.join().unwrap().unwrap();
+
+    drop(server);
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_other_protocol_versions_are_refused() {
+    let path = socket("version");
+    let server = Server::bind(&path).unwrap();
+    server.serve(ktmm::Handle::new(), describe).unwrap();
+
+    let mut stream = UnixStream::connect(&path).unwrap();
+    stream
+        .write_all(b"{\"version\":2,\"command\":\"status\"}\n")
+        .unwrap();
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line).unwrap();
+    let response: daemon::Response = serde_json::from_str(&line).unwrap();
+    assert!(!response.ok);
+    assert!(response.error.unwrap().contains("version 2"));
+}
+
+#[test]
+fn test_an_idle_client_does_not_hold_up_others() {
+    let path = socket("idle");
+    let server = Server::bind(&path).unwrap();
+    server.serve(ktmm::Handle::new(), describe).unwrap();
+
+    // Connect without ever sending a request
+    let _idle = UnixStream::connect(&path).unwrap();
+    let start = Instant::now();
+    daemon::send(&path, Request::Status).unwrap();
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn test_a_second_server_is_refused_but_stale_sockets_are_replaced() {
+    let path = socket("twice");
+    let server = Server::bind(&path).unwrap();
+    assert!(matches!(
+        Server::bind(&path),
+        Err(KtmmError::ControlError(_))
+    ));
+    drop(server);
+
+    // A socket file left behind by a daemon that died is taken over
+    drop(UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+    drop(Server::bind(&path).unwrap());
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_binary_reports_a_missing_daemon() {
+    let path = socket("missing");
+    let output = Command::new(env!("CARGO_BIN_EXE_ktmm"))
+        .args(["status", "--socket"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("no ktmm daemon is listening"), "{}", stderr);
+}
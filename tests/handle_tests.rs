@@ -0,0 +1,126 @@
+use common::config;
+use ktmm::clock::VirtualClock;
+use ktmm::{MouseMover, MouseMoverConfig, MoverEvent, RecordingBackend};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+mod common;
+
+// Most of these tests run the real mover loop on a thread and control it
+// through its Handle, so they take a few seconds each. The others act on the
+// handle at set times of a virtual clock, which takes no time at all.
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+#[test]
+fn test_stop_wakes_the_loop_immediately() {
+    let backend = RecordingBackend::new((10, 10));
+    let (handle, thread) = MouseMover::with_backend(config(60), backend.clone()).spawn();
+
+    thread::sleep(Duration::from_millis(50));
+    let stopped_at = Instant::now();
+    handle.stop();
+    thread.join().unwrap().unwrap();
+
+    assert!(stopped_at.elapsed() < Duration::from_secs(1));
+    assert!(backend.events().is_empty());
+}
+
+#[test]
+fn test_pause_and_resume() {
+    let backend = RecordingBackend::new((10, 10));
+    let mover = MouseMover::with_backend(config(1), backend.clone());
+    mover.handle().pause();
+    let (handle, thread) = mover.spawn();
+
+    // Paused movers do not move the mouse
+    thread::sleep(Duration::from_millis(1500));
+    assert!(backend.events().is_empty());
+
+    // Resuming restarts the interval
+    handle.resume();
+    thread::sleep(Duration::from_millis(1500));
+    handle.stop();
+    thread.join().unwrap().unwrap();
+
+    assert_eq!(backend.positions(), vec![(11, 11), (10, 10)]);
+}
+
+#[test]
+fn test_set_interval_takes_effect_immediately() {
+    let backend = RecordingBackend::new((10, 10));
+    let (handle, thread) = MouseMover::with_backend(config(60), backend.clone()).spawn();
+
+    handle.set_interval(1).unwrap();
+    thread::sleep(Duration::from_millis(1500));
+    handle.stop();
+    thread.join().unwrap().unwrap();
+
+    assert_eq!(backend.events().len(), 2);
+    assert_eq!(handle.state().interval_secs, Some(1));
+}
+
+#[test]
+fn test_nudge_now_also_nudges_a_paused_mover() {
+    let backend = RecordingBackend::new((10, 10));
+    let clock = Arc::new(VirtualClock::new(MINUTE));
+    let mut mover = MouseMover::with_backend(config(60), backend.clone());
+    mover.set_clock(clock.clone());
+    mover.handle().pause();
+    clock.schedule(Duration::from_secs(10), |handle| handle.nudge_now());
+
+    mover.start().unwrap();
+
+    assert_eq!(backend.positions(), vec![(11, 11), (10, 10)]);
+}
+
+#[test]
+fn test_reload_replaces_the_configuration() {
+    let backend = RecordingBackend::new((10, 10));
+    let clock = Arc::new(VirtualClock::new(MINUTE));
+    let mut mover = MouseMover::with_backend(config(60), backend.clone());
+    mover.set_clock(clock.clone());
+    clock.schedule(Duration::from_secs(5), |handle| {
+        handle.reload(MouseMoverConfig {
+            movement_pixels: (3, 0),
+            ..config(20)
+        })
+    });
+
+    mover.start().unwrap();
+
+    // Nudges at 25s and 45s
+    assert_eq!(backend.positions(), [(13, 10), (10, 10)].repeat(2));
+}
+
+#[test]
+fn test_reload_is_rejected_when_the_calendar_is_unreadable() {
+    let backend = RecordingBackend::new((10, 10));
+    let clock = Arc::new(VirtualClock::new(MINUTE));
+    let mut mover = MouseMover::with_backend(config(30), backend.clone());
+    mover.set_clock(clock.clone());
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    mover.set_event_handler(move |event| sink.lock().unwrap().push(event.clone()));
+    clock.schedule(Duration::from_secs(5), |handle| {
+        handle.reload(MouseMoverConfig {
+            movement_pixels: (3, 0),
+            calendar: Some("/nonexistent/ktmm-test.ics".into()),
+            ..config(20)
+        })
+    });
+
+    // The run goes on with the previous configuration rather than failing
+    mover.start().unwrap();
+
+    assert_eq!(clock.elapsed(), MINUTE);
+    // One nudge at 35s, with the old interval and movement
+    assert_eq!(backend.positions(), [(11, 11), (10, 10)]);
+    let events = events.lock().unwrap();
+    assert!(
+        matches!(events.as_slice(), [MoverEvent::ReloadRejected { .. }, ..]),
+        "{:?}",
+        events
+    );
+}
@@ -0,0 +1,209 @@
+#![cfg(target_os = "linux")]
+
+use ktmm::inhibit::Inhibitor;
+use std::io::{BufRead, BufReader, Write};
+use std::os::fd::OwnedFd;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use zbus::blocking::connection;
+
+// A private session bus that is shut down when the test ends
+struct PrivateBus {
+    daemon: Child,
+    address: String,
+}
+
+impl PrivateBus {
+    // Start `dbus-daemon --session`, or return None if it is not installed
+    fn start() -> Option<Self> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take()?)
+            .read_line(&mut address)
+            .ok()?;
+        Some(Self {
+            daemon,
+            address: address.trim().to_string(),
+        })
+    }
+
+    fn connect(&self) -> zbus::Result<zbus::blocking::Connection> {
+        connection::Builder::address(self.address.as_str())?.build()
+    }
+}
+
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+// Records the calls made to the stub services
+#[derive(Default)]
+struct Calls {
+    inhibited: Vec<(String, String)>,
+    uninhibited: Vec<u32>,
+    logind: Vec<(String, String)>,
+}
+
+struct StubScreenSaver {
+    calls: Arc<Mutex<Calls>>,
+}
+
+#[zbus::interface(name = "org.freedesktop.ScreenSaver")]
+impl StubScreenSaver {
+    fn inhibit(&mut self, application_name: &str, reason: &str) -> u32 {
+        let mut calls = self.calls.lock().unwrap();
+        calls
+            .inhibited
+            .push((application_name.to_string(), reason.to_string()));
+        41 + calls.inhibited.len() as u32
+    }
+
+    fn un_inhibit(&mut self, cookie: u32) {
+        self.calls.lock().unwrap().uninhibited.push(cookie);
+    }
+}
+
+struct StubLogind {
+    calls: Arc<Mutex<Calls>>,
+    // The write end of the pipe whose read end is handed out
+    lock_writer: Arc<Mutex<Option<std::io::PipeWriter>>>,
+}
+
+#[zbus::interface(name = "org.freedesktop.login1.Manager")]
+impl StubLogind {
+    fn inhibit(
+        &mut self,
+        what: &str,
+        _who: &str,
+        _why: &str,
+        mode: &str,
+    ) -> zbus::fdo::Result<zbus::zvariant::OwnedFd> {
+        self.calls
+            .lock()
+            .unwrap()
+            .logind
+            .push((what.to_string(), mode.to_string()));
+        let (reader, writer) =
+            std::io::pipe().map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
+        *self.lock_writer.lock().unwrap() = Some(writer);
+        Ok(OwnedFd::from(reader).into())
+    }
+}
+
+#[test]
+fn test_inhibitor_holds_and_releases_both_locks() {
+    let Some(bus) = PrivateBus::start() else {
+        println!(
+            "Skipping test_inhibitor_holds_and_releases_both_locks: dbus-daemon not available"
+        );
+        return;
+    };
+
+    let calls = Arc::new(Mutex::new(Calls::default()));
+    let lock_writer = Arc::new(Mutex::new(None));
+    let _service = connection::Builder::address(bus.address.as_str())
+        .unwrap()
+        .name("org.freedesktop.ScreenSaver")
+        .unwrap()
+        .name("org.freedesktop.login1")
+        .unwrap()
+        .serve_at(
+            "/org/freedesktop/ScreenSaver",
+            StubScreenSaver {
+                calls: calls.clone(),
+            },
+        )
+        .unwrap()
+        .serve_at(
+            "/org/freedesktop/login1",
+            StubLogind {
+                calls: calls.clone(),
+                lock_writer: lock_writer.clone(),
+            },
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    // Use the private bus as both the session and the system bus
+    let inhibitor = Inhibitor::acquire_on(bus.connect(), bus.connect(), "testing").unwrap();
+
+    assert_eq!(inhibitor.screensaver_cookie(), Some(42));
+    assert!(inhibitor.holds_logind_lock());
+    {
+        let calls = calls.lock().unwrap();
+        assert_eq!(
+            calls.inhibited,
+            vec![("ktmm".to_string(), "testing".to_string())]
+        );
+        assert_eq!(
+            calls.logind,
+            vec![("idle:sleep".to_string(), "block".to_string())]
+        );
+        assert!(calls.uninhibited.is_empty());
+    }
+
+    // While the inhibitor holds the logind fd, the pipe stays open
+    let mut writer = lock_writer.lock().unwrap().take().unwrap();
+    writer.write_all(b"x").unwrap();
+
+    inhibitor.release().unwrap();
+
+    assert_eq!(calls.lock().unwrap().uninhibited, vec![42]);
+    // Once released, nobody holds the read end any more
+    assert_eq!(
+        writer.write_all(b"x").unwrap_err().kind(),
+        std::io::ErrorKind::BrokenPipe
+    );
+}
+
+#[test]
+fn test_inhibitor_works_with_only_the_screensaver() {
+    let Some(bus) = PrivateBus::start() else {
+        println!(
+            "Skipping test_inhibitor_works_with_only_the_screensaver: dbus-daemon not available"
+        );
+        return;
+    };
+
+    let calls = Arc::new(Mutex::new(Calls::default()));
+    let _service = connection::Builder::address(bus.address.as_str())
+        .unwrap()
+        .name("org.freedesktop.ScreenSaver")
+        .unwrap()
+        .serve_at(
+            "/org/freedesktop/ScreenSaver",
+            StubScreenSaver {
+                calls: calls.clone(),
+            },
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    let inhibitor = Inhibitor::acquire_on(bus.connect(), bus.connect(), "testing").unwrap();
+    assert_eq!(inhibitor.screensaver_cookie(), Some(42));
+    assert!(!inhibitor.holds_logind_lock());
+
+    // Dropping releases the inhibitor as well
+    drop(inhibitor);
+    assert_eq!(calls.lock().unwrap().uninhibited, vec![42]);
+}
+
+#[test]
+fn test_inhibitor_fails_without_any_service() {
+    let Some(bus) = PrivateBus::start() else {
+        println!("Skipping test_inhibitor_fails_without_any_service: dbus-daemon not available");
+        return;
+    };
+
+    assert!(Inhibitor::acquire_on(bus.connect(), bus.connect(), "testing").is_err());
+}
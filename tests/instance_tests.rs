@@ -0,0 +1,100 @@
+#![cfg(unix)]
+
+use ktmm::instance::{InstanceLock, LockAttempt};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+mod common;
+
+fn paths(name: &str) -> (PathBuf, PathBuf) {
+    let base = common::temp_path(name);
+    (base.with_extension("lock"), base.with_extension("pid"))
+}
+
+#[test]
+fn test_lock_is_exclusive_and_names_the_holder() {
+    let (lock_path, pid_path) = paths("exclusive");
+    let LockAttempt::Acquired(lock) = InstanceLock::try_acquire(&lock_path, &pid_path).unwrap()
+    else {
+        panic!("the lock should be free");
+    };
+    assert_eq!(
+        std::fs::read_to_string(&pid_path).unwrap().trim(),
+        std::process::id().to_string()
+    );
+
+    match InstanceLock::try_acquire(&lock_path, &pid_path).unwrap() {
+        LockAttempt::Held { pid } => assert_eq!(pid, Some(std::process::id())),
+        LockAttempt::Acquired(_) => panic!("the lock should be held"),
+    }
+
+    drop(lock);
+    assert!(!pid_path.exists());
+    assert!(matches!(
+        InstanceLock::try_acquire(&lock_path, &pid_path).unwrap(),
+        LockAttempt::Acquired(_)
+    ));
+}
+
+#[test]
+fn test_replace_gives_up_on_an_instance_that_does_not_exit() {
+    let (lock_path, pid_path) = paths("stubborn");
+    let LockAttempt::Acquired(_lock) = InstanceLock::try_acquire(&lock_path, &pid_path).unwrap()
+    else {
+        panic!("the lock should be free");
+    };
+    // Without a PID file there is nobody to signal, so the lock never frees up
+    std::fs::remove_file(&pid_path).unwrap();
+
+    let result = InstanceLock::replace(&lock_path, &pid_path, Duration::from_millis(200));
+    assert!(result.unwrap_err().to_string().contains("did not release"));
+}
+
+#[test]
+fn test_replace_takes_over_from_a_running_instance() {
+    let runtime_dir = common::temp_path("takeover");
+    std::fs::create_dir_all(&runtime_dir).unwrap();
+    let pid_path = runtime_dir.join("ktmm.pid");
+    let ktmm = || {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_ktmm"));
+        command
+            .env("XDG_RUNTIME_DIR", &runtime_dir)
+            .args(["--backend", "uinput"])
+            .stderr(Stdio::null());
+        command
+    };
+
+    let mut old = ktmm().args(["--", "sleep", "30"]).spawn().unwrap();
+    let started = Instant::now();
+    while !pid_path.exists() {
+        assert!(started.elapsed() < Duration::from_secs(5), "no PID file");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    // The new instance reports which PID holds the lock while it runs
+    let new = ktmm()
+        .args([
+            "--replace",
+            "--",
+            "sh",
+            "-c",
+            "cat \"$XDG_RUNTIME_DIR/ktmm.pid\"",
+        ])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let new_pid = new.id();
+    let output = new.wait_with_output().unwrap();
+
+    // The old instance passed SIGTERM on to its command and exited with it
+    assert_eq!(old.wait().unwrap().code(), Some(128 + libc::SIGTERM));
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        new_pid.to_string()
+    );
+    assert!(!pid_path.exists());
+
+    std::fs::remove_dir_all(&runtime_dir).unwrap();
+}
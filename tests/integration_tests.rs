@@ -1,4 +1,4 @@
-use ktmm::{MouseMover, MouseMoverConfig};
+use ktmm::{MouseMover, MouseMoverConfig, RecordingBackend};
 use std::env;
 
 // Helper function to detect if we're running in a headless environment
@@ -38,6 +38,7 @@ fn test_custom_config() {
         interval_secs: 30,
         movement_pixels: (2, 3),
         return_delay_ms: 10,
+        ..MouseMoverConfig::default()
     };
 
     let mover = MouseMover::new(config.clone());
@@ -54,6 +55,7 @@ fn test_custom_config() {
         interval_secs: 30,
         movement_pixels: (2, 3),
         return_delay_ms: 10,
+        ..MouseMoverConfig::default()
     };
 
     // Just test the config values directly without creating a MouseMover
@@ -77,11 +79,13 @@ fn test_permissions() {
     assert!(result.is_ok());
 }
 
-// Mock tests would be added here in a real implementation
-// For now, we'll just add a placeholder that always passes
+// This test verifies that the mover drives whatever input backend it is given
 #[test]
 fn test_mock_mouse_movement() {
-    // In a real implementation, we would use mockall to mock the dependencies
-    // and verify that the mouse movement functions are called correctly
-    assert!(true);
+    let backend = RecordingBackend::new((20, 30));
+    let mut mover = MouseMover::with_backend(MouseMoverConfig::default(), backend.clone());
+
+    mover.move_mouse_once().unwrap();
+
+    assert_eq!(backend.positions(), vec![(21, 31), (20, 30)]);
 }
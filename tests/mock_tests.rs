@@ -1,100 +1,28 @@
-use ktmm::MouseMoverConfig;
-use std::sync::{Arc, Mutex};
+use ktmm::backend::InputEvent;
+use ktmm::geometry::Rect;
+use ktmm::{MouseMover, MouseMoverConfig, RecordingBackend};
 
-// This is a mock test module that demonstrates how we would test
-// the mouse movement functionality without actually moving the mouse
-
-// Mock implementation of Enigo for testing
-struct MockEnigo {
-    positions: Arc<Mutex<Vec<(i32, i32)>>>,
-}
-
-impl MockEnigo {
-    fn new() -> Self {
-        Self {
-            positions: Arc::new(Mutex::new(Vec::new())),
-        }
-    }
-
-    fn mouse_move_to(&mut self, x: i32, y: i32) {
-        self.positions.lock().unwrap().push((x, y));
-    }
-
-    fn get_positions(&self) -> Vec<(i32, i32)> {
-        self.positions.lock().unwrap().clone()
-    }
-}
-
-// Mock implementation of DeviceState for testing
-struct MockDeviceState {
-    position: (i32, i32),
-}
-
-impl MockDeviceState {
-    fn new(position: (i32, i32)) -> Self {
-        Self { position }
-    }
-
-    fn get_mouse(&self) -> MockMouse {
-        MockMouse {
-            coords: self.position,
-        }
-    }
-}
-
-struct MockMouse {
-    coords: (i32, i32),
-}
-
-// Mock implementation of MouseMover for testing
-struct MockMouseMover {
-    config: MouseMoverConfig,
-    enigo: MockEnigo,
-    device_state: MockDeviceState,
-}
-
-impl MockMouseMover {
-    fn new(config: MouseMoverConfig, initial_position: (i32, i32)) -> Self {
-        Self {
-            config,
-            enigo: MockEnigo::new(),
-            device_state: MockDeviceState::new(initial_position),
-        }
-    }
-
-    fn move_mouse_once(&mut self) {
-        // Get current mouse position
-        let mouse_pos = self.device_state.get_mouse();
-        let (x, y) = mouse_pos.coords;
-
-        // Move mouse by the configured amount
-        let (dx, dy) = self.config.movement_pixels;
-        self.enigo.mouse_move_to(x + dx, y + dy);
-
-        // Move mouse back to original position
-        self.enigo.mouse_move_to(x, y);
-    }
-
-    fn get_mouse_positions(&self) -> Vec<(i32, i32)> {
-        self.enigo.get_positions()
-    }
-}
+// These tests drive the real MouseMover against the in-memory
+// RecordingBackend, so the mouse movement logic is exercised without
+// actually moving the mouse
 
 #[test]
 fn test_mock_mouse_movement() {
-    // Create a mock mouse mover with initial position (100, 100)
+    // Create a mouse mover with the pointer at (100, 100)
     let config = MouseMoverConfig {
         interval_secs: 1,
         movement_pixels: (5, 10),
         return_delay_ms: 1,
+        ..MouseMoverConfig::default()
     };
-    let mut mover = MockMouseMover::new(config, (100, 100));
+    let backend = RecordingBackend::new((100, 100));
+    let mut mover = MouseMover::with_backend(config, backend.clone());
 
     // Move the mouse once
-    mover.move_mouse_once();
+    mover.move_mouse_once().unwrap();
 
     // Check that the mouse was moved to the expected positions
-    let positions = mover.get_mouse_positions();
+    let positions = backend.positions();
     assert_eq!(positions.len(), 2);
     assert_eq!(positions[0], (105, 110)); // 100+5, 100+10
     assert_eq!(positions[1], (100, 100)); // Back to original position
@@ -102,21 +30,23 @@ fn test_mock_mouse_movement() {
 
 #[test]
 fn test_mock_multiple_movements() {
-    // Create a mock mouse mover with initial position (50, 50)
+    // Create a mouse mover with the pointer at (50, 50)
     let config = MouseMoverConfig {
         interval_secs: 1,
         movement_pixels: (2, 3),
         return_delay_ms: 1,
+        ..MouseMoverConfig::default()
     };
-    let mut mover = MockMouseMover::new(config, (50, 50));
+    let backend = RecordingBackend::new((50, 50));
+    let mut mover = MouseMover::with_backend(config, backend.clone());
 
     // Move the mouse multiple times
     for _ in 0..3 {
-        mover.move_mouse_once();
+        mover.move_mouse_once().unwrap();
     }
 
     // Check that the mouse was moved to the expected positions
-    let positions = mover.get_mouse_positions();
+    let positions = backend.positions();
     assert_eq!(positions.len(), 6); // 3 movements, 2 positions each
 
     // First movement
@@ -131,3 +61,59 @@ fn test_mock_multiple_movements() {
     assert_eq!(positions[4], (52, 53)); // 50+2, 50+3
     assert_eq!(positions[5], (50, 50)); // Back to original position
 }
+
+#[test]
+fn test_mock_backend_records_absolute_moves() {
+    let backend = RecordingBackend::new((0, 0));
+    let mut mover = MouseMover::with_backend(MouseMoverConfig::default(), backend.clone());
+
+    mover.move_mouse_once().unwrap();
+
+    assert_eq!(
+        backend.events(),
+        vec![InputEvent::MoveTo(1, 1), InputEvent::MoveTo(0, 0)]
+    );
+}
+
+#[test]
+fn test_mock_backend_follows_user_movement() {
+    let backend = RecordingBackend::new((10, 10));
+    let mut mover = MouseMover::with_backend(MouseMoverConfig::default(), backend.clone());
+
+    // The user moves the pointer between two nudges
+    mover.move_mouse_once().unwrap();
+    backend.set_position(300, 400);
+    mover.move_mouse_once().unwrap();
+
+    assert_eq!(
+        backend.positions(),
+        vec![(11, 11), (10, 10), (301, 401), (300, 400)]
+    );
+}
+
+#[test]
+fn test_mock_restore_skipped_after_user_grab() {
+    let backend = RecordingBackend::new((10, 10));
+    let mut mover = MouseMover::with_backend(MouseMoverConfig::default(), backend.clone());
+
+    // The user grabs the mouse during the first nudge's return delay
+    backend.interrupt_next_move(50, 60);
+    mover.move_mouse_once().unwrap();
+    // The next nudge starts from where the user left the pointer
+    mover.move_mouse_once().unwrap();
+
+    assert_eq!(backend.positions(), vec![(11, 11), (51, 61), (50, 60)]);
+    assert_eq!(backend.position(), (50, 60));
+}
+
+#[test]
+fn test_mock_nudge_flips_at_screen_corner() {
+    let backend = RecordingBackend::new((1919, 1079));
+    backend.set_monitors(vec![Rect::new(0, 0, 1920, 1080)]);
+    let mut mover = MouseMover::with_backend(MouseMoverConfig::default(), backend.clone());
+
+    mover.move_mouse_once().unwrap();
+
+    // The nudge goes up and left instead of off the screen, then comes back
+    assert_eq!(backend.positions(), vec![(1918, 1078), (1919, 1079)]);
+}
@@ -0,0 +1,107 @@
+#![cfg(target_os = "linux")]
+
+use ktmm::daemon::{self, Request};
+use ktmm::systemd::Notifier;
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+mod common;
+
+/// A datagram socket standing in for systemd's notification socket
+fn notify_socket(name: &str) -> (UnixDatagram, PathBuf) {
+    let path = common::temp_path(name).with_extension("notify");
+    let _ = std::fs::remove_file(&path);
+    let socket = UnixDatagram::bind(&path).unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    (socket, path)
+}
+
+fn receive(socket: &UnixDatagram) -> String {
+    let mut buf = [0; 1024];
+    let len = socket.recv(&mut buf).unwrap();
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+#[test]
+fn test_notifications_are_sent_as_datagrams() {
+    let (socket, path) = notify_socket("messages");
+    let notifier = Notifier::new(path.to_str().unwrap()).unwrap();
+
+    notifier.ready("Waiting\nto nudge").unwrap();
+    assert_eq!(receive(&socket), "READY=1\nSTATUS=Waiting to nudge");
+    notifier.status("Last nudge at 12:00:00").unwrap();
+    assert_eq!(receive(&socket), "STATUS=Last nudge at 12:00:00");
+    notifier.watchdog().unwrap();
+    assert_eq!(receive(&socket), "WATCHDOG=1");
+    notifier.stopping().unwrap();
+    assert_eq!(receive(&socket), "STOPPING=1");
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_abstract_notify_socket() {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::SocketAddr;
+
+    let name = format!("ktmm-test-abstract-{}", std::process::id());
+    let socket =
+        UnixDatagram::bind_addr(&SocketAddr::from_abstract_name(name.as_bytes()).unwrap()).unwrap();
+    let notifier = Notifier::new(&format!("@{}", name)).unwrap();
+    notifier.watchdog().unwrap();
+    assert_eq!(receive(&socket), "WATCHDOG=1");
+}
+
+#[test]
+fn test_daemon_notifies_ready_and_stopping() {
+    let (socket, path) = notify_socket("daemon");
+    let runtime_dir = common::temp_path("daemon-runtime");
+    std::fs::create_dir_all(&runtime_dir).unwrap();
+
+    // Outside its schedule the inhibitor starts without needing D-Bus
+    let mut daemon = Command::new(env!("CARGO_BIN_EXE_ktmm"))
+        .env("XDG_RUNTIME_DIR", &runtime_dir)
+        .env("NOTIFY_SOCKET", &path)
+        .args([
+            "--quiet",
+            "--mode",
+            "inhibit",
+            "--cron",
+            "0 0 30 2 *",
+            "daemon",
+        ])
+        .spawn()
+        .unwrap();
+
+    let ready = receive(&socket);
+    assert!(ready.starts_with("READY=1\nSTATUS="), "{}", ready);
+    daemon::send(&runtime_dir.join("ktmm.sock"), Request::Stop).unwrap();
+    assert!(daemon.wait().unwrap().success());
+    assert_eq!(receive(&socket), "STOPPING=1");
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_install_service_writes_a_user_unit() {
+    let config_home = common::temp_path("service");
+    let _ = std::fs::remove_dir_all(&config_home);
+    let install = || {
+        Command::new(env!("CARGO_BIN_EXE_ktmm"))
+            .env("XDG_CONFIG_HOME", &config_home)
+            .args(["install-service", "--profile", "work"])
+            .output()
+            .unwrap()
+    };
+
+    assert!(install().status.success());
+    let unit = std::fs::read_to_string(config_home.join("systemd/user/ktmm.service")).unwrap();
+    assert!(unit.contains("Type=notify\n"));
+    assert!(unit.contains(" --profile work daemon\n"), "{}", unit);
+
+    // An existing unit is only replaced with --force
+    assert!(!install().status.success());
+    let _ = std::fs::remove_dir_all(&config_home);
+}
@@ -0,0 +1,45 @@
+#![cfg(target_os = "linux")]
+
+use ktmm::uinput::{decode_events, UinputBackend, EV_REL, EV_SYN, REL_X, REL_Y, SYN_REPORT};
+use ktmm::{KtmmError, MouseMover, MouseMoverConfig};
+use std::fs::{self, File};
+use std::path::Path;
+
+// The mover should nudge a relative-only backend there and back
+#[test]
+fn test_mouse_mover_writes_uinput_events_to_file() {
+    let path = std::env::temp_dir().join(format!("ktmm-uinput-{}.bin", std::process::id()));
+    let file = File::create(&path).unwrap();
+    let config = MouseMoverConfig {
+        movement_pixels: (1, 2),
+        return_delay_ms: 0,
+        ..MouseMoverConfig::default()
+    };
+    let mut mover = MouseMover::with_backend(config, UinputBackend::from_writer(file));
+
+    mover.move_mouse_once().unwrap();
+    drop(mover);
+
+    let bytes = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(
+        decode_events(&bytes),
+        vec![
+            (EV_REL, REL_X, 1),
+            (EV_REL, REL_Y, 2),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_REL, REL_X, -1),
+            (EV_REL, REL_Y, -2),
+            (EV_SYN, SYN_REPORT, 0),
+        ]
+    );
+}
+
+#[test]
+fn test_missing_uinput_device_is_diagnosed() {
+    match UinputBackend::open_path(Path::new("/nonexistent/uinput")) {
+        Err(KtmmError::PlatformError(msg)) => assert!(msg.contains("modprobe uinput"), "{}", msg),
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("opening a missing device should fail"),
+    }
+}
@@ -0,0 +1,113 @@
+use common::config;
+use ktmm::{MouseMover, RecordingBackend};
+use std::process::Command;
+use std::time::Duration;
+
+mod common;
+
+#[test]
+fn test_run_while_returns_the_task_result() {
+    let backend = RecordingBackend::new((10, 10));
+    let mut mover = MouseMover::with_backend(config(1), backend.clone());
+
+    let result = mover
+        .run_while(|| {
+            std::thread::sleep(Duration::from_millis(1500));
+            42
+        })
+        .unwrap();
+
+    assert_eq!(result, 42);
+    // One nudge happened while the task ran
+    assert_eq!(backend.positions(), vec![(11, 11), (10, 10)]);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_run_command_returns_the_exit_status() {
+    let backend = RecordingBackend::new((10, 10));
+    let mut mover = MouseMover::with_backend(config(60), backend.clone());
+
+    let status = mover
+        .run_command(Command::new("sh").args(["-c", "exit 3"]))
+        .unwrap();
+
+    assert_eq!(status.code(), Some(3));
+    assert!(backend.events().is_empty());
+
+    assert!(mover
+        .run_command(&mut Command::new("ktmm-test-no-such-command"))
+        .is_err());
+}
+
+// The binary keeps running the command even if it cannot keep the system
+// awake (no /dev/uinput access in CI), so its exit status is still passed on
+#[cfg(unix)]
+#[test]
+fn test_binary_exits_with_the_command_status() {
+    let status = Command::new(env!("CARGO_BIN_EXE_ktmm"))
+        .args(["--backend", "uinput", "--", "sh", "-c", "exit 7"])
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(7));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_ktmm"))
+        .args(["--backend", "uinput", "--", "ktmm-test-no-such-command"])
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(127));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_binary_forwards_signals_to_the_command() {
+    let mut ktmm = Command::new(env!("CARGO_BIN_EXE_ktmm"))
+        .args(["--backend", "uinput", "--", "sleep", "30"])
+        .spawn()
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(500));
+    unsafe {
+        libc::kill(ktmm.id() as libc::pid_t, libc::SIGTERM);
+    }
+
+    let status = ktmm.wait().unwrap();
+    assert_eq!(status.code(), Some(128 + libc::SIGTERM));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_only_runs_that_keep_nudging_are_kept_to_one_instance() {
+    use ktmm::instance::{InstanceLock, LockAttempt};
+
+    let runtime_dir = common::temp_path("instance");
+    std::fs::create_dir_all(&runtime_dir).unwrap();
+    // Stand in for a running instance
+    let LockAttempt::Acquired(_lock) = InstanceLock::try_acquire(
+        &runtime_dir.join("ktmm.lock"),
+        &runtime_dir.join("ktmm.pid"),
+    )
+    .unwrap() else {
+        panic!("the lock should be free");
+    };
+    let refused = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_ktmm"))
+            .env("XDG_RUNTIME_DIR", &runtime_dir)
+            .args(["--backend", "uinput"])
+            .args(args)
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        let refused = stderr.contains(&format!("already running (PID {})", std::process::id()));
+        assert!(!refused || output.status.code() == Some(1), "{}", stderr);
+        refused
+    };
+
+    assert!(refused(&[]));
+    assert!(refused(&["--", "sh", "-c", "exit 7"]));
+    let mut child = Command::new("sleep").arg("0.5").spawn().unwrap();
+    assert!(refused(&["--wait-pid", &child.id().to_string()]));
+    child.wait().unwrap();
+
+    assert!(!refused(&["--once"]));
+    assert!(!refused(&["--mode", "inhibit", "--duration", "1s"]));
+}